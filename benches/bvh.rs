@@ -0,0 +1,91 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use pathtracer::hittables::{BvhNode, BvhSettings, FlatBvh, HittableList, WideBvh};
+use pathtracer::misc::Interval;
+use pathtracer::primitive::*;
+use pathtracer::transform::MediumStack;
+use pathtracer::*;
+use rand::{Rng, SeedableRng};
+use std::sync::Arc;
+
+// Traversal of the recursive Bvh compared to the flattened and the wide one, on the same triangles and rays
+
+pub fn criterion_benchmark(c: &mut Criterion) {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+
+    let mut point = |range: f32| {
+        V3::new(
+            rng.gen_range(-range..range),
+            rng.gen_range(-range..range),
+            rng.gen_range(-range..range),
+        )
+    };
+
+    let triangles: Vec<Arc<Triangle>> = (0..50000)
+        .map(|_| {
+            let a = point(50.0);
+            Arc::new(Triangle::new(
+                V3::new(a, a + point(1.0), a + point(1.0)),
+                None,
+                0,
+            ))
+        })
+        .collect();
+
+    let mut hittable = HittableList::new();
+    for triangle in &triangles {
+        hittable.add_shared(triangle.clone());
+    }
+
+    let setting = RaySetting {
+        background_color: V3::new(0.0, 0.0, 0.0),
+        depth: 1,
+        ray_time: Interval {
+            min: 0.0,
+            max: 2048.0,
+        },
+    };
+
+    let rays: Vec<Ray> = (0..4096)
+        .map(|_| Ray {
+            origin: point(60.0),
+            end: point(1.0),
+            time: 0.0,
+            cone: RayCone::default(),
+            channel: None,
+            media: MediumStack::default(),
+            setting: &setting,
+            materials: None,
+        })
+        .collect();
+
+    let tree = BvhNode::new(&hittable);
+    let flat = FlatBvh::from_tree(&tree);
+    let wide = WideBvh::from_triangles(&triangles, BvhSettings::default());
+
+    let mut group = c.benchmark_group("bvh");
+    group.bench_function("tree", |b| {
+        b.iter(|| {
+            rays.iter()
+                .filter(|ray| tree.hit(black_box(ray)).is_some())
+                .count()
+        })
+    });
+    group.bench_function("flat", |b| {
+        b.iter(|| {
+            rays.iter()
+                .filter(|ray| flat.hit(black_box(ray)).is_some())
+                .count()
+        })
+    });
+    group.bench_function("wide", |b| {
+        b.iter(|| {
+            rays.iter()
+                .filter(|ray| wide.hit(black_box(ray)).is_some())
+                .count()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
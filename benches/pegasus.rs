@@ -1,8 +1,10 @@
 use std::time::Duration;
 
 use criterion::{criterion_group, criterion_main, Criterion};
-use pathtracer::hittables::BvhNode;
+use pathtracer::hittables::BvhSettings;
+use pathtracer::hittables::FlatBvh;
 use pathtracer::hittables::HittableList;
+use pathtracer::hittables::SplitMethod;
 use pathtracer::material::*;
 use pathtracer::misc::Interval;
 use pathtracer::primitive::*;
@@ -60,28 +62,18 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         material: red,
     });
 
-    hittable.add(
-        XZrect {
-            x0: 0.595,
-            x1: 1.105,
-            z0: 0.595,
-            z1: 1.105,
-            k: 1.69,
-            material: light,
-        }
-        .flip_face(),
-    );
-
-    lights.add(
-        XZrect {
-            x0: 0.595,
-            x1: 1.105,
-            z0: 0.595,
-            z1: 1.105,
-            k: 1.69,
-            material: light,
-        }
-        .flip_face(),
+    lights.add_shared(
+        hittable.add(
+            XZrect {
+                x0: 0.595,
+                x1: 1.105,
+                z0: 0.595,
+                z1: 1.105,
+                k: 1.69,
+                material: light,
+            }
+            .flip_face(),
+        ),
     );
 
     hittable.add(XZrect {
@@ -111,9 +103,22 @@ pub fn criterion_benchmark(c: &mut Criterion) {
         material: white,
     });
 
+    let pegasus = Mesh::new("assets/pegasus.obj", glass_material).unwrap();
+
+    for (name, split) in [
+        ("median", SplitMethod::Median),
+        ("sah", SplitMethod::Sah { bins: 16 }),
+    ] {
+        let settings = BvhSettings {
+            split,
+            ..BvhSettings::default()
+        };
+        let stats = pegasus.clone().with_bvh_settings(settings).bvh_stats(1.0);
+        println!("pegasus bvh ({name}): {stats:?}");
+    }
+
     hittable.add(
-        Mesh::new("assets/pegasus.obj", glass_material)
-            .unwrap()
+        pegasus
             //.rotate(V3::new(0.0, 1.0, 0.0), 0.8)
             .rotate(V3::new(0.0, 1.0, 0.0), 3.1415)
             .translate(V3::new(0.85, 0.0, 0.85)),
@@ -127,7 +132,7 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             background_color: V3::new(0.0, 0.0, 0.0),
             depth: 16,
             ray_time: Interval {
-                min: 0.001,
+                min: 0.0,
                 max: 2048.0,
             },
         },
@@ -146,7 +151,7 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             0.0,                       //time0
             1.0,                       //time1
         ),
-        world: BvhNode::new(&hittable),
+        world: FlatBvh::new(&hittable),
         lights: Some(lights),
         materials,
     };
@@ -1,7 +1,7 @@
 use std::time::Duration;
 
 use criterion::{criterion_group, criterion_main, Criterion};
-use pathtracer::hittables::BvhNode;
+use pathtracer::hittables::FlatBvh;
 use pathtracer::hittables::HittableList;
 use pathtracer::material::*;
 use pathtracer::misc::Interval;
@@ -54,7 +54,7 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             background_color: V3::new(0.5, 0.7, 1.0),
             depth: 32,
             ray_time: Interval {
-                min: 0.001,
+                min: 0.0,
                 max: 2048.0,
             },
         },
@@ -73,7 +73,7 @@ pub fn criterion_benchmark(c: &mut Criterion) {
             0.0,                     //time0
             1.0,                     //time1
         ),
-        world: BvhNode::new(&hittable),
+        world: FlatBvh::new(&hittable),
         lights: None,
         materials,
     };
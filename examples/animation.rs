@@ -0,0 +1,118 @@
+use pathtracer::hittables::*;
+use pathtracer::material::*;
+use pathtracer::misc::*;
+use pathtracer::texture::*;
+use pathtracer::transform::Transform;
+use pathtracer::*;
+use rand::Rng;
+use std::sync::Arc;
+
+// Clusters of spheres orbiting over a static field, only the top level of the Tlas is refitted between frames
+
+fn main() {
+    let mut materials = MaterialContainer::default();
+    let mut lights = HittableList::new();
+    let mut seed = rand::thread_rng();
+
+    let color = |materials: &mut MaterialContainer, color: V3<f32>| {
+        materials.add(Lambertian::new(Box::new(ConstantTexture { color })))
+    };
+
+    let mut field = HittableList::new();
+    field.add(Sphere::new(
+        V3::new(0.0, -1000.0, 0.0),
+        1000.0,
+        color(&mut materials, V3::new(0.5, 0.5, 0.5)),
+    ));
+    let grass = color(&mut materials, V3::new(0.2, 0.4, 0.1));
+    for _ in 0..2000 {
+        let center = V3::new(
+            seed.gen_range(-10.0..10.0),
+            0.0,
+            seed.gen_range(-10.0..10.0),
+        );
+        field.add(Sphere::new(center, seed.gen_range(0.05..0.15), grass));
+    }
+
+    let mut cluster = HittableList::new();
+    let red = color(&mut materials, V3::new(0.7, 0.1, 0.1));
+    let glass = materials.add(Dielectric::new(1.5));
+    for _ in 0..50 {
+        let center = V3::new(
+            seed.gen_range(-0.5..0.5),
+            seed.gen_range(-0.5..0.5),
+            seed.gen_range(-0.5..0.5),
+        );
+        let material = if seed.gen::<f32>() < 0.5 { red } else { glass };
+        cluster.add(Sphere::new(center, 0.15, material));
+    }
+    let cluster: Arc<dyn HittablePdf> = Arc::new(FlatBvh::new(&cluster));
+
+    let light: Arc<dyn HittablePdf> = Arc::new(XZrect::new(
+        -3.0,
+        3.0,
+        -3.0,
+        3.0,
+        8.0,
+        materials.add(LightSource::new(Box::new(ConstantTexture {
+            color: V3::new(15.0, 15.0, 15.0),
+        }))),
+    ));
+    lights.add_shared(light.clone());
+
+    let mut world = Tlas::new();
+    world.add(Arc::new(FlatBvh::new(&field)), Transform::identity());
+    world.add(light, Transform::identity());
+    let clusters: Vec<usize> = (0..3)
+        .map(|_| world.add(cluster.clone(), Transform::identity()))
+        .collect();
+
+    let mut scene = Scene {
+        camera: Camera::new(
+            V3::new(13.0, 4.0, 3.0), //lookfrom
+            V3::new(0.0, 1.0, 0.0),  //lookat
+            V3::new(0.0, 1.0, 0.0),  //vup
+            30.0,                    //vertical_fov
+            2.0,                     //aspect
+            0.0,                     //aperture
+            10.0,                    //focus_dist
+            0.0,                     //time0
+            1.0,                     //time1
+        ),
+        world,
+        lights: Some(lights),
+        materials,
+    };
+
+    let image_config = ImageConfig {
+        nx: 512,
+        ny: 256,
+        samples_per_pixel: 64,
+        ray_setting: RaySetting {
+            background_color: V3::new(0.1, 0.1, 0.2),
+            depth: 8,
+            ray_time: Interval {
+                min: 0.0,
+                max: 2048.0,
+            },
+        },
+        name: "animation",
+    };
+
+    for frame in 0..24 {
+        let angle = frame as f32 / 24.0 * std::f32::consts::TAU;
+        for (index, &instance) in clusters.iter().enumerate() {
+            let phase = angle + index as f32 * std::f32::consts::TAU / 3.0;
+            let transform = Transform::rotation(V3::new(0.0, 1.0, 0.0), angle * 2.0).then(
+                Transform::translation(V3::new(3.0 * phase.cos(), 1.5, 3.0 * phase.sin())),
+            );
+            scene.world.set_transform(instance, transform);
+        }
+        scene.world.refit();
+
+        scene
+            .render(image_config)
+            .write_file(&format!("{}_{:02}.ppm", image_config.name, frame))
+            .expect("Couldn't write frame");
+    }
+}
@@ -1,4 +1,4 @@
-use pathtracer::hittables::{BvhNode, HittableList};
+use pathtracer::hittables::{FlatBvh, HittableList};
 use pathtracer::material::{Dielectric, Lambertian, MaterialContainer, Metalic};
 use pathtracer::misc::{Interval, Perlin};
 use pathtracer::primitive::{MovingSphere, Sphere};
@@ -18,6 +18,7 @@ pub fn main() {
         even: Box::new(ConstantTexture {
             color: V3::new(0.9, 0.9, 0.9),
         }),
+        frequency: 10.0,
     };
 
     let perlin_texture = PerlinNoiseTexture {
@@ -88,7 +89,7 @@ pub fn main() {
     ));
     hittable2.add(Sphere::new(V3::new(0.0, 1.0, 0.0), 1.0, glass_material));
 
-    hittable.add(BvhNode::new(&hittable2));
+    hittable.add(FlatBvh::new(&hittable2));
 
     let image_config = ImageConfig {
         nx: 2048,
@@ -98,7 +99,7 @@ pub fn main() {
             background_color: V3::new(0.5, 0.7, 1.0),
             depth: 32,
             ray_time: Interval {
-                min: 0.001,
+                min: 0.0,
                 max: 2048.0,
             },
         },
@@ -117,7 +118,7 @@ pub fn main() {
             0.0,                     //time0
             1.0,                     //time1
         ),
-        world: BvhNode::new(&hittable),
+        world: FlatBvh::new(&hittable),
         lights: None,
         materials,
     };
@@ -30,8 +30,9 @@ fn main() {
 
     hittable.add(YZrect::new(0.0, 555.0, 0.0, 555.0, 0.0, red));
 
-    hittable.add(XZrect::new(213.0, 343.0, 227.0, 332.0, 554.0, light).flip_face());
-    lights.add(XZrect::new(213.0, 343.0, 227.0, 332.0, 554.0, light).flip_face());
+    lights.add_shared(
+        hittable.add(XZrect::new(213.0, 343.0, 227.0, 332.0, 554.0, light).flip_face()),
+    );
 
     hittable.add(XZrect::new(0.0, 555.0, 0.0, 555.0, 0.0, white));
 
@@ -59,7 +60,7 @@ fn main() {
             background_color: V3::new(0.0, 0.0, 0.0),
             depth: 32,
             ray_time: Interval {
-                min: 0.001,
+                min: 0.0,
                 max: 2048.0,
             },
         },
@@ -78,7 +79,7 @@ fn main() {
             0.0,                           //time0
             1.0,                           //time1
         ),
-        world: BvhNode::new(&hittable),
+        world: FlatBvh::new(&hittable),
         lights: Some(lights),
         materials,
     }
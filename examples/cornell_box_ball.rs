@@ -33,7 +33,9 @@ fn main() {
 
     hittable.add(YZrect::new(0.0, 555.0, 0.0, 555.0, 0.0, red));
 
-    hittable.add(XZrect::new(213.0, 343.0, 227.0, 332.0, 554.0, light).flip_face());
+    lights.add_shared(
+        hittable.add(XZrect::new(213.0, 343.0, 227.0, 332.0, 554.0, light).flip_face()),
+    );
 
     hittable.add(XZrect::new(0.0, 555.0, 0.0, 555.0, 0.0, white));
 
@@ -61,15 +63,7 @@ fn main() {
         .translate(V3::new(130.0, 0.0, 65.0)),
     );*/
 
-    hittable.add(Sphere::new(V3::new(190.0, 90.0, 190.0), 90.0, glass));
-
-    lights.add(XZrect::new(213.0, 343.0, 227.0, 332.0, 554.0, light).flip_face());
-
-    lights.add(Sphere {
-        center: V3::new(190.0, 90.0, 190.0),
-        radius: 90.0,
-        material: glass,
-    });
+    lights.add_shared(hittable.add(Sphere::new(V3::new(190.0, 90.0, 190.0), 90.0, glass)));
 
     let image_config = ImageConfig {
         nx: 1024,
@@ -79,7 +73,7 @@ fn main() {
             background_color: V3::new(0.0, 0.0, 0.0),
             depth: 32,
             ray_time: Interval {
-                min: 0.001,
+                min: 0.0,
                 max: 2048.0,
             },
         },
@@ -98,7 +92,7 @@ fn main() {
             0.0,                           //time0
             1.0,                           //time1
         ),
-        world: BvhNode::new(&hittable),
+        world: FlatBvh::new(&hittable),
         lights: Some(lights),
         materials,
     }
@@ -41,7 +41,7 @@ fn main() {
             background_color: V3::new(0.5, 0.7, 1.0),
             depth: 16,
             ray_time: Interval {
-                min: 0.001,
+                min: 0.0,
                 max: 2048.0,
             },
         },
@@ -60,7 +60,7 @@ fn main() {
             0.0,                     //time0
             1.0,                     //time1
         ),
-        world: BvhNode::new(&hittable),
+        world: FlatBvh::new(&hittable),
         lights: None,
         materials,
     }
@@ -13,9 +13,9 @@ fn main() {
     hittable.add(Sphere::new(
         V3::new(0.0, 0.0, 0.0),
         2.0,
-        materials.add(Lambertian::new(Box::new(ImageTexture::new(
-            "assets/earthmap.png",
-        )))),
+        materials.add(Lambertian::new(Box::new(
+            ImageTexture::new("assets/earthmap.png").unwrap(),
+        ))),
     ));
     let mut seed = rand::thread_rng();
 
@@ -27,8 +27,7 @@ fn main() {
         }))),
     );
 
-    hittable.add(light.clone());
-    lights.add(light);
+    lights.add_shared(hittable.add(light));
 
     let image_config = ImageConfig {
         nx: 2048,
@@ -38,7 +37,7 @@ fn main() {
             background_color: V3::new(0.0, 0.0, 0.0),
             depth: 4,
             ray_time: Interval {
-                min: 0.001,
+                min: 0.0,
                 max: 2048.0,
             },
         },
@@ -57,7 +56,7 @@ fn main() {
             0.0,                     //time0
             1.0,                     //time1
         ),
-        world: BvhNode::new(&hittable),
+        world: FlatBvh::new(&hittable),
         lights: Some(lights),
         materials,
     }
@@ -35,18 +35,17 @@ fn main() {
             V3::new(x0, y0, z0),
             V3::new(x1, y1, z1),
             ground,
-        ))
+        ));
     });
 
-    objects.add(BvhNode::new(&boxes));
+    objects.add(FlatBvh::new(&boxes));
 
     let light = materials.add(LightSource::new(Box::new(ConstantTexture {
         color: V3::new(7.0, 7.0, 7.0),
     })));
 
-    objects.add(XZrect::new(123.0, 423.0, 147.0, 412.0, 554.0, light).flip_face());
-
-    lights.add(XZrect::new(123.0, 423.0, 147.0, 412.0, 554.0, light).flip_face());
+    lights
+        .add_shared(objects.add(XZrect::new(123.0, 423.0, 147.0, 412.0, 554.0, light).flip_face()));
 
     let moving_sphere_material = ConstantTexture {
         color: V3::new(0.7, 0.3, 0.1),
@@ -92,9 +91,9 @@ fn main() {
     objects.add(Sphere::new(
         V3::new(400.0, 200.0, 400.0),
         100.0,
-        materials.add(Lambertian::new(Box::new(ImageTexture::new(
-            "assets/earthmap.png",
-        )))),
+        materials.add(Lambertian::new(Box::new(
+            ImageTexture::new("assets/earthmap.png").unwrap(),
+        ))),
     ));
 
     objects.add(Sphere::new(
@@ -133,7 +132,7 @@ fn main() {
     });
 
     objects.add(
-        BvhNode::new(&boxes2)
+        FlatBvh::new(&boxes2)
             .rotate(V3::new(0.0, 1.0, 0.0), 0.261_799_4)
             .translate(V3::new(-100.0, 270.0, 395.0)),
     );
@@ -146,7 +145,7 @@ fn main() {
             background_color: V3::new(0.0, 0.0, 0.0),
             depth: 32,
             ray_time: Interval {
-                min: 0.001,
+                min: 0.0,
                 max: 2048.0,
             },
         },
@@ -165,7 +164,7 @@ fn main() {
             0.0,                           //time0
             1.0,                           //time1
         ),
-        world: BvhNode::new(&objects),
+        world: FlatBvh::new(&objects),
         lights: Some(lights),
         materials,
     };
@@ -20,34 +20,21 @@ fn main() {
 
     let glass_material = materials.add(Dielectric::new(1.5));
 
-    hittable.add(
-        XYrect::new(
-            -5.0,
-            5.0,
-            -5.0,
-            5.0,
-            0.0,
-            materials.add(LightSource::new(Box::new(PlasmaTexture {
-                param: seed.gen::<f32>() * 100.0,
-                scale,
-            }))),
-        )
-        .translate(V3::new(0.0, 0.0, -10.0)),
-    );
-
-    lights.add(
-        XYrect::new(
-            -5.0,
-            5.0,
-            -5.0,
-            5.0,
-            0.0,
-            materials.add(LightSource::new(Box::new(PlasmaTexture {
-                param: seed.gen::<f32>() * 100.0,
-                scale,
-            }))),
-        )
-        .translate(V3::new(0.0, 0.0, -10.0)),
+    lights.add_shared(
+        hittable.add(
+            XYrect::new(
+                -5.0,
+                5.0,
+                -5.0,
+                5.0,
+                0.0,
+                materials.add(LightSource::new(Box::new(PlasmaTexture {
+                    param: seed.gen::<f32>() * 100.0,
+                    scale,
+                }))),
+            )
+            .translate(V3::new(0.0, 0.0, -10.0)),
+        ),
     );
 
     hittable.add(
@@ -64,7 +51,7 @@ fn main() {
             background_color: V3::new(0.0, 0.0, 0.0),
             depth: 32,
             ray_time: Interval {
-                min: 0.001,
+                min: 0.0,
                 max: 2048.0,
             },
         },
@@ -83,7 +70,7 @@ fn main() {
             0.0,                     //time0
             1.0,                     //time1
         ),
-        world: BvhNode::new(&hittable),
+        world: FlatBvh::new(&hittable),
         lights: None,
         materials,
     }
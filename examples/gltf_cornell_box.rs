@@ -0,0 +1,25 @@
+use pathtracer::misc::Interval;
+use pathtracer::*;
+
+// Cornell box loaded from .gltf file, with its camera, point light, embedded texture and instanced boxes
+
+fn main() {
+    let scene = Scene::load_gltf("assets/cornell_box.gltf", 1.0).unwrap();
+
+    let image_config = ImageConfig {
+        nx: 512,
+        ny: 512,
+        samples_per_pixel: 16,
+        ray_setting: RaySetting {
+            background_color: V3::new(0.0, 0.0, 0.0),
+            depth: 32,
+            ray_time: Interval {
+                min: 0.0,
+                max: 2048.0,
+            },
+        },
+        name: "gltf_cornell_box",
+    };
+
+    scene.loop_render(image_config, 12);
+}
@@ -0,0 +1,116 @@
+use pathtracer::hittables::*;
+use pathtracer::material::*;
+use pathtracer::misc::{FractalMode, FractalNoise, Interval, NoiseBasis};
+use pathtracer::primitive::*;
+use pathtracer::texture::*;
+use pathtracer::*;
+
+fn main() {
+    let mut hittable = HittableList::new();
+    let mut materials = MaterialContainer::default();
+
+    let marble = ColorRamp::new(
+        NoiseTexture::new(
+            FractalNoise::new(1).with_mode(FractalMode::Ridged),
+            NoisePattern::Marble {
+                frequency: 6.0,
+                distortion: 2.0,
+            },
+            2.0,
+        ),
+        vec![
+            (0.0, V3::new(0.25, 0.25, 0.3)),
+            (0.6, V3::new(0.85, 0.85, 0.8)),
+            (1.0, V3::new(0.95, 0.95, 0.95)),
+        ],
+    );
+
+    let wood = ColorRamp::new(
+        NoiseTexture::new(
+            FractalNoise::new(2).with_octaves(3, 2.0, 0.5),
+            NoisePattern::Wood {
+                rings: 8.0,
+                distortion: 0.3,
+            },
+            1.0,
+        ),
+        vec![
+            (0.0, V3::new(0.55, 0.33, 0.15)),
+            (0.8, V3::new(0.35, 0.18, 0.07)),
+            (1.0, V3::new(0.55, 0.33, 0.15)),
+        ],
+    );
+
+    let clouds = MixTexture::new(
+        ConstantTexture {
+            color: V3::new(0.3, 0.5, 0.9),
+        },
+        ConstantTexture {
+            color: V3::new(1.0, 1.0, 1.0),
+        },
+        NoiseTexture::new(
+            FractalNoise::new(3)
+                .with_basis(NoiseBasis::Simplex)
+                .with_warp(0.5)
+                .with_time(0.0),
+            NoisePattern::Clouds {
+                coverage: 0.45,
+                softness: 0.2,
+            },
+            3.0,
+        ),
+    );
+
+    for (x, texture) in [
+        (-1.1, Box::new(marble) as Box<dyn Texture>),
+        (0.0, Box::new(wood)),
+        (1.1, Box::new(clouds)),
+    ] {
+        hittable.add(Sphere::new(
+            V3::new(x, 0.0, -2.5),
+            0.5,
+            materials.add(Lambertian::new(texture)),
+        ));
+    }
+
+    hittable.add(Sphere::new(
+        V3::new(0.0, -100.5, -2.0),
+        100.0,
+        materials.add(Lambertian::new(Box::new(ConstantTexture {
+            color: V3::new(0.5, 0.5, 0.5),
+        }))),
+    ));
+
+    let image_config = ImageConfig {
+        nx: 2048,
+        ny: 1024,
+        samples_per_pixel: 2,
+        ray_setting: RaySetting {
+            background_color: V3::new(0.5, 0.7, 1.0),
+            depth: 32,
+            ray_time: Interval {
+                min: 0.0,
+                max: 2048.0,
+            },
+        },
+        name: "noise_textures",
+    };
+
+    Scene {
+        camera: Camera::new(
+            V3::new(0.0, 0.0, 0.0),  //lookfrom
+            V3::new(0.0, 0.0, -1.0), //lookat
+            V3::new(0.0, 1.0, 0.0),  //vup
+            45.0,                    //vertical_fov
+            2.0,                     //aspect
+            0.0,                     //aperture
+            2.5,                     //focus_dist
+            0.0,                     //time0
+            1.0,                     //time1
+        ),
+        world: FlatBvh::new(&hittable),
+        lights: None,
+        materials,
+    }
+    .loop_render(image_config, 12);
+}
@@ -0,0 +1,49 @@
+use pathtracer::hittables::*;
+use pathtracer::material::*;
+use pathtracer::misc::Interval;
+use pathtracer::primitive::*;
+use pathtracer::*;
+
+// Cornell box loaded from .obj file, with materials created from its .mtl file
+
+fn main() {
+    let mut hittable = HittableList::new();
+    let mut materials = MaterialContainer::default();
+
+    for (_, mesh) in Mesh::load_obj("assets/cornell_box.obj", &mut materials).unwrap() {
+        hittable.add(mesh);
+    }
+
+    let image_config = ImageConfig {
+        nx: 512,
+        ny: 512,
+        samples_per_pixel: 16,
+        ray_setting: RaySetting {
+            background_color: V3::new(0.0, 0.0, 0.0),
+            depth: 32,
+            ray_time: Interval {
+                min: 0.0,
+                max: 2048.0,
+            },
+        },
+        name: "obj_cornell_box",
+    };
+
+    Scene {
+        camera: Camera::new(
+            V3::new(278.0, 278.0, -800.0), //lookfrom
+            V3::new(278.0, 278.0, 0.0),    //lookat
+            V3::new(0.0, 1.0, 0.0),        //vup
+            40.0,                          //vertical_fov
+            1.0,                           //aspect
+            0.0,                           //aperture
+            26.0,                          //focus_dist
+            0.0,                           //time0
+            1.0,                           //time1
+        ),
+        world: FlatBvh::new(&hittable),
+        lights: None,
+        materials,
+    }
+    .loop_render(image_config, 12);
+}
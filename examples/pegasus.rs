@@ -56,28 +56,18 @@ fn main() {
         material: red,
     });
 
-    hittable.add(
-        Box::new(XZrect {
-            x0: 0.595,
-            x1: 1.105,
-            z0: 0.595,
-            z1: 1.105,
-            k: 1.69,
-            material: light,
-        })
-        .flip_face(),
-    );
-
-    lights.add(
-        Box::new(XZrect {
-            x0: 0.595,
-            x1: 1.105,
-            z0: 0.595,
-            z1: 1.105,
-            k: 1.69,
-            material: light,
-        })
-        .flip_face(),
+    lights.add_shared(
+        hittable.add(
+            Box::new(XZrect {
+                x0: 0.595,
+                x1: 1.105,
+                z0: 0.595,
+                z1: 1.105,
+                k: 1.69,
+                material: light,
+            })
+            .flip_face(),
+        ),
     );
 
     hittable.add(XZrect {
@@ -123,7 +113,7 @@ fn main() {
             background_color: V3::new(0.0, 0.0, 0.0),
             depth: 16,
             ray_time: Interval {
-                min: 0.001,
+                min: 0.0,
                 max: 2048.0,
             },
         },
@@ -142,7 +132,7 @@ fn main() {
             0.0,                       //time0
             1.0,                       //time1
         ),
-        world: BvhNode::new(&hittable),
+        world: FlatBvh::new(&hittable),
         lights: Some(lights),
         materials,
     }
@@ -24,54 +24,31 @@ fn main() {
     let scale = 2.0;
     let mut seed = rand::thread_rng();
 
-    hittable.add(Sphere::new(
+    lights.add_shared(hittable.add(Sphere::new(
         V3::new(-1.0, 0.0, -2.0),
         0.5,
         materials.add(LightSource::new(Box::new(PlasmaTexture {
             param: seed.gen::<f32>() * 100.0,
             scale,
         }))),
-    ));
+    )));
 
-    lights.add(Sphere::new(
-        V3::new(-1.0, 0.0, -2.0),
-        0.5,
-        materials.add(LightSource::new(Box::new(PlasmaTexture {
-            param: seed.gen::<f32>() * 100.0,
-            scale,
-        }))),
-    ));
-
-    hittable.add(
-        XYrect::new(
-            -0.5,
-            0.5,
-            -0.5,
-            0.5,
-            0.0,
-            materials.add(LightSource::new(Box::new(PlasmaTexture {
-                param: seed.gen::<f32>() * 100.0,
-                scale: 1.0,
-            }))),
-        )
-        //.rotate(V3::new(0.0, 1.0, 0.0), -0.8)
-        .translate(V3::new(0.5, 0.0, -2.0)),
-    );
-
-    lights.add(
-        XYrect::new(
-            -0.5,
-            0.5,
-            -0.5,
-            0.5,
-            0.0,
-            materials.add(LightSource::new(Box::new(PlasmaTexture {
-                param: seed.gen::<f32>() * 100.0,
-                scale: 1.0,
-            }))),
-        )
-        //.rotate(V3::new(0.0, 1.0, 0.0), -0.8)
-        .translate(V3::new(0.5, 0.0, -2.0)),
+    lights.add_shared(
+        hittable.add(
+            XYrect::new(
+                -0.5,
+                0.5,
+                -0.5,
+                0.5,
+                0.0,
+                materials.add(LightSource::new(Box::new(PlasmaTexture {
+                    param: seed.gen::<f32>() * 100.0,
+                    scale: 1.0,
+                }))),
+            )
+            //.rotate(V3::new(0.0, 1.0, 0.0), -0.8)
+            .translate(V3::new(0.5, 0.0, -2.0)),
+        ),
     );
 
     let image_config = ImageConfig {
@@ -82,7 +59,7 @@ fn main() {
             background_color: V3::new(0.0, 0.0, 0.0),
             depth: 32,
             ray_time: Interval {
-                min: 0.001,
+                min: 0.0,
                 max: 2048.0,
             },
         },
@@ -101,7 +78,7 @@ fn main() {
             0.0,                     //time0
             1.0,                     //time1
         ),
-        world: BvhNode::new(&hittable),
+        world: FlatBvh::new(&hittable),
         lights: Some(lights),
         materials,
     }
@@ -1,4 +1,4 @@
-use pathtracer::hittables::{BvhNode, HittableList};
+use pathtracer::hittables::{FlatBvh, HittableList};
 use pathtracer::material::{Lambertian, MaterialContainer};
 use pathtracer::misc::Interval;
 use pathtracer::primitive::Quad;
@@ -64,7 +64,7 @@ pub fn main() {
             background_color: V3::new(0.5, 0.7, 1.0),
             depth: 32,
             ray_time: Interval {
-                min: 0.001,
+                min: 0.0,
                 max: 2048.0,
             },
         },
@@ -83,7 +83,7 @@ pub fn main() {
             0.0,                    //time0
             1.0,                    //time1
         ),
-        world: BvhNode::new(&hittable),
+        world: FlatBvh::new(&hittable),
         lights: None,
         materials,
     };
@@ -19,6 +19,7 @@ fn main() {
         even: Box::new(ConstantTexture {
             color: V3::new(0.9, 0.9, 0.9),
         }),
+        frequency: 10.0,
     };
 
     let perlin_texture = PerlinNoiseTexture {
@@ -85,7 +86,7 @@ fn main() {
     ));
     hittable2.add(Sphere::new(V3::new(0.0, 1.0, 0.0), 1.0, glass_material));
 
-    hittable.add(BvhNode::new(&hittable2));
+    hittable.add(FlatBvh::new(&hittable2));
 
     let image_config = ImageConfig {
         nx: 2048,
@@ -95,7 +96,7 @@ fn main() {
             background_color: V3::new(0.5, 0.7, 1.0),
             depth: 32,
             ray_time: Interval {
-                min: 0.001,
+                min: 0.0,
                 max: 2048.0,
             },
         },
@@ -114,7 +115,7 @@ fn main() {
             0.0,                     //time0
             1.0,                     //time1
         ),
-        world: BvhNode::new(&hittable),
+        world: FlatBvh::new(&hittable),
         lights: None,
         materials,
     }
@@ -18,6 +18,7 @@ fn main() {
         even: Box::new(ConstantTexture {
             color: V3::new(0.9, 0.9, 0.9),
         }),
+        frequency: 10.0,
     };
 
     hittable.add(Sphere::new(
@@ -71,23 +72,15 @@ fn main() {
         }
     }
 
-    hittable2.add(Sphere::new(
+    lights.add_shared(hittable2.add(Sphere::new(
         V3::new(4.0, 0.2, 0.0),
         0.1,
         materials.add(LightSource::new(Box::new(ConstantTexture {
             color: V3::new(1.0, 1.0, 1.0) * 300.0 as f32,
         }))),
-    ));
-
-    lights.add(Sphere::new(
-        V3::new(4.0, 0.2, 0.0),
-        0.1,
-        materials.add(LightSource::new(Box::new(ConstantTexture {
-            color: V3::new(1.0, 1.0, 1.0) * 300.0 as f32,
-        }))),
-    ));
+    )));
 
-    hittable.add(BvhNode::new(&hittable2));
+    hittable.add(FlatBvh::new(&hittable2));
 
     let image_config = ImageConfig {
         nx: 2048,
@@ -97,7 +90,7 @@ fn main() {
             background_color: V3::new(0.0, 0.0, 0.0),
             depth: 32,
             ray_time: Interval {
-                min: 0.001,
+                min: 0.0,
                 max: 2048.0,
             },
         },
@@ -116,7 +109,7 @@ fn main() {
             0.0,                     //time0
             1.0,                     //time1
         ),
-        world: BvhNode::new(&hittable),
+        world: FlatBvh::new(&hittable),
         lights: Some(lights),
         materials,
     }
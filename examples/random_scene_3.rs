@@ -21,6 +21,7 @@ fn main() {
         even: Box::new(ConstantTexture {
             color: V3::new(0.9, 0.9, 0.9),
         }),
+        frequency: 10.0,
     };
 
     hittable.add(Sphere::new(
@@ -51,33 +52,21 @@ fn main() {
                         }))),
                     ));
                 } else if choose_mat < 0.8 {
-                    hittable2.add(Sphere::new(
-                        center,
-                        0.2,
-                        materials.add(LightSource::new(Box::new(ConstantTexture {
-                            color: V3::new(
-                                seed.gen::<f32>() * seed.gen::<f32>(),
-                                seed.gen::<f32>() * seed.gen::<f32>(),
-                                seed.gen::<f32>() * seed.gen::<f32>(),
-                            )
-                            .norm()
-                                * (15.0 + 15.0 * seed.gen::<f32>()),
-                        }))),
-                    ));
-
-                    lights.add(Sphere::new(
-                        center,
-                        0.2,
-                        materials.add(LightSource::new(Box::new(ConstantTexture {
-                            color: V3::new(
-                                seed.gen::<f32>() * seed.gen::<f32>(),
-                                seed.gen::<f32>() * seed.gen::<f32>(),
-                                seed.gen::<f32>() * seed.gen::<f32>(),
-                            )
-                            .norm()
-                                * (15.0 + 15.0 * seed.gen::<f32>()),
-                        }))),
-                    ));
+                    lights.add_shared(
+                        hittable2.add(Sphere::new(
+                            center,
+                            0.2,
+                            materials.add(LightSource::new(Box::new(ConstantTexture {
+                                color: V3::new(
+                                    seed.gen::<f32>() * seed.gen::<f32>(),
+                                    seed.gen::<f32>() * seed.gen::<f32>(),
+                                    seed.gen::<f32>() * seed.gen::<f32>(),
+                                )
+                                .norm()
+                                    * (15.0 + 15.0 * seed.gen::<f32>()),
+                            }))),
+                        )),
+                    );
                 } else if choose_mat < 0.88 {
                     hittable2.add(Sphere::new(
                         center,
@@ -112,7 +101,7 @@ fn main() {
     ));
     hittable2.add(Sphere::new(V3::new(0.0, 1.0, 0.0), 1.0, glass_material));
 
-    hittable.add(BvhNode::new(&hittable2));
+    hittable.add(FlatBvh::new(&hittable2));
 
     let image_config = ImageConfig {
         nx: 2048,
@@ -122,7 +111,7 @@ fn main() {
             background_color: V3::new(0.0, 0.0, 0.0),
             depth: 128,
             ray_time: Interval {
-                min: 0.001,
+                min: 0.0,
                 max: 2048.0,
             },
         },
@@ -141,7 +130,7 @@ fn main() {
             0.0,                     //time0
             1.0,                     //time1
         ),
-        world: BvhNode::new(&hittable),
+        world: FlatBvh::new(&hittable),
         lights: Some(lights),
         materials,
     }
@@ -30,7 +30,7 @@ fn main() {
         materials.add(Lambertian::new(Box::new(perlin_texture))),
     ));
 
-    hittable.add(XYrect::new(
+    lights.add_shared(hittable.add(XYrect::new(
         3.0,
         5.0,
         1.0,
@@ -39,18 +39,7 @@ fn main() {
         materials.add(LightSource::new(Box::new(ConstantTexture {
             color: V3::new(4.0, 4.0, 4.0),
         }))),
-    ));
-
-    lights.add(XYrect::new(
-        3.0,
-        5.0,
-        1.0,
-        3.0,
-        -2.0,
-        materials.add(LightSource::new(Box::new(ConstantTexture {
-            color: V3::new(4.0, 4.0, 4.0),
-        }))),
-    ));
+    )));
 
     let image_config = ImageConfig {
         nx: 2048,
@@ -60,7 +49,7 @@ fn main() {
             background_color: V3::new(0.0, 0.0, 0.0),
             depth: 4,
             ray_time: Interval {
-                min: 0.001,
+                min: 0.0,
                 max: 2048.0,
             },
         },
@@ -79,7 +68,7 @@ fn main() {
             0.0,                     //time0
             1.0,                     //time1
         ),
-        world: BvhNode::new(&hittable),
+        world: FlatBvh::new(&hittable),
         lights: Some(lights),
         materials,
     }
@@ -27,9 +27,9 @@ fn main() {
 
     hittable.add(YZrect::new(0.0, 555.0, 0.0, 555.0, 0.0, red));
 
-    hittable.add(XZrect::new(250.0, 380.0, 250.0, 380.0, 554.0, light).flip_face());
-
-    lights.add(XZrect::new(250.0, 380.0, 250.0, 380.0, 554.0, light).flip_face());
+    lights.add_shared(
+        hittable.add(XZrect::new(250.0, 380.0, 250.0, 380.0, 554.0, light).flip_face()),
+    );
 
     hittable.add(XZrect::new(0.0, 555.0, 0.0, 555.0, 0.0, white));
 
@@ -67,7 +67,7 @@ fn main() {
             background_color: V3::new(0.0, 0.0, 0.0),
             depth: 64,
             ray_time: Interval {
-                min: 0.001,
+                min: 0.0,
                 max: 2048.0,
             },
         },
@@ -86,7 +86,7 @@ fn main() {
             0.0,                           //time0
             1.0,                           //time1
         ),
-        world: BvhNode::new(&hittable),
+        world: FlatBvh::new(&hittable),
         lights: Some(lights),
         materials,
     }
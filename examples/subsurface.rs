@@ -0,0 +1,80 @@
+use pathtracer::hittables::*;
+use pathtracer::material::*;
+use pathtracer::misc::Interval;
+use pathtracer::primitive::*;
+use pathtracer::texture::*;
+use pathtracer::transform::*;
+use pathtracer::*;
+
+// Blender monkey made of wax next to a marble ball
+
+fn main() {
+    let mut hittable = HittableList::new();
+    let mut materials = MaterialContainer::default();
+
+    let wax_particles = materials.add(Subsurface::phase_material(
+        V3::new(0.9, 0.6, 0.4),
+        HenyeyGreenstein { g: 0.0 },
+    ));
+    let wax = materials.add(Subsurface::new(1.4, V3::new(0.3, 0.1, 0.05), wax_particles));
+
+    let marble_particles = materials.add(Subsurface::phase_material(
+        V3::new(0.9, 0.9, 0.9),
+        HenyeyGreenstein { g: 0.3 },
+    ));
+    let marble = materials.add(Subsurface::new(
+        1.5,
+        V3::new(0.05, 0.05, 0.05),
+        marble_particles,
+    ));
+
+    hittable.add(
+        Mesh::new("assets/monkey (2).obj", wax)
+            .unwrap()
+            .translate(V3::new(-1.2, 0.0, -3.0)),
+    );
+
+    hittable.add(Sphere::new(V3::new(1.2, 0.0, -3.0), 0.9, marble));
+
+    hittable.add(Sphere::new(
+        V3::new(0.0, -101.0, -3.0),
+        100.0,
+        materials.add(Lambertian::new(Box::new(ConstantTexture {
+            color: V3::new(0.5, 0.5, 0.5),
+        }))),
+    ));
+
+    let image_config = ImageConfig {
+        nx: 2048,
+        ny: 1024,
+        samples_per_pixel: 4,
+        ray_setting: RaySetting {
+            background_color: V3::new(0.5, 0.7, 1.0),
+            // Random walk inside of the objects needs many bounces
+            depth: 256,
+            ray_time: Interval {
+                min: 0.0,
+                max: 2048.0,
+            },
+        },
+        name: "subsurface",
+    };
+
+    Scene {
+        camera: Camera::new(
+            V3::new(0.0, 0.5, 2.0),  //lookfrom
+            V3::new(0.0, 0.0, -3.0), //lookat
+            V3::new(0.0, 1.0, 0.0),  //vup
+            50.0,                    //vertical_fov
+            2.0,                     //aspect
+            0.0,                     //aperture
+            5.0,                     //focus_dist
+            0.0,                     //time0
+            1.0,                     //time1
+        ),
+        world: FlatBvh::new(&hittable),
+        lights: None,
+        materials,
+    }
+    .loop_render(image_config, 12);
+}
@@ -1,4 +1,5 @@
-use crate::{Ray, RaySetting, V3};
+use crate::transform::MediumStack;
+use crate::{Ray, RayCone, RaySetting, V3};
 use rand::Rng;
 /// Lens-based camera.
 pub struct Camera {
@@ -9,8 +10,11 @@ pub struct Camera {
     u: V3<f32>,
     v: V3<f32>,
     lens_radius: f32,
+    view_height: f32,
     time_begin: f32,
     time_end: f32,
+    /// Media the camera is inside of, which its Rays start in.
+    media: MediumStack,
 }
 
 impl Camera {
@@ -66,10 +70,30 @@ impl Camera {
             u,
             v,
             lens_radius,
+            view_height: half_height * 2.0,
             time_begin,
             time_end,
+            media: MediumStack::default(),
         }
     }
+
+    /// Returns the camera placed inside of media, like under water or in fog, which have to be the media of the objects around it.
+    ///
+    /// `media` - Media the camera is inside of, in the order they were entered.
+    pub fn with_media(mut self, media: MediumStack) -> Self {
+        self.media = media;
+        self
+    }
+    /// Returns cone that covers single pixel of the rendered image, used to filter textures.
+    ///
+    /// `ny` - Height of the rendered image in pixels.
+    pub fn pixel_cone(&self, ny: u32) -> RayCone {
+        RayCone {
+            width: 0.0,
+            spread: self.view_height / (ny as f32),
+        }
+    }
+
     /// Get Ray from Camera origin through wanted position on the final image.
     ///
     /// `U`, `V` - Coords of pixel, should be in range <0,1>.
@@ -86,7 +110,11 @@ impl Camera {
             origin: self.origin + offset,
             end,
             time: rand::thread_rng().gen_range(self.time_begin..self.time_end),
+            cone: RayCone::default(),
+            channel: None,
+            media: self.media,
             setting,
+            materials: None,
         }
     }
 }
@@ -0,0 +1,704 @@
+//! Import of glTF 2.0 scenes from .gltf files, with external or embedded buffers, and from binary .glb files.
+
+use crate::hittables::{FlatBvh, HittableList};
+use crate::material::*;
+use crate::misc::{HittablePdf, Matrix4};
+use crate::primitive::{Mesh, MeshData, Sphere};
+use crate::texture::*;
+use crate::transform::{Transform, Transformed};
+use crate::{Camera, Hittable, Scene, V3};
+
+use gltf::camera::Projection;
+use gltf::image::Source;
+use gltf::khr_lights_punctual::{Kind, Light};
+use gltf::mesh::Mode;
+use gltf::texture::WrappingMode;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Radius of spheres standing in for point and spot lights, relative to the radius of the scene.
+const LIGHT_SIZE: f32 = 0.01;
+
+/// Angular radius in radians of spheres standing in for directional lights.
+const DIRECTIONAL_ANGLE: f32 = 0.05;
+
+/// Distance of spheres standing in for directional lights from the center of the scene, relative to its radius.
+const DIRECTIONAL_DISTANCE: f32 = 10.0;
+
+impl Scene {
+    /// Returns scene of the .gltf or .glb file, if it could be made.
+    ///
+    /// Meshes are instanced by nodes of the default scene with their transforms. Materials are mapped onto the closest ones: emissive materials become light sources, transmissive ones dielectrics, metallic ones metals and the rest diffuse, with base color and normal textures. Point, spot and directional lights become small emitting spheres, and the first perspective camera is used, or a camera looking at the whole scene if there is none.
+    ///
+    /// `file_path` - Path to the .gltf or .glb file.
+    ///
+    /// `aspect` - Ratio of width to height of the rendered image, used instead of the one of the camera.
+    pub fn load_gltf(file_path: &str, aspect: f32) -> Result<Scene, Error> {
+        let directory = Path::new(file_path).parent().unwrap_or(Path::new(""));
+        load(&fs::read(file_path)?, directory, aspect)
+    }
+}
+
+/// Returns scene of the content of the .gltf or .glb file.
+///
+/// `bytes` - Content of the file.
+///
+/// `directory` - Directory relative to which external buffers and images are read.
+///
+/// `aspect` - Ratio of width to height of the rendered image.
+fn load(bytes: &[u8], directory: &Path, aspect: f32) -> Result<Scene, Error> {
+    let gltf::Gltf { document, mut blob } =
+        gltf::Gltf::from_slice(bytes).map_err(|error| invalid_data(&error.to_string()))?;
+
+    let mut buffers = Vec::new();
+    for buffer in document.buffers() {
+        let data = match buffer.source() {
+            gltf::buffer::Source::Bin => blob
+                .take()
+                .ok_or_else(|| invalid_data("Binary buffer is missing"))?,
+            gltf::buffer::Source::Uri(uri) => read_uri(uri, directory)?,
+        };
+        if data.len() < buffer.length() {
+            return Err(invalid_data("Buffer is shorter than declared"));
+        }
+        buffers.push(data);
+    }
+
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| invalid_data("File has no scene"))?;
+
+    let mut loader = Loader {
+        buffers,
+        directory,
+        materials: MaterialContainer::default(),
+        used_materials: HashMap::new(),
+        textures: HashMap::new(),
+        meshes: HashMap::new(),
+        world: HittableList::new(),
+        camera: None,
+        lights: Vec::new(),
+    };
+    for node in scene.nodes() {
+        loader.add_node(node, Matrix4::identity(), aspect)?;
+    }
+
+    // Lights and the default camera are sized by the scene
+    let (center, radius) = if loader.world.list.is_empty() {
+        (V3::default(), 1.0)
+    } else {
+        let bounds = loader.world.bounding_box();
+        let min = V3::new(bounds.x.min, bounds.y.min, bounds.z.min);
+        let max = V3::new(bounds.x.max, bounds.y.max, bounds.z.max);
+        ((min + max) * 0.5, ((max - min) * 0.5).length().max(1e-3))
+    };
+
+    let mut lights = HittableList::new();
+    for (light, transform) in std::mem::take(&mut loader.lights) {
+        let light = loader.add_light(&light, &transform, center, radius);
+        loader.world.add_shared(light.clone());
+        lights.add_shared(light);
+    }
+
+    let camera = loader.camera.take().unwrap_or_else(|| {
+        let vertical_fov: f32 = 40.0;
+        let distance = radius / (vertical_fov.to_radians() * 0.5).tan() * 1.1;
+        let lookfrom = center + V3::new(0.0, 0.0, distance);
+        Camera::new(
+            lookfrom,
+            center,
+            V3::new(0.0, 1.0, 0.0),
+            vertical_fov,
+            aspect,
+            0.0,
+            distance,
+            0.0,
+            1.0,
+        )
+    });
+
+    Ok(Scene {
+        camera,
+        world: FlatBvh::new(&loader.world),
+        lights: (!lights.list.is_empty()).then_some(lights),
+        materials: loader.materials,
+    })
+}
+
+/// State of the import, objects are added to the world as nodes are visited.
+struct Loader<'a> {
+    buffers: Vec<Vec<u8>>,
+    directory: &'a Path,
+    materials: MaterialContainer,
+    /// Index of the material and cutout mask made for each material of the file, None is the default material.
+    used_materials: HashMap<Option<usize>, usize>,
+    /// Textures made for each image of the file, decoded as sRGB or linear.
+    textures: HashMap<(usize, bool), ImageTexture>,
+    /// Object made for each mesh of the file, shared by all nodes using it, None if the mesh has no triangles.
+    meshes: HashMap<usize, Option<Arc<dyn HittablePdf>>>,
+    world: HittableList,
+    camera: Option<Camera>,
+    /// Punctual lights with transforms of their nodes, made once the size of the scene is known.
+    lights: Vec<(Light<'a>, Transform)>,
+}
+
+impl<'a> Loader<'a> {
+    /// Adds objects of the node and its children.
+    ///
+    /// `node` - Node of the scene.
+    ///
+    /// `parent` - Matrix transforming from the parent node to the world.
+    ///
+    /// `aspect` - Ratio of width to height of the rendered image.
+    fn add_node(
+        &mut self,
+        node: gltf::Node<'a>,
+        parent: Matrix4,
+        aspect: f32,
+    ) -> Result<(), Error> {
+        // Matrices of glTF are stored by columns
+        let matrix = parent
+            * Matrix4 {
+                m: node.transform().matrix(),
+            }
+            .transpose();
+
+        // Nodes scaled to nothing, or beyond the range of floats, can't be seen
+        if let Some(transform) = Transform::from_matrix(matrix) {
+            if let Some(mesh) = node.mesh() {
+                if let Some(object) = self.mesh(mesh)? {
+                    if matrix == Matrix4::identity() {
+                        self.world.add_shared(object);
+                    } else {
+                        self.world.add(Transformed::new(object, transform));
+                    }
+                }
+            }
+
+            if let (None, Some(camera)) = (&self.camera, node.camera()) {
+                // Orthographic cameras aren't supported, so the next perspective one is used
+                if let Projection::Perspective(perspective) = camera.projection() {
+                    let lookfrom = transform.point(V3::default());
+                    self.camera = Some(Camera::new(
+                        lookfrom,
+                        lookfrom + transform.vector(V3::new(0.0, 0.0, -1.0)),
+                        transform.vector(V3::new(0.0, 1.0, 0.0)),
+                        perspective.yfov().to_degrees(),
+                        aspect,
+                        0.0,
+                        1.0,
+                        0.0,
+                        1.0,
+                    ));
+                }
+            }
+
+            if let Some(light) = node.light() {
+                self.lights.push((light, transform));
+            }
+        }
+
+        for child in node.children() {
+            self.add_node(child, matrix, aspect)?;
+        }
+        Ok(())
+    }
+
+    /// Returns object made of triangles of all primitives of the mesh, or None if it has none.
+    fn mesh(&mut self, mesh: gltf::Mesh) -> Result<Option<Arc<dyn HittablePdf>>, Error> {
+        if let Some(object) = self.meshes.get(&mesh.index()) {
+            return Ok(object.clone());
+        }
+
+        let mut primitives = HittableList::new();
+        for primitive in mesh.primitives() {
+            if let Some(data) = self.primitive_data(&primitive)? {
+                primitives.add(Mesh::from_data(data)?);
+            }
+        }
+
+        let object: Option<Arc<dyn HittablePdf>> = match primitives.list.len() {
+            0 => None,
+            1 => primitives.list.pop(),
+            _ => Some(Arc::new(FlatBvh::new(&primitives))),
+        };
+        self.meshes.insert(mesh.index(), object.clone());
+        Ok(object)
+    }
+
+    /// Returns indexed triangles of the primitive, or None if it is made of points or lines.
+    fn primitive_data(&mut self, primitive: &gltf::Primitive) -> Result<Option<MeshData>, Error> {
+        let buffers = &self.buffers;
+        let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(Vec::as_slice));
+
+        let positions: Vec<V3<f32>> = match reader.read_positions() {
+            Some(positions) => positions.map(|[x, y, z]| V3::new(x, y, z)).collect(),
+            None => return Ok(None),
+        };
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(indices) => indices.into_u32().collect(),
+            None => (0..positions.len() as u32).collect(),
+        };
+        if indices
+            .iter()
+            .any(|&index| index as usize >= positions.len())
+        {
+            return Err(invalid_data(
+                "Primitive has index of a verticle it doesn't have",
+            ));
+        }
+
+        let triangles: Vec<[u32; 3]> = match primitive.mode() {
+            Mode::Triangles => indices
+                .chunks_exact(3)
+                .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+                .collect(),
+            // Every other triangle of a strip is wound the other way
+            Mode::TriangleStrip => indices
+                .windows(3)
+                .enumerate()
+                .map(|(index, strip)| match index % 2 {
+                    0 => [strip[0], strip[1], strip[2]],
+                    _ => [strip[1], strip[0], strip[2]],
+                })
+                .collect(),
+            Mode::TriangleFan => indices
+                .windows(2)
+                .skip(1)
+                .map(|edge| [indices[0], edge[0], edge[1]])
+                .collect(),
+            Mode::Points | Mode::Lines | Mode::LineLoop | Mode::LineStrip => return Ok(None),
+        };
+
+        let normals: Vec<V3<f32>> = reader
+            .read_normals()
+            .map(|normals| normals.map(|[x, y, z]| V3::new(x, y, z)).collect())
+            .unwrap_or_default();
+        // Texture coordinates of glTF start at the top of the image
+        let uvs: Vec<(f32, f32)> = reader
+            .read_tex_coords(0)
+            .map(|uvs| uvs.into_f32().map(|[u, v]| (u, 1.0 - v)).collect())
+            .unwrap_or_default();
+        // Flipped texture coordinates flip the bitangent too
+        let tangents: Vec<(V3<f32>, f32)> = reader
+            .read_tangents()
+            .filter(|_| !normals.is_empty())
+            .map(|tangents| {
+                tangents
+                    .map(|[x, y, z, w]| (V3::new(x, y, z), -w))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if [normals.len(), uvs.len(), tangents.len()]
+            .iter()
+            .any(|&count| count != 0 && count != positions.len())
+        {
+            return Err(invalid_data(
+                "Primitive has attributes of other length than positions",
+            ));
+        }
+
+        let material = self.material(primitive.material())?;
+        let data = MeshData {
+            positions,
+            normals,
+            uvs,
+            tangents,
+            materials: vec![material; triangles.len()],
+            indices: triangles,
+        };
+
+        // Triangles without normals are shaded flat, as the specification asks
+        Ok(Some(if data.normals.is_empty() {
+            flat_shaded(&data)
+        } else {
+            data
+        }))
+    }
+
+    /// Returns index of the material closest to the one of the file, with the cutout mask of its base color.
+    fn material(&mut self, material: gltf::Material) -> Result<usize, Error> {
+        if let Some(&used) = self.used_materials.get(&material.index()) {
+            return Ok(used);
+        }
+
+        let pbr = material.pbr_metallic_roughness();
+        let [r, g, b, _] = pbr.base_color_factor();
+        let base_factor = V3::new(r, g, b);
+        let base_texture = pbr
+            .base_color_texture()
+            .map(|info| self.texture(info.texture(), ColorSpace::Srgb))
+            .transpose()?;
+        let base: Box<dyn Texture + Sync + Send> = match base_texture {
+            Some(texture) if base_factor == V3::new(1.0, 1.0, 1.0) => Box::new(texture),
+            Some(texture) => Box::new(MathTexture::new(
+                texture,
+                ConstantTexture { color: base_factor },
+                MathOperation::Multiply,
+            )),
+            None => Box::new(ConstantTexture { color: base_factor }),
+        };
+
+        // Alpha comes from the base color texture only
+        let opacity = match (material.alpha_mode(), pbr.base_color_texture()) {
+            (gltf::material::AlphaMode::Mask, Some(_)) => Some(Opacity {
+                texture: Arc::from(base.clone()),
+                mode: AlphaMode::Threshold(material.alpha_cutoff().unwrap_or(0.5)),
+            }),
+            (gltf::material::AlphaMode::Blend, Some(_)) => Some(Opacity {
+                texture: Arc::from(base.clone()),
+                mode: AlphaMode::Stochastic,
+            }),
+            _ => None,
+        };
+
+        let [r, g, b] = material.emissive_factor();
+        let emission = V3::new(r, g, b) * material.emissive_strength().unwrap_or(1.0);
+        let normal_map = material
+            .normal_texture()
+            .map(|info| self.texture(info.texture(), ColorSpace::Linear))
+            .transpose()?;
+        let transmission = material
+            .transmission()
+            .map_or(0.0, |transmission| transmission.transmission_factor());
+
+        let index = if emission != V3::default() {
+            let texture: Box<dyn Texture + Sync + Send> = match material.emissive_texture() {
+                Some(info) => Box::new(MathTexture::new(
+                    self.texture(info.texture(), ColorSpace::Srgb)?,
+                    ConstantTexture { color: emission },
+                    MathOperation::Multiply,
+                )),
+                None => Box::new(ConstantTexture { color: emission }),
+            };
+            add_material(
+                &mut self.materials,
+                LightSource::new(texture),
+                None,
+                opacity,
+            )
+        } else if transmission > 0.5 {
+            let dielectric = Dielectric::new(material.ior().unwrap_or(1.5));
+            add_material(&mut self.materials, dielectric, normal_map, opacity)
+        } else if pbr.metallic_factor() >= 0.5 {
+            // Factor is already multiplied into the base color texture
+            let metal = match pbr.base_color_texture() {
+                Some(_) => {
+                    Metalic::new(V3::new(1.0, 1.0, 1.0), pbr.roughness_factor()).with_texture(base)
+                }
+                None => Metalic::new(base_factor, pbr.roughness_factor()),
+            };
+            add_material(&mut self.materials, metal, normal_map, opacity)
+        } else {
+            add_material(
+                &mut self.materials,
+                Lambertian::new(base),
+                normal_map,
+                opacity,
+            )
+        };
+
+        self.used_materials.insert(material.index(), index);
+        Ok(index)
+    }
+
+    /// Returns texture of the image, sampled like the sampler of the file says.
+    ///
+    /// `texture` - Texture of the file.
+    ///
+    /// `color_space` - Encoding of colors of the image, sRGB for colors and linear for data.
+    fn texture(
+        &mut self,
+        texture: gltf::Texture,
+        color_space: ColorSpace,
+    ) -> Result<ImageTexture, Error> {
+        let image = texture.source();
+        let key = (image.index(), color_space == ColorSpace::Srgb);
+
+        let decoded = match self.textures.get(&key) {
+            Some(decoded) => decoded.clone(),
+            None => {
+                let decoded = match image.source() {
+                    Source::View { view, .. } => {
+                        let bytes = self.buffers[view.buffer().index()]
+                            .get(view.offset()..view.offset() + view.length())
+                            .ok_or_else(|| invalid_data("Image is out of its buffer"))?;
+                        ImageTexture::from_bytes(bytes, color_space)?
+                    }
+                    Source::Uri { uri, .. } => {
+                        ImageTexture::from_bytes(&read_uri(uri, self.directory)?, color_space)?
+                    }
+                }
+                .with_filter(TextureFilter::Trilinear);
+                self.textures.insert(key, decoded.clone());
+                decoded
+            }
+        };
+
+        let wrap = match texture.sampler().wrap_s() {
+            WrappingMode::Repeat => WrapMode::Repeat,
+            WrappingMode::MirroredRepeat => WrapMode::Mirror,
+            WrappingMode::ClampToEdge => WrapMode::Clamp,
+        };
+        Ok(decoded.with_wrap(wrap))
+    }
+
+    /// Returns sphere standing in for the punctual light.
+    ///
+    /// `light` - Light of the file.
+    ///
+    /// `transform` - Transform of the node of the light.
+    ///
+    /// `center`, `radius` - Bounding sphere of the scene.
+    fn add_light(
+        &mut self,
+        light: &Light,
+        transform: &Transform,
+        center: V3<f32>,
+        radius: f32,
+    ) -> Arc<dyn HittablePdf> {
+        let [r, g, b] = light.color();
+        let intensity = V3::new(r, g, b) * light.intensity();
+        let position = transform.point(V3::default());
+        let direction = transform.vector(V3::new(0.0, 0.0, -1.0)).norm();
+
+        // Sphere seen from afar emits as much as its cross section times its radiance
+        let size = LIGHT_SIZE * radius;
+        let radiance = intensity / (std::f32::consts::PI * size * size);
+        let constant = |color| LightSource::new(Box::new(ConstantTexture { color }));
+
+        let sphere = match light.kind() {
+            Kind::Point => Sphere::new(position, size, self.materials.add(constant(radiance))),
+            Kind::Spot {
+                inner_cone_angle,
+                outer_cone_angle,
+            } => Sphere::new(
+                position,
+                size,
+                self.materials.add(SpotLight::new(
+                    radiance,
+                    direction,
+                    inner_cone_angle,
+                    outer_cone_angle,
+                )),
+            ),
+            // Intensity of directional lights is the illuminance they give
+            Kind::Directional => {
+                let distance = DIRECTIONAL_DISTANCE * radius;
+                let sin = DIRECTIONAL_ANGLE.sin();
+                let radiance = intensity / (std::f32::consts::PI * sin * sin);
+                Sphere::new(
+                    center - direction * distance,
+                    distance * sin,
+                    self.materials.add(constant(radiance)),
+                )
+            }
+        };
+        Arc::new(sphere)
+    }
+}
+
+/// Adds material, with normal map and cutout mask if it has them, and returns its index.
+fn add_material<T: MaterialTrait + 'static>(
+    materials: &mut MaterialContainer,
+    material: T,
+    normal_map: Option<ImageTexture>,
+    opacity: Option<Opacity>,
+) -> usize {
+    match (normal_map, opacity) {
+        (Some(map), Some(opacity)) => materials.add_with_opacity(
+            BumpMapped::new(material, NormalPerturbation::NormalMap(Box::new(map))),
+            opacity,
+        ),
+        (Some(map), None) => materials.add(BumpMapped::new(
+            material,
+            NormalPerturbation::NormalMap(Box::new(map)),
+        )),
+        (None, Some(opacity)) => materials.add_with_opacity(material, opacity),
+        (None, None) => materials.add(material),
+    }
+}
+
+/// Returns copy of the triangles, each with its own verticles with the normal of the triangle.
+fn flat_shaded(data: &MeshData) -> MeshData {
+    let mut flat = MeshData {
+        materials: data.materials.clone(),
+        ..MeshData::default()
+    };
+
+    for (triangle, indices) in data.indices.iter().enumerate() {
+        let [a, b, c] = data.verticles(triangle);
+        let normal = (b - a).cross(c - a).norm();
+        let first = flat.positions.len() as u32;
+
+        flat.positions.extend([a, b, c]);
+        flat.normals.extend([normal; 3]);
+        if !data.uvs.is_empty() {
+            flat.uvs
+                .extend(indices.map(|index| data.uvs[index as usize]));
+        }
+        flat.indices.push([first, first + 1, first + 2]);
+    }
+    flat
+}
+
+/// Returns content of the external or embedded file.
+///
+/// `uri` - Data URI with base64 content, or path relative to the directory.
+///
+/// `directory` - Directory of the .gltf file.
+fn read_uri(uri: &str, directory: &Path) -> Result<Vec<u8>, Error> {
+    match uri.strip_prefix("data:") {
+        Some(data) => match data.split_once(',') {
+            Some((header, content)) if header.ends_with(";base64") => decode_base64(content),
+            _ => Err(invalid_data("Only base64 data URIs are supported")),
+        },
+        None => fs::read(directory.join(decode_percents(uri))),
+    }
+}
+
+/// Returns bytes encoded in base64, standard or URL safe, with or without padding.
+fn decode_base64(text: &str) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let (mut accumulator, mut bits) = (0u32, 0);
+
+    for character in text
+        .bytes()
+        .filter(|&c| c != b'=' && !c.is_ascii_whitespace())
+    {
+        let value = match character {
+            b'A'..=b'Z' => character - b'A',
+            b'a'..=b'z' => character - b'a' + 26,
+            b'0'..=b'9' => character - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return Err(invalid_data("Invalid base64 data")),
+        };
+
+        // Only bits not written yet are kept
+        accumulator = (accumulator << 6 | value as u32) & 0xffff;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((accumulator >> bits) as u8);
+        }
+    }
+    Ok(bytes)
+}
+
+/// Returns path with escaped characters of the URI, like `%20`, decoded.
+fn decode_percents(uri: &str) -> String {
+    let bytes = uri.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+
+    let mut index = 0;
+    while index < bytes.len() {
+        let escaped = bytes
+            .get(index + 1..index + 3)
+            .filter(|_| bytes[index] == b'%')
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                index += 3;
+            }
+            None => {
+                decoded.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::misc::Interval;
+    use crate::{Ray, RaySetting};
+
+    #[test]
+    fn decode_test() {
+        assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_base64("-_8").unwrap(), [0xfb, 0xff]);
+        assert!(decode_base64("a*b").is_err());
+        assert_eq!(decode_percents("box%20(2).bin"), "box (2).bin");
+        assert_eq!(decode_percents("100%"), "100%");
+    }
+
+    #[test]
+    fn load_test() {
+        let scene = load(
+            include_bytes!("../assets/cornell_box.gltf"),
+            Path::new("assets"),
+            1.0,
+        )
+        .unwrap();
+        assert_eq!(
+            scene.lights.as_ref().map(|lights| lights.list.len()),
+            Some(1)
+        );
+
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+        let height_below = |x: f32, z: f32| {
+            let ray = Ray::for_test(V3::new(x, 500.0, z), V3::new(0.0, -1.0, 0.0), &setting);
+            scene.world.hit(&ray).map(|hit| hit.point.y)
+        };
+
+        // Instances of the cube are moved and scaled by their nodes, under the node of the boxes
+        let near = |height: Option<f32>, expected: f32| (height.unwrap() - expected).abs() < 0.01;
+        assert!(near(height_below(185.0, 169.0), 165.0));
+        assert!(near(height_below(368.0, 351.0), 330.0));
+        assert!(near(height_below(50.0, 500.0), 0.0));
+    }
+
+    #[test]
+    fn malformed_image_test() {
+        // Image claims more bytes than its buffer has
+        let gltf = br#"{
+            "asset": {"version": "2.0"},
+            "scenes": [{"nodes": [0]}],
+            "nodes": [{"mesh": 0}],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}, "material": 0}]}],
+            "materials": [{"pbrMetallicRoughness": {"baseColorTexture": {"index": 0}}}],
+            "textures": [{"source": 0}],
+            "images": [{"bufferView": 1, "mimeType": "image/png"}],
+            "accessors": [{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [0, 0, 0], "max": [1, 1, 0]}],
+            "bufferViews": [{"buffer": 0, "byteLength": 36}, {"buffer": 0, "byteOffset": 32, "byteLength": 100}],
+            "buffers": [{"byteLength": 36,
+                "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA"}]
+        }"#;
+        assert!(load(gltf, Path::new(""), 1.0).is_err());
+    }
+
+    #[test]
+    fn overflowing_node_test() {
+        // Scale of the node overflows floats, so the node is left out instead of aborting the load
+        let gltf = br#"{
+            "asset": {"version": "2.0"},
+            "scenes": [{"nodes": [0, 1]}],
+            "nodes": [{"mesh": 0, "scale": [1e39, 1, 1]}, {"mesh": 0}],
+            "meshes": [{"primitives": [{"attributes": {"POSITION": 0}}]}],
+            "accessors": [{"bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+                "min": [0, 0, 0], "max": [1, 1, 0]}],
+            "bufferViews": [{"buffer": 0, "byteLength": 36}],
+            "buffers": [{"byteLength": 36,
+                "uri": "data:application/octet-stream;base64,AAAAAAAAAAAAAAAAAACAPwAAAAAAAAAAAAAAAAAAgD8AAAAA"}]
+        }"#;
+        let scene = load(gltf, Path::new(""), 1.0).unwrap();
+        assert_eq!(scene.world.objects().list.len(), 1);
+    }
+}
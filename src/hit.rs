@@ -1,15 +1,22 @@
 use crate::hittables::Aabb;
-use crate::{Ray, V3};
+use crate::misc::{gamma, offset_ray_origin, Interval};
+use crate::transform::MediumInterface;
+use crate::{Ray, RaySetting, V3};
 
 use objekt_clonable::*;
 /// Hit event, created when ray hits some object.
 
 //#[derive(Default)]
+#[derive(Clone, Copy)]
 pub struct Hit {
     /// Time of collision.
     pub t: f32,
     /// Point on the object where hit occured.
     pub point: V3<f32>,
+    /// Bound of the absolute rounding error of `point` along each axis.
+    pub error: V3<f32>,
+    /// Point where hit occured in the coordinates of the object, before it was moved by transformations.
+    pub object_point: V3<f32>,
     /// Normal vector to the surface where hit occured.
     pub normal: V3<f32>,
     /// Object hit material index.
@@ -20,10 +27,22 @@ pub struct Hit {
     pub v: f32,
     /// True if ray hit the object from the exterior.
     pub front_face: bool,
+    /// Partial derivative of the hit point with respect to `u`, or zero vector if the primitive doesn't provide it.
+    pub dpdu: V3<f32>,
+    /// Partial derivative of the hit point with respect to `v`, or zero vector if the primitive doesn't provide it.
+    pub dpdv: V3<f32>,
+    /// Direction of the ray that hit the object.
+    pub direction: V3<f32>,
+    /// Width of the ray cone at the hit point, zero if the ray has no cone.
+    pub footprint: f32,
+    /// Color channel picked by a medium with colored extinction, which the Ray has to carry alone to go through it.
+    pub channel: Option<u32>,
+    /// Medium inside of the hit object, if the object is a medium boundary.
+    pub interface: Option<MediumInterface>,
 }
 
 impl Hit {
-    /// Method to create new hit struct, it imposes correct normal vector
+    /// Method to create new hit struct, it imposes correct normal vector. Error of the point is bounded as if it was computed along the ray from an exact `t`, primitives which know better replace it.
     ///
     /// `r` - Ray that hit the object.
     ///
@@ -55,14 +74,46 @@ impl Hit {
         Hit {
             t,
             point,
+            error: (r.origin.abs() + (r.end * t).abs()) * gamma(7),
+            object_point: point,
             normal,
             material,
             u,
             v,
             front_face,
+            dpdu: V3::default(),
+            dpdv: V3::default(),
+            direction: r.end,
+            footprint: r.cone.width_at(t * r.end.length()),
+            channel: None,
+            interface: None,
         }
     }
 
+    /// Returns hit event with surface tangents, which are needed to map textures onto the normal vector.
+    ///
+    /// `dpdu`, `dpdv` - Partial derivatives of the hit point with respect to texture coordinates.
+    pub fn with_tangents(mut self, dpdu: V3<f32>, dpdv: V3<f32>) -> Self {
+        self.dpdu = dpdu;
+        self.dpdv = dpdv;
+        self
+    }
+
+    /// Returns hit event with bound of the rounding error of the point replaced.
+    ///
+    /// `error` - Bound of the absolute error of the point along each axis.
+    pub fn with_error(mut self, error: V3<f32>) -> Self {
+        self.error = error;
+        self
+    }
+
+    /// Returns origin of a ray leaving the hit point, moved off the surface just enough to not hit it again.
+    ///
+    /// `direction` - Direction of the leaving ray.
+    pub fn spawn_origin(&self, direction: V3<f32>) -> V3<f32> {
+        offset_ray_origin(self.point, self.error, self.normal, direction)
+    }
+
     /// Returns hit event with front face atribute flipped, making hit event from interior to exterior and vice versa.
     pub fn flip_front_face(mut self) -> Self {
         self.front_face = !self.front_face;
@@ -79,4 +130,42 @@ pub trait Hittable: Send + Sync + Clone {
     fn hit(&self, ray: &Ray) -> Option<Hit>;
     /// Returns bounding box of the object
     fn bounding_box(&self) -> Aabb;
+    /// Returns true, if Ray hits this object between the start of its time interval and `t_max`. It can stop at any hit, not only the closest one, and doesn't compute how the hit looks, so it is cheaper than `hit`.
+    ///
+    /// `ray` - Ray that should hit the object, like a shadow ray toward a light.
+    ///
+    /// `t_max` - Time at which the tested segment of the Ray ends, it replaces the end of the Ray time interval.
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        hit_before(self, ray, t_max).is_some()
+    }
+    /// Returns fraction of light, separately for each color channel, which goes along the Ray through this object between the start of its time interval and `t_max`. Surfaces stop all the light, media let part of it through.
+    ///
+    /// `ray` - Ray going toward a light.
+    ///
+    /// `t_max` - Time at which the tested segment of the Ray ends, it replaces the end of the Ray time interval.
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> V3<f32> {
+        if self.occluded(ray, t_max) {
+            V3::default()
+        } else {
+            V3::new(1.0, 1.0, 1.0)
+        }
+    }
+}
+
+/// Returns the closest hit of the object between the start of the Ray time interval and `t_max`, for objects that can't tell if they are hit without finding the hit.
+///
+/// `hittable` - Object that should be hit.
+///
+/// `ray` - Ray that should hit the object.
+///
+/// `t_max` - Time at which the tested segment of the Ray ends.
+pub fn hit_before<H: Hittable + ?Sized>(hittable: &H, ray: &Ray, t_max: f32) -> Option<Hit> {
+    let setting = RaySetting {
+        ray_time: Interval::new(ray.setting.ray_time.min, t_max),
+        ..*ray.setting
+    };
+    hittable.hit(&Ray {
+        setting: &setting,
+        ..*ray
+    })
 }
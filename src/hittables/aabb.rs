@@ -69,6 +69,37 @@ impl Aabb {
         lolol0 <= lolol1
     }
 
+    /// Checks if the part of the Ray inside of the interval intersects with this bounding box.
+    ///
+    /// `origin` - Origin point of the Ray.
+    ///
+    /// `inverse_direction` - Inverse of each coordinate of the Ray direction, computed once per Ray.
+    ///
+    /// `interval` - Range of the Ray parameter, in which the box is searched for.
+    pub fn hit_interval(
+        &self,
+        origin: V3<f32>,
+        inverse_direction: V3<f32>,
+        interval: Interval,
+    ) -> bool {
+        let mut min = interval.min;
+        let mut max = interval.max;
+
+        for (bounds, origin, inverse) in [
+            (self.x, origin.x, inverse_direction.x),
+            (self.y, origin.y, inverse_direction.y),
+            (self.z, origin.z, inverse_direction.z),
+        ] {
+            let t0 = (bounds.min - origin) * inverse;
+            let t1 = (bounds.max - origin) * inverse;
+            // NaN from a ray lying in the plane of a side is ignored by min and max
+            min = min.max(t0.min(t1));
+            max = max.min(t0.max(t1));
+        }
+
+        min <= max
+    }
+
     /// Creates new bounding box that surrounds two bounding boxes.
     pub fn surrounding_box(&self, second: Aabb) -> Self {
         Aabb {
@@ -87,6 +118,15 @@ impl Aabb {
         }
     }
 
+    /// Returns surface area of this bounding box, zero for an empty box.
+    pub fn surface_area(&self) -> f32 {
+        let (x, y, z) = (self.x.size(), self.y.size(), self.z.size());
+        if x < 0.0 || y < 0.0 || z < 0.0 {
+            return 0.0;
+        }
+        2.0 * (x * y + y * z + z * x)
+    }
+
     /// Returns list of verticles of this bounding box.
     pub fn get_box_points(&self) -> Vec<V3<f32>> {
         iproduct!(0..=1, 0..=1, 0..=1)
@@ -2,35 +2,165 @@ use crate::hit::*;
 use crate::hittables::{Aabb, HittableList};
 use crate::misc::{HittablePdf, Pdf};
 use crate::ray::*;
+use crate::V3;
+
+use std::sync::Arc;
+
+/// How the objects of a node are divided between its children.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SplitMethod {
+    /// Objects are sorted along the longest axis and split in half, fast to build but slow on uneven scenes.
+    Median,
+    /// Split minimizing the surface area heuristic, evaluated at the borders of bins along each axis.
+    Sah {
+        /// Number of bins along each axis.
+        bins: usize,
+    },
+}
+
+/// Settings of building the Bvh.
+#[derive(Clone, Copy, Debug)]
+pub struct BvhSettings {
+    /// How the objects are divided between children.
+    pub split: SplitMethod,
+    /// Most objects in a leaf, nodes with more objects are always split.
+    pub max_leaf_size: usize,
+    /// Cost of visiting a node, relative to the cost of intersecting one object.
+    pub traversal_cost: f32,
+    /// Refitted tree is built again, when its cost by the surface area heuristic grows by this factor over the cost right after building.
+    pub rebuild_threshold: f32,
+}
+
+impl Default for BvhSettings {
+    fn default() -> Self {
+        BvhSettings {
+            split: SplitMethod::Sah { bins: 16 },
+            max_leaf_size: 4,
+            traversal_cost: 1.0,
+            rebuild_threshold: 1.5,
+        }
+    }
+}
+
+impl BvhSettings {
+    /// Returns true if the refitted tree got so much slower to trace, that it should be built again.
+    ///
+    /// `built_cost` - Cost of the tree by the surface area heuristic right after it was built, from its statistics.
+    ///
+    /// `refitted_cost` - Cost of the same tree after refitting.
+    pub fn needs_rebuild(&self, built_cost: f32, refitted_cost: f32) -> bool {
+        refitted_cost > built_cost * self.rebuild_threshold
+    }
+}
+
+/// Statistics of the built Bvh, to compare different settings.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BvhStats {
+    /// Number of all nodes, including leaves.
+    pub nodes: usize,
+    /// Number of leaves.
+    pub leaves: usize,
+    /// Number of objects in all leaves.
+    pub objects: usize,
+    /// Length of the longest path from the root to a leaf, root alone has depth 1.
+    pub depth: usize,
+    /// Most objects in one leaf.
+    pub max_leaf_size: usize,
+    /// Expected cost of tracing a ray through the tree by the surface area heuristic, in units of intersecting one object.
+    pub sah_cost: f32,
+}
+
+/// Children of the Bvh node.
+#[derive(Clone)]
+pub(crate) enum BvhContent {
+    /// Node split into two smaller nodes.
+    Inner(Arc<BvhNode>, Arc<BvhNode>),
+    /// Node with objects tested one by one, and indices of the objects in the list the tree was built from.
+    Leaf(Vec<Arc<dyn HittablePdf>>, Vec<usize>),
+}
 
 #[derive(Clone)]
 /// Octtree that contains Scene, for checking ray intersections in O(nlog(n)) time rather than in O(n^2)
+///
+/// Objects are shared with the list the tree was built from, so cloning the tree doesn't copy them.
 pub struct BvhNode {
     boxx: Aabb,
-    left: Box<dyn HittablePdf>,
-    right: Box<dyn HittablePdf>,
+    content: BvhContent,
+}
+
+/// Object with its bounding box computed once, used while building the tree.
+struct BuildItem {
+    object: Arc<dyn HittablePdf>,
+    index: usize,
+    bounding_box: Aabb,
+    centroid: V3<f32>,
 }
 
+/// Nodes with more objects build their children in parallel.
+const PARALLEL_BUILD_THRESHOLD: usize = 1024;
+
 impl Hittable for BvhNode {
     fn hit(&self, ray: &Ray) -> Option<Hit> {
-        if self.boxx.hit(ray) {
-            let hit_right = self.right.hit(ray);
-
-            match self.left.hit(ray) {
-                Some(hit_left) => match hit_right {
-                    Some(hit_right) => {
-                        if hit_left.t < hit_right.t {
-                            Some(hit_left)
-                        } else {
-                            Some(hit_right)
-                        }
+        if !self.boxx.hit(ray) {
+            return None;
+        }
+
+        match &self.content {
+            BvhContent::Inner(left, right) => match (left.hit(ray), right.hit(ray)) {
+                (Some(hit_left), Some(hit_right)) => {
+                    if hit_left.t < hit_right.t {
+                        Some(hit_left)
+                    } else {
+                        Some(hit_right)
                     }
-                    None => Some(hit_left),
-                },
-                None => hit_right,
+                }
+                (hit_left, hit_right) => hit_left.or(hit_right),
+            },
+            BvhContent::Leaf(objects, _) => objects
+                .iter()
+                .filter_map(|object| object.hit(ray))
+                .min_by(|x, y| x.t.partial_cmp(&y.t).expect("Tried to compare a NaN")),
+        }
+    }
+
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        if !self.boxx.hit(ray) {
+            return false;
+        }
+
+        match &self.content {
+            BvhContent::Inner(left, right) => {
+                left.occluded(ray, t_max) || right.occluded(ray, t_max)
+            }
+            BvhContent::Leaf(objects, _) => {
+                objects.iter().any(|object| object.occluded(ray, t_max))
+            }
+        }
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> V3<f32> {
+        if !self.boxx.hit(ray) {
+            return V3::new(1.0, 1.0, 1.0);
+        }
+
+        match &self.content {
+            BvhContent::Inner(left, right) => {
+                let transmittance = left.transmittance(ray, t_max);
+                if transmittance == V3::default() {
+                    return transmittance;
+                }
+                transmittance.hadamard(right.transmittance(ray, t_max))
+            }
+            BvhContent::Leaf(objects, _) => {
+                let mut transmittance = V3::new(1.0, 1.0, 1.0);
+                for object in objects {
+                    transmittance = transmittance.hadamard(object.transmittance(ray, t_max));
+                    if transmittance == V3::default() {
+                        break;
+                    }
+                }
+                transmittance
             }
-        } else {
-            None
         }
     }
 
@@ -40,75 +170,287 @@ impl Hittable for BvhNode {
 }
 
 impl BvhNode {
-    /// Tree is created by recursively dividing the scene, in form of hittableList, in half by plane aligned each time to the longest axis.
+    /// Tree is created by recursively dividing the scene, in form of hittableList, by the split with the lowest surface area heuristic cost.
     pub fn new(hlist: &HittableList) -> BvhNode {
-        // creating bounding box spanning whole set
-        let mut sorted = hlist.list.clone();
-        let list_bounding_box = sorted.iter().fold(Aabb::default(), |acc, b| {
-            acc.surrounding_box(b.bounding_box())
-        });
+        BvhNode::with_settings(hlist, BvhSettings::default())
+    }
 
-        match list_bounding_box.longest_axis() {
-            0 => {
-                sorted.sort_by(|a, b| {
-                    a.bounding_box()
-                        .x
-                        .min
-                        .partial_cmp(&b.bounding_box().x.min)
-                        .unwrap()
-                });
+    /// Returns tree built with given settings, big nodes are built in parallel.
+    ///
+    /// `hlist` - Objects in the tree, they are shared, not copied.
+    ///
+    /// `settings` - How the tree is built.
+    pub fn with_settings(hlist: &HittableList, settings: BvhSettings) -> BvhNode {
+        let mut items: Vec<BuildItem> = hlist
+            .list
+            .iter()
+            .enumerate()
+            .map(|(index, object)| {
+                let bounding_box = object.bounding_box();
+                let centroid = V3::new(
+                    (bounding_box.x.min + bounding_box.x.max) / 2.0,
+                    (bounding_box.y.min + bounding_box.y.max) / 2.0,
+                    (bounding_box.z.min + bounding_box.z.max) / 2.0,
+                );
+                BuildItem {
+                    object: object.clone(),
+                    index,
+                    bounding_box,
+                    centroid,
+                }
+            })
+            .collect();
+
+        BvhNode::build(&mut items, &settings)
+    }
+
+    /// Returns children of the node.
+    pub(crate) fn content(&self) -> &BvhContent {
+        &self.content
+    }
+
+    /// Returns list of all objects in the tree, in the order of leaves.
+    pub fn objects(&self) -> HittableList {
+        let mut list = HittableList::new();
+        self.collect_objects(&mut list);
+        list
+    }
+
+    fn collect_objects(&self, list: &mut HittableList) {
+        match &self.content {
+            BvhContent::Inner(left, right) => {
+                left.collect_objects(list);
+                right.collect_objects(list);
             }
-            1 => {
-                sorted.sort_by(|a, b| {
-                    a.bounding_box()
-                        .y
-                        .min
-                        .partial_cmp(&b.bounding_box().y.min)
-                        .unwrap()
-                });
+            BvhContent::Leaf(objects, _) => {
+                for object in objects {
+                    list.add_shared(object.clone());
+                }
+            }
+        }
+    }
+
+    /// Replaces objects of the tree and updates bounding boxes of nodes to them, keeping the structure of the tree.
+    ///
+    /// Nodes shared with clones of the tree are copied, so the clones stay unchanged.
+    ///
+    /// `hlist` - New objects in the order of leaves, like returned by [`objects`](BvhNode::objects), as many as there are in the tree.
+    pub fn refit(&mut self, hlist: &HittableList) {
+        assert_eq!(
+            self.stats(1.0).objects,
+            hlist.list.len(),
+            "Tree was refitted with other number of objects than it has."
+        );
+        self.refit_objects(&hlist.list);
+    }
+
+    /// Refits the node with objects from the beginning of the slice, returns how many of them it used.
+    fn refit_objects(&mut self, objects: &[Arc<dyn HittablePdf>]) -> usize {
+        match &mut self.content {
+            BvhContent::Inner(left, right) => {
+                let left = Arc::make_mut(left);
+                let right = Arc::make_mut(right);
+                let used = left.refit_objects(objects);
+                let used = used + right.refit_objects(&objects[used..]);
+                self.boxx = left.boxx.surrounding_box(right.boxx.clone());
+                used
             }
-            2 => {
-                sorted.sort_by(|a, b| {
-                    a.bounding_box()
-                        .z
-                        .min
-                        .partial_cmp(&b.bounding_box().z.min)
-                        .unwrap()
+            BvhContent::Leaf(leaf, _) => {
+                let count = leaf.len();
+                leaf.clone_from_slice(&objects[..count]);
+                self.boxx = leaf.iter().fold(Aabb::default(), |acc, object| {
+                    acc.surrounding_box(object.bounding_box())
                 });
+                count
             }
-            _ => (),
         }
+    }
 
-        let sorted_length = sorted.len();
-        match sorted_length {
-            1 => BvhNode {
-                boxx: sorted[0]
-                    .bounding_box()
-                    .surrounding_box(sorted[0].bounding_box()),
-                left: sorted[0].clone(),
-                right: sorted[0].clone(),
-            },
-            2 => BvhNode {
-                boxx: sorted[0]
-                    .bounding_box()
-                    .surrounding_box(sorted[1].bounding_box()),
-                left: sorted[0].clone(),
-                right: sorted[1].clone(),
+    /// Returns statistics of the tree.
+    ///
+    /// `traversal_cost` - Cost of visiting a node used in the surface area heuristic, relative to the cost of intersecting one object.
+    pub fn stats(&self, traversal_cost: f32) -> BvhStats {
+        let mut stats = BvhStats::default();
+        let cost = self.collect_stats(&mut stats, 1, traversal_cost);
+        stats.sah_cost = cost / self.boxx.surface_area().max(f32::EPSILON);
+        stats
+    }
+
+    /// Adds the node to statistics, returns its cost multiplied by its surface area.
+    fn collect_stats(&self, stats: &mut BvhStats, depth: usize, traversal_cost: f32) -> f32 {
+        stats.nodes += 1;
+        stats.depth = stats.depth.max(depth);
+        let area = self.boxx.surface_area();
+
+        match &self.content {
+            BvhContent::Inner(left, right) => {
+                area * traversal_cost
+                    + left.collect_stats(stats, depth + 1, traversal_cost)
+                    + right.collect_stats(stats, depth + 1, traversal_cost)
+            }
+            BvhContent::Leaf(objects, _) => {
+                stats.leaves += 1;
+                stats.objects += objects.len();
+                stats.max_leaf_size = stats.max_leaf_size.max(objects.len());
+                area * objects.len() as f32
+            }
+        }
+    }
+
+    fn build(items: &mut [BuildItem], settings: &BvhSettings) -> BvhNode {
+        let boxx = items.iter().fold(Aabb::default(), |acc, item| {
+            acc.surrounding_box(item.bounding_box.clone())
+        });
+
+        let leaf = |items: &mut [BuildItem], boxx: Aabb| BvhNode {
+            boxx,
+            content: BvhContent::Leaf(
+                items.iter().map(|item| item.object.clone()).collect(),
+                items.iter().map(|item| item.index).collect(),
+            ),
+        };
+
+        if items.len() <= 1 {
+            return leaf(items, boxx);
+        }
+
+        let mid = match settings.split {
+            SplitMethod::Median => {
+                if items.len() <= settings.max_leaf_size {
+                    return leaf(items, boxx);
+                }
+                Self::median_split(items)
+            }
+            SplitMethod::Sah { bins } => match Self::sah_split(items, &boxx, bins, settings) {
+                Some(mid) => mid,
+                None if items.len() <= settings.max_leaf_size => return leaf(items, boxx),
+                // All centroids are in the same place, or splitting doesn't pay off for too big leaf
+                None => Self::median_split(items),
             },
-            _ => {
-                let left = BvhNode::new(&HittableList {
-                    list: sorted[0..sorted_length / 2].to_vec(),
-                });
-                let right = BvhNode::new(&HittableList {
-                    list: sorted[sorted_length / 2..sorted_length].to_vec(),
-                });
-                BvhNode {
-                    boxx: left.boxx.surrounding_box(right.boxx.clone()),
-                    left: Box::new(left),
-                    right: Box::new(right),
+        };
+
+        let (left_items, right_items) = items.split_at_mut(mid);
+        let (left, right) = if left_items.len() + right_items.len() > PARALLEL_BUILD_THRESHOLD {
+            rayon::join(
+                || BvhNode::build(left_items, settings),
+                || BvhNode::build(right_items, settings),
+            )
+        } else {
+            (
+                BvhNode::build(left_items, settings),
+                BvhNode::build(right_items, settings),
+            )
+        };
+
+        BvhNode {
+            boxx,
+            content: BvhContent::Inner(Arc::new(left), Arc::new(right)),
+        }
+    }
+
+    /// Sorts items along the longest axis of their centroids, returns index of the middle.
+    fn median_split(items: &mut [BuildItem]) -> usize {
+        let centroids = Self::centroid_bounds(items);
+        let axis = centroids.longest_axis() as u32;
+        items.sort_by(|a, b| {
+            a.centroid[axis]
+                .partial_cmp(&b.centroid[axis])
+                .expect("Tried to compare a NaN")
+        });
+        items.len() / 2
+    }
+
+    /// Returns box around centroids of the items.
+    fn centroid_bounds(items: &[BuildItem]) -> Aabb {
+        let (min, max) = items.iter().fold(
+            (items[0].centroid, items[0].centroid),
+            |(min, max), item| (min.min(item.centroid), max.max(item.centroid)),
+        );
+        Aabb {
+            x: crate::misc::Interval::new(min.x, max.x),
+            y: crate::misc::Interval::new(min.y, max.y),
+            z: crate::misc::Interval::new(min.z, max.z),
+        }
+    }
+
+    /// Partitions items by the binned split with the lowest surface area heuristic cost, returns index of the first item of the right child.
+    ///
+    /// Returns None if centroids can't be separated, or if the split costs more than a leaf which is small enough.
+    fn sah_split(
+        items: &mut [BuildItem],
+        boxx: &Aabb,
+        bins: usize,
+        settings: &BvhSettings,
+    ) -> Option<usize> {
+        let bins = bins.max(2);
+        let centroids = Self::centroid_bounds(items);
+        let parent_area = boxx.surface_area().max(f32::EPSILON);
+
+        let bin_of = |centroid: f32, interval: crate::misc::Interval| {
+            let bin = ((centroid - interval.min) / interval.size() * bins as f32) as usize;
+            bin.min(bins - 1)
+        };
+
+        // Axis, index of the last bin of the left child and cost of the best split
+        let mut best: Option<(u32, usize, f32)> = None;
+
+        for axis in 0..3 {
+            let interval = [centroids.x, centroids.y, centroids.z][axis as usize];
+            if interval.size() <= 0.0 {
+                continue;
+            }
+
+            let mut counts = vec![0usize; bins];
+            let mut boxes = vec![Aabb::default(); bins];
+            for item in items.iter() {
+                let bin = bin_of(item.centroid[axis], interval);
+                counts[bin] += 1;
+                boxes[bin] = boxes[bin].surrounding_box(item.bounding_box.clone());
+            }
+
+            // Areas and counts of everything right of each split, sweeping from the right
+            let mut right_costs = vec![0.0; bins];
+            let mut right_box = Aabb::default();
+            let mut right_count = 0;
+            for bin in (1..bins).rev() {
+                right_box = right_box.surrounding_box(boxes[bin].clone());
+                right_count += counts[bin];
+                right_costs[bin - 1] = right_box.surface_area() * right_count as f32;
+            }
+
+            let mut left_box = Aabb::default();
+            let mut left_count = 0;
+            for bin in 0..bins - 1 {
+                left_box = left_box.surrounding_box(boxes[bin].clone());
+                left_count += counts[bin];
+                if left_count == 0 || left_count == items.len() {
+                    continue;
                 }
+
+                let cost = settings.traversal_cost
+                    + (left_box.surface_area() * left_count as f32 + right_costs[bin])
+                        / parent_area;
+                if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                    best = Some((axis, bin, cost));
+                }
+            }
+        }
+
+        let (axis, last_left_bin, cost) = best?;
+        if items.len() <= settings.max_leaf_size && cost >= items.len() as f32 {
+            return None;
+        }
+
+        let interval = [centroids.x, centroids.y, centroids.z][axis as usize];
+        let mut mid = 0;
+        for i in 0..items.len() {
+            if bin_of(items[i].centroid[axis], interval) <= last_left_bin {
+                items.swap(i, mid);
+                mid += 1;
             }
         }
+
+        Some(mid)
     }
 }
 
@@ -121,3 +463,88 @@ impl Pdf for BvhNode {
         todo!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitive::Sphere;
+
+    #[test]
+    fn sah_build_test() {
+        let mut list = HittableList::new();
+        // Dense cluster and a few far away objects, which median split handles badly
+        for i in 0..64 {
+            list.add(Sphere::new(V3::new(i as f32 * 0.1, 0.0, 0.0), 0.05, 0));
+        }
+        for i in 0..4 {
+            list.add(Sphere::new(
+                V3::new(100.0 + i as f32 * 50.0, 0.0, 0.0),
+                0.05,
+                0,
+            ));
+        }
+
+        let sah = BvhNode::new(&list).stats(1.0);
+        let median = BvhNode::with_settings(
+            &list,
+            BvhSettings {
+                split: SplitMethod::Median,
+                ..BvhSettings::default()
+            },
+        )
+        .stats(1.0);
+
+        assert_eq!(sah.objects, 68);
+        assert_eq!(median.objects, 68);
+        assert!(sah.max_leaf_size <= 4);
+        assert!(sah.sah_cost < median.sah_cost);
+    }
+
+    #[test]
+    fn refit_test() {
+        let spheres = |offset: &dyn Fn(usize) -> f32| {
+            let mut list = HittableList::new();
+            for i in 0..64 {
+                list.add(Sphere::new(V3::new(i as f32 + offset(i), 0.0, 0.0), 0.4, 0));
+            }
+            list
+        };
+
+        let settings = BvhSettings::default();
+        let mut tree = BvhNode::new(&spheres(&|_| 0.0));
+        let built = tree.stats(1.0).sah_cost;
+        let order: Vec<usize> = tree
+            .objects()
+            .list
+            .iter()
+            .map(|object| object.bounding_box().x.min.round() as usize)
+            .collect();
+        let in_leaf_order = |list: HittableList| HittableList {
+            list: order.iter().map(|&i| list.list[i].clone()).collect(),
+        };
+
+        // Small movement keeps the tree good enough
+        let moved = spheres(&|i| 0.3 * (i as f32).sin());
+        tree.refit(&in_leaf_order(moved.clone()));
+        assert_eq!(tree.bounding_box().x.min, moved.bounding_box().x.min);
+        assert!(!settings.needs_rebuild(built, tree.stats(1.0).sah_cost));
+
+        // Objects swapping places across the whole tree make it much worse
+        let scrambled = spheres(&|i| ((i * 37) % 64) as f32 - i as f32);
+        tree.refit(&in_leaf_order(scrambled));
+        assert!(settings.needs_rebuild(built, tree.stats(1.0).sah_cost));
+    }
+
+    #[test]
+    #[should_panic(expected = "Tree was refitted with other number of objects than it has.")]
+    fn refit_fewer_objects_test() {
+        let mut list = HittableList::new();
+        for i in 0..8 {
+            list.add(Sphere::new(V3::new(i as f32, 0.0, 0.0), 0.4, 0));
+        }
+        let mut tree = BvhNode::new(&list);
+
+        list.list.pop();
+        tree.refit(&list);
+    }
+}
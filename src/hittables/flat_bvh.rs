@@ -0,0 +1,418 @@
+use crate::hit::*;
+use crate::hittables::traversal_stack::TraversalStack;
+use crate::hittables::{Aabb, BvhContent, BvhNode, BvhSettings, BvhStats, HittableList};
+use crate::misc::{HittablePdf, Interval, Pdf};
+use crate::ray::*;
+use crate::V3;
+
+use std::sync::Arc;
+
+/// Depth of the tree up to which the traversal stack isn't allocated, trees built from the binned heuristic are usually much shallower.
+const MAX_DEPTH: usize = 64;
+
+/// Node of the flattened Bvh, the first child of an inner node directly follows it.
+#[derive(Clone)]
+struct FlatNode {
+    bounds: Aabb,
+    /// Index of the first object of a leaf, or index of the second child of an inner node.
+    offset: u32,
+    /// Number of objects in a leaf, zero for inner nodes.
+    count: u32,
+    /// Axis along which the children are ordered, the first child is nearer for rays going in the positive direction.
+    axis: u32,
+}
+
+/// Bvh stored in one array of nodes in depth first order, traversed front to back without recursion, faster to trace than [`BvhNode`].
+#[derive(Clone)]
+pub struct FlatBvh {
+    nodes: Vec<FlatNode>,
+    /// Objects in the order of leaves, each leaf is a range of them.
+    primitives: Vec<Arc<dyn HittablePdf>>,
+    /// Index of each object in the list the tree was built from, in the order of leaves.
+    order: Vec<usize>,
+}
+
+impl FlatBvh {
+    /// Returns flattened Bvh built with the surface area heuristic.
+    ///
+    /// `hlist` - Objects in the tree, they are shared, not copied.
+    pub fn new(hlist: &HittableList) -> Self {
+        FlatBvh::from_tree(&BvhNode::new(hlist))
+    }
+
+    /// Returns flattened Bvh built with given settings.
+    ///
+    /// `hlist` - Objects in the tree, they are shared, not copied.
+    ///
+    /// `settings` - How the tree is built.
+    pub fn with_settings(hlist: &HittableList, settings: BvhSettings) -> Self {
+        FlatBvh::from_tree(&BvhNode::with_settings(hlist, settings))
+    }
+
+    /// Returns flattened copy of the tree, objects are shared with it.
+    ///
+    /// `tree` - Tree to flatten.
+    pub fn from_tree(tree: &BvhNode) -> Self {
+        let mut bvh = FlatBvh {
+            nodes: Vec::new(),
+            primitives: Vec::new(),
+            order: Vec::new(),
+        };
+        // Tree of an empty list is a leaf without objects, which has nothing to trace
+        if !matches!(tree.content(), BvhContent::Leaf(objects, _) if objects.is_empty()) {
+            bvh.flatten(tree);
+        }
+        bvh
+    }
+
+    /// Pushes the node and its subtree, returns index of the node.
+    fn flatten(&mut self, node: &BvhNode) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(FlatNode {
+            bounds: node.bounding_box(),
+            offset: 0,
+            count: 0,
+            axis: 0,
+        });
+
+        match node.content() {
+            BvhContent::Inner(left, right) => {
+                // Children are ordered along the axis, on which their centers are the farthest apart
+                let center = |bounds: Aabb| {
+                    V3::new(
+                        bounds.x.min + bounds.x.max,
+                        bounds.y.min + bounds.y.max,
+                        bounds.z.min + bounds.z.max,
+                    )
+                };
+                let distance = center(right.bounding_box()) - center(left.bounding_box());
+                let axis = (0..3)
+                    .max_by(|&a, &b| {
+                        distance[a]
+                            .abs()
+                            .partial_cmp(&distance[b].abs())
+                            .expect("Tried to compare a NaN")
+                    })
+                    .unwrap_or(0);
+                let (first, second) = if distance[axis] >= 0.0 {
+                    (left, right)
+                } else {
+                    (right, left)
+                };
+
+                self.flatten(first);
+                let second = self.flatten(second);
+                self.nodes[index].offset = second as u32;
+                self.nodes[index].axis = axis;
+            }
+            BvhContent::Leaf(objects, indices) => {
+                self.nodes[index].offset = self.primitives.len() as u32;
+                self.nodes[index].count = objects.len() as u32;
+                self.primitives.extend(objects.iter().cloned());
+                self.order.extend(indices);
+            }
+        }
+
+        index
+    }
+
+    /// Returns list of all objects in the tree, in the order of leaves.
+    pub fn objects(&self) -> HittableList {
+        HittableList {
+            list: self.primitives.clone(),
+        }
+    }
+
+    /// Returns index of each object in the list the tree was built from, in the order of leaves.
+    pub(crate) fn order(&self) -> &[usize] {
+        &self.order
+    }
+
+    /// Replaces the object, the tree has to be refitted, if its bounding box changed.
+    ///
+    /// `index` - Index of the object in the order of leaves.
+    ///
+    /// `object` - New object.
+    pub(crate) fn replace(&mut self, index: usize, object: Arc<dyn HittablePdf>) {
+        self.primitives[index] = object;
+    }
+
+    /// Updates bounding boxes of nodes to the current bounding boxes of objects, keeping the structure of the tree.
+    ///
+    /// Much faster than building the tree again, but the tree gets slower to trace as objects move far from where they were, when it was built.
+    pub fn refit(&mut self) {
+        // Children are stored after their parent, so they are updated first going backwards
+        for index in (0..self.nodes.len()).rev() {
+            let node = &self.nodes[index];
+            let bounds = if node.count > 0 {
+                let first = node.offset as usize;
+                self.primitives[first..first + node.count as usize]
+                    .iter()
+                    .fold(Aabb::default(), |acc, primitive| {
+                        acc.surrounding_box(primitive.bounding_box())
+                    })
+            } else {
+                self.nodes[index + 1]
+                    .bounds
+                    .surrounding_box(self.nodes[node.offset as usize].bounds.clone())
+            };
+            self.nodes[index].bounds = bounds;
+        }
+    }
+
+    /// Returns statistics of the tree.
+    ///
+    /// `traversal_cost` - Cost of visiting a node used in the surface area heuristic, relative to the cost of intersecting one object.
+    pub fn stats(&self, traversal_cost: f32) -> BvhStats {
+        let mut stats = BvhStats::default();
+        if let Some(root) = self.nodes.first() {
+            let cost = self.collect_stats(0, &mut stats, 1, traversal_cost);
+            stats.sah_cost = cost / root.bounds.surface_area().max(f32::EPSILON);
+        }
+        stats
+    }
+
+    /// Adds the node to statistics, returns its cost multiplied by its surface area.
+    fn collect_stats(
+        &self,
+        index: usize,
+        stats: &mut BvhStats,
+        depth: usize,
+        traversal_cost: f32,
+    ) -> f32 {
+        let node = &self.nodes[index];
+        stats.nodes += 1;
+        stats.depth = stats.depth.max(depth);
+        let area = node.bounds.surface_area();
+
+        if node.count > 0 {
+            stats.leaves += 1;
+            stats.objects += node.count as usize;
+            stats.max_leaf_size = stats.max_leaf_size.max(node.count as usize);
+            area * node.count as f32
+        } else {
+            area * traversal_cost
+                + self.collect_stats(index + 1, stats, depth + 1, traversal_cost)
+                + self.collect_stats(node.offset as usize, stats, depth + 1, traversal_cost)
+        }
+    }
+
+    /// Visits leaves hit by the Ray front to back, until `leaf` returns true, then returns true.
+    ///
+    /// `setting` - Setting of the Ray, whose time interval `leaf` can shrink to skip farther nodes.
+    ///
+    /// `leaf` - Called with objects of each visited leaf.
+    fn traverse(
+        &self,
+        ray: &Ray,
+        setting: &mut RaySetting,
+        mut leaf: impl FnMut(&[Arc<dyn HittablePdf>], &mut RaySetting) -> bool,
+    ) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let inverse_direction = V3::new(1.0 / ray.end.x, 1.0 / ray.end.y, 1.0 / ray.end.z);
+
+        let mut stack = TraversalStack::<usize, MAX_DEPTH>::new(0);
+        let mut current = 0;
+
+        loop {
+            let node = &self.nodes[current];
+
+            if node
+                .bounds
+                .hit_interval(ray.origin, inverse_direction, setting.ray_time)
+            {
+                if node.count > 0 {
+                    let first = node.offset as usize;
+                    if leaf(
+                        &self.primitives[first..first + node.count as usize],
+                        setting,
+                    ) {
+                        return true;
+                    }
+                } else {
+                    // Nearer child is visited first, the other one waits on the stack
+                    let (near, far) = if inverse_direction[node.axis] < 0.0 {
+                        (node.offset as usize, current + 1)
+                    } else {
+                        (current + 1, node.offset as usize)
+                    };
+                    stack.push(far);
+                    current = near;
+                    continue;
+                }
+            }
+
+            match stack.pop() {
+                Some(next) => current = next,
+                None => return false,
+            }
+        }
+    }
+}
+
+impl Hittable for FlatBvh {
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        // Interval of the ray shrinks to the closest hit found, so farther nodes are skipped
+        let mut setting = *ray.setting;
+        let mut closest = None;
+
+        self.traverse(ray, &mut setting, |primitives, setting| {
+            for primitive in primitives {
+                if let Some(hit) = primitive.hit(&Ray {
+                    setting: &*setting,
+                    ..*ray
+                }) {
+                    if hit.t <= setting.ray_time.max {
+                        setting.ray_time.max = hit.t;
+                        closest = Some(hit);
+                    }
+                }
+            }
+            false
+        });
+
+        closest
+    }
+
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        let mut setting = RaySetting {
+            ray_time: Interval::new(ray.setting.ray_time.min, t_max),
+            ..*ray.setting
+        };
+
+        self.traverse(ray, &mut setting, |primitives, _| {
+            primitives
+                .iter()
+                .any(|primitive| primitive.occluded(ray, t_max))
+        })
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> V3<f32> {
+        let mut setting = RaySetting {
+            ray_time: Interval::new(ray.setting.ray_time.min, t_max),
+            ..*ray.setting
+        };
+
+        // Media along the whole segment let light through, it stops at the first surface
+        let mut transmittance = V3::new(1.0, 1.0, 1.0);
+        self.traverse(ray, &mut setting, |primitives, _| {
+            for primitive in primitives {
+                transmittance = transmittance.hadamard(primitive.transmittance(ray, t_max));
+                if transmittance == V3::default() {
+                    return true;
+                }
+            }
+            false
+        });
+        transmittance
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map(|root| root.bounds.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Pdf for FlatBvh {
+    fn value(&self, origin: V3<f32>, direction: V3<f32>) -> f32 {
+        let weight = 1.0 / self.primitives.len() as f32;
+        self.primitives
+            .iter()
+            .map(|primitive| primitive.value(origin, direction) * weight)
+            .sum()
+    }
+
+    fn generate(&self, origin: V3<f32>) -> V3<f32> {
+        use rand::seq::SliceRandom;
+
+        self.primitives
+            .choose(&mut rand::thread_rng())
+            .expect("FlatBvh is empty, thus cannot generate random direction toward it.")
+            .generate(origin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittables::SplitMethod;
+    use crate::primitive::Sphere;
+    use crate::RaySetting;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn same_hits_as_tree_test() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        let mut list = HittableList::new();
+        for _ in 0..200 {
+            let center = V3::new(
+                rng.gen_range(-10.0..10.0),
+                rng.gen_range(-10.0..10.0),
+                rng.gen_range(-10.0..10.0),
+            );
+            list.add(Sphere::new(center, rng.gen_range(0.1..1.0), 0));
+        }
+
+        let tree = BvhNode::new(&list);
+        let flat = FlatBvh::from_tree(&tree);
+        assert_eq!(flat.stats(1.0).objects, 200);
+
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+        for _ in 0..500 {
+            let ray = Ray::for_test(
+                V3::new(0.0, 0.0, 0.0),
+                V3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                ),
+                &setting,
+            );
+
+            let expected = tree.hit(&ray).map(|hit| hit.t);
+            let t = flat.hit(&ray).map(|hit| hit.t);
+            assert_eq!(expected, t);
+
+            let occluded = expected.is_some_and(|t| t <= 5.0);
+            assert_eq!(flat.occluded(&ray, 5.0), occluded);
+            assert_eq!(tree.occluded(&ray, 5.0), occluded);
+        }
+    }
+
+    #[test]
+    fn deep_tree_test() {
+        // Each sphere is less than half the size of the previous one, so every split cuts off just the biggest spheres
+        let mut list = HittableList::new();
+        for i in 0..100 {
+            let size = 0.45f32.powi(i);
+            list.add(Sphere::new(V3::new(size, 0.0, 0.0), size * 0.25, 0));
+        }
+        let settings = BvhSettings {
+            split: SplitMethod::Sah { bins: 2 },
+            ..BvhSettings::default()
+        };
+        let flat = FlatBvh::with_settings(&list, settings);
+        assert!(flat.stats(1.0).depth > MAX_DEPTH);
+
+        // Ray along the spheres goes through all the nested nodes, from the smallest ones
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.0, f32::INFINITY),
+        };
+        let ray = Ray::for_test(V3::new(-1.0, 0.0, 0.0), V3::new(1.0, 0.0, 0.0), &setting);
+        let t = flat.hit(&ray).map(|hit| hit.t).unwrap();
+        assert!(t > 1.0 && t < 1.1);
+        assert!(flat.occluded(&ray, 1.1));
+        assert!(!flat.occluded(&ray, 0.5));
+    }
+}
@@ -2,12 +2,15 @@ use crate::hit::*;
 use crate::hittables::Aabb;
 use crate::misc::{HittablePdf, Pdf};
 use crate::ray::Ray;
+use crate::V3;
+
+use std::sync::Arc;
 
 /// hittable objects aggregated into list.
 #[derive(Clone, Default)]
 pub struct HittableList {
-    /// Underlying list of hittable objects
-    pub list: Vec<Box<dyn HittablePdf>>,
+    /// Underlying list of hittable objects, shared with other lists and trees built from this list
+    pub list: Vec<Arc<dyn HittablePdf>>,
 }
 
 impl HittableList {
@@ -16,9 +19,16 @@ impl HittableList {
         HittableList { list: Vec::new() }
     }
 
-    /// Adds object to the list
-    pub fn add<T: HittablePdf + 'static>(&mut self, object: T) {
-        self.list.push(Box::new(object));
+    /// Adds object to the list, returns shared reference to it, so the same object can be added to other lists, like lights, without copying it
+    pub fn add<T: HittablePdf + 'static>(&mut self, object: T) -> Arc<dyn HittablePdf> {
+        let object: Arc<dyn HittablePdf> = Arc::new(object);
+        self.add_shared(object.clone());
+        object
+    }
+
+    /// Adds object that is already shared with other lists
+    pub fn add_shared(&mut self, object: Arc<dyn HittablePdf>) {
+        self.list.push(object);
     }
 }
 
@@ -31,6 +41,21 @@ impl Hittable for HittableList {
         //choose the closest object to the cam, the one that is visible
     }
 
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        self.list.iter().any(|object| object.occluded(ray, t_max))
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> V3<f32> {
+        let mut transmittance = V3::new(1.0, 1.0, 1.0);
+        for object in &self.list {
+            transmittance = transmittance.hadamard(object.transmittance(ray, t_max));
+            if transmittance == V3::default() {
+                break;
+            }
+        }
+        transmittance
+    }
+
     // Adds consequtive objects from list to the bounding box, to create bounding box for the whole scene
     fn bounding_box(&self) -> Aabb {
         self.list.iter().fold(
@@ -64,3 +89,39 @@ impl Pdf for HittableList {
             .generate(origin)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittables::{BvhNode, FlatBvh};
+    use crate::primitive::Sphere;
+
+    #[test]
+    fn shared_light_test() {
+        let mut world = HittableList::new();
+        world.add(Sphere::new(V3::new(0.0, 0.0, -1.0), 0.5, 0));
+        let light = world.add(Sphere::new(V3::new(0.0, 2.0, -1.0), 0.2, 1));
+
+        let mut lights = HittableList::new();
+        lights.add_shared(light.clone());
+        assert!(Arc::ptr_eq(&lights.list[0], &world.list[1]));
+        assert!(Arc::ptr_eq(&lights.list[0], &light));
+    }
+
+    #[test]
+    fn trees_share_objects_test() {
+        let mut world = HittableList::new();
+        for i in 0..16 {
+            world.add(Sphere::new(V3::new(i as f32, 0.0, 0.0), 0.4, 0));
+        }
+
+        let tree = BvhNode::new(&world);
+        let flat = FlatBvh::new(&world);
+        for objects in [tree.objects(), flat.objects()] {
+            assert_eq!(objects.list.len(), world.list.len());
+            for object in &objects.list {
+                assert!(world.list.iter().any(|shared| Arc::ptr_eq(shared, object)));
+            }
+        }
+    }
+}
@@ -2,8 +2,15 @@
 
 mod aabb;
 mod bvh;
+mod flat_bvh;
 mod hittable_list;
+mod tlas;
+mod traversal_stack;
+mod wide_bvh;
 
 pub use aabb::*;
 pub use bvh::*;
+pub use flat_bvh::*;
 pub use hittable_list::*;
+pub use tlas::*;
+pub use wide_bvh::*;
@@ -0,0 +1,262 @@
+use crate::hit::*;
+use crate::hittables::{Aabb, BvhSettings, BvhStats, FlatBvh, HittableList};
+use crate::misc::{HittablePdf, Pdf};
+use crate::ray::*;
+use crate::transform::{Transform, Transformed};
+use crate::V3;
+
+use std::sync::Arc;
+
+/// Object placed into the Tlas by a transformation.
+#[derive(Clone)]
+struct Instance {
+    /// Object with its own bottom level Bvh, shared by all its instances.
+    object: Arc<dyn HittablePdf>,
+    transform: Transform,
+    /// Index of the instance among objects of the top level Bvh, None until it is rebuilt.
+    slot: Option<usize>,
+}
+
+/// Two-level Bvh, the top level is built over instances of objects, which have their own bottom level Bvh, like a [`Mesh`](crate::primitive::Mesh) or a [`FlatBvh`].
+///
+/// Moving instances only refits the small top level, so animated scenes don't rebuild their static geometry for every frame.
+#[derive(Clone)]
+pub struct Tlas {
+    instances: Vec<Instance>,
+    bvh: FlatBvh,
+    settings: BvhSettings,
+    /// Cost of the top level by the surface area heuristic right after it was built.
+    built_cost: f32,
+}
+
+impl Tlas {
+    /// Returns empty Tlas.
+    pub fn new() -> Self {
+        Tlas::with_settings(BvhSettings::default())
+    }
+
+    /// Returns empty Tlas, whose top level is built with given settings.
+    ///
+    /// `settings` - How the top level is built.
+    pub fn with_settings(settings: BvhSettings) -> Self {
+        Tlas {
+            instances: Vec::new(),
+            bvh: FlatBvh::new(&HittableList::new()),
+            settings,
+            built_cost: 0.0,
+        }
+    }
+
+    /// Adds instance of the object, returns its index. The instance is traced after the Tlas is rebuilt.
+    ///
+    /// `object` - Shared object, like a big mesh or a Bvh, which is not copied.
+    ///
+    /// `transform` - Transformation from the object to the world.
+    pub fn add(&mut self, object: Arc<dyn HittablePdf>, transform: Transform) -> usize {
+        self.instances.push(Instance {
+            object,
+            transform,
+            slot: None,
+        });
+        self.instances.len() - 1
+    }
+
+    /// Returns transformation of the instance.
+    ///
+    /// `instance` - Index returned by [`add`](Tlas::add).
+    pub fn transform(&self, instance: usize) -> Transform {
+        self.instances[instance].transform
+    }
+
+    /// Moves the instance, the Tlas has to be refitted or rebuilt before it is traced again.
+    ///
+    /// `instance` - Index returned by [`add`](Tlas::add).
+    ///
+    /// `transform` - New transformation from the object to the world.
+    pub fn set_transform(&mut self, instance: usize, transform: Transform) {
+        let instance = &mut self.instances[instance];
+        instance.transform = transform;
+        if let Some(slot) = instance.slot {
+            self.bvh.replace(
+                slot,
+                Arc::new(Transformed::new(instance.object.clone(), transform)),
+            );
+        }
+    }
+
+    /// Updates bounding boxes of the top level to moved instances, or rebuilds it, if instances were added since it was built or refitting made it too slow to trace. Returns true if the top level was built again.
+    pub fn refit(&mut self) -> bool {
+        if self
+            .instances
+            .iter()
+            .any(|instance| instance.slot.is_none())
+        {
+            self.rebuild();
+            return true;
+        }
+
+        self.bvh.refit();
+        let cost = self.stats().sah_cost;
+        if self.settings.needs_rebuild(self.built_cost, cost) {
+            self.rebuild();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Builds the top level again, which keeps tracing fast after instances moved far from where they were.
+    pub fn rebuild(&mut self) {
+        let mut hlist = HittableList::new();
+        for instance in &self.instances {
+            hlist.add(Transformed::new(
+                instance.object.clone(),
+                instance.transform,
+            ));
+        }
+        self.bvh = FlatBvh::with_settings(&hlist, self.settings);
+
+        for (slot, &index) in self.bvh.order().iter().enumerate() {
+            self.instances[index].slot = Some(slot);
+        }
+        self.built_cost = self.stats().sah_cost;
+    }
+
+    /// Returns statistics of the top level.
+    pub fn stats(&self) -> BvhStats {
+        self.bvh.stats(self.settings.traversal_cost)
+    }
+}
+
+impl Default for Tlas {
+    fn default() -> Self {
+        Tlas::new()
+    }
+}
+
+impl Hittable for Tlas {
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        self.bvh.hit(ray)
+    }
+
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        self.bvh.occluded(ray, t_max)
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> V3<f32> {
+        self.bvh.transmittance(ray, t_max)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+}
+
+impl Pdf for Tlas {
+    fn value(&self, origin: V3<f32>, direction: V3<f32>) -> f32 {
+        self.bvh.value(origin, direction)
+    }
+
+    fn generate(&self, origin: V3<f32>) -> V3<f32> {
+        self.bvh.generate(origin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::misc::Interval;
+    use crate::primitive::Sphere;
+    use crate::RaySetting;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn refit_test() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let mut spheres = HittableList::new();
+        for _ in 0..20 {
+            let center = V3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+            );
+            spheres.add(Sphere::new(center, 0.2, 0));
+        }
+        let object: Arc<dyn HittablePdf> = Arc::new(FlatBvh::new(&spheres));
+
+        let mut tlas = Tlas::new();
+        for _ in 0..30 {
+            let offset = V3::new(
+                rng.gen_range(-10.0..10.0),
+                rng.gen_range(-10.0..10.0),
+                rng.gen_range(-10.0..10.0),
+            );
+            tlas.add(object.clone(), Transform::translation(offset));
+        }
+        assert!(tlas.refit());
+        assert_eq!(tlas.stats().objects, 30);
+
+        // Every other instance moves, refitted tree has to hit the same as the rebuilt one
+        for instance in (0..30).step_by(2) {
+            let offset = V3::new(rng.gen_range(-5.0..5.0), 0.0, rng.gen_range(-5.0..5.0));
+            let transform = tlas.transform(instance);
+            tlas.set_transform(instance, transform.then(Transform::translation(offset)));
+        }
+        tlas.refit();
+        let mut rebuilt = tlas.clone();
+        rebuilt.rebuild();
+
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+        for _ in 0..500 {
+            let ray = Ray::for_test(
+                V3::new(0.0, 0.0, 0.0),
+                V3::new(
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                    rng.gen_range(-1.0..1.0),
+                ),
+                &setting,
+            );
+
+            let expected = rebuilt.hit(&ray).map(|hit| hit.t);
+            assert_eq!(tlas.hit(&ray).map(|hit| hit.t), expected);
+            assert_eq!(tlas.occluded(&ray, 5.0), expected.is_some_and(|t| t <= 5.0));
+        }
+    }
+
+    #[test]
+    fn rebuild_when_slow_test() {
+        let object: Arc<dyn HittablePdf> = Arc::new(Sphere::new(V3::new(0.0, 0.0, 0.0), 0.4, 0));
+        let mut tlas = Tlas::new();
+        for i in 0..64 {
+            tlas.add(
+                object.clone(),
+                Transform::translation(V3::new(i as f32, 0.0, 0.0)),
+            );
+        }
+        assert!(tlas.refit());
+        let built = tlas.stats().sah_cost;
+
+        // Small movement keeps the top level good enough
+        for i in 0..64 {
+            let offset = V3::new(i as f32 + 0.3 * (i as f32).sin(), 0.0, 0.0);
+            tlas.set_transform(i, Transform::translation(offset));
+        }
+        assert!(!tlas.refit());
+        assert!(tlas.stats().sah_cost > built);
+
+        // Instances swapping places across the whole top level make it much worse, so it is built again
+        for i in 0..64 {
+            let offset = V3::new(((i * 37) % 64) as f32, 0.0, 0.0);
+            tlas.set_transform(i, Transform::translation(offset));
+        }
+        assert!(tlas.refit());
+        let mut rebuilt = tlas.clone();
+        rebuilt.rebuild();
+        assert_eq!(tlas.stats().sah_cost, rebuilt.stats().sah_cost);
+    }
+}
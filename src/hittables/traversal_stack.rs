@@ -0,0 +1,60 @@
+/// Stack of nodes waiting for traversal, kept inline for usual trees and spilled onto the heap for deeper ones.
+pub(crate) struct TraversalStack<T: Copy, const N: usize> {
+    inline: [T; N],
+    size: usize,
+    /// Entries pushed when the inline part was full, it doesn't allocate until then.
+    spilled: Vec<T>,
+}
+
+impl<T: Copy, const N: usize> TraversalStack<T, N> {
+    /// Returns empty stack.
+    ///
+    /// `fill` - Value filling the unused inline entries.
+    pub(crate) fn new(fill: T) -> Self {
+        TraversalStack {
+            inline: [fill; N],
+            size: 0,
+            spilled: Vec::new(),
+        }
+    }
+
+    /// Pushes node on top of the stack.
+    ///
+    /// `value` - Node to be visited later.
+    pub(crate) fn push(&mut self, value: T) {
+        if self.size < N {
+            self.inline[self.size] = value;
+            self.size += 1;
+        } else {
+            self.spilled.push(value);
+        }
+    }
+
+    /// Returns node from the top of the stack, or None if it is empty.
+    pub(crate) fn pop(&mut self) -> Option<T> {
+        if let Some(value) = self.spilled.pop() {
+            return Some(value);
+        }
+        if self.size == 0 {
+            return None;
+        }
+        self.size -= 1;
+        Some(self.inline[self.size])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spill_test() {
+        let mut stack = TraversalStack::<usize, 4>::new(0);
+        for value in 0..10 {
+            stack.push(value);
+        }
+        // Spilled entries come back first, in the same order as if the stack had no inline part
+        let popped: Vec<usize> = std::iter::from_fn(|| stack.pop()).collect();
+        assert_eq!(popped, (0..10).rev().collect::<Vec<_>>());
+    }
+}
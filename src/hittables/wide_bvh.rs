@@ -0,0 +1,870 @@
+use crate::hit::*;
+use crate::hittables::traversal_stack::TraversalStack;
+use crate::hittables::{Aabb, BvhContent, BvhNode, BvhSettings, BvhStats, HittableList};
+use crate::misc::{F32x4, HittablePdf, Interval, Pdf};
+use crate::primitive::Triangle;
+use crate::ray::*;
+use crate::V3;
+
+use std::sync::Arc;
+
+/// Children waiting for traversal up to which the stack isn't allocated, each visited node adds at most 4 of them.
+const STACK_SIZE: usize = 256;
+
+/// Child of a node of the wide Bvh.
+#[derive(Clone, Copy)]
+enum WideChild {
+    /// Unused slot of a node with less than 4 children.
+    Empty,
+    /// Inner node, by its index.
+    Node(u32),
+    /// Range of objects tested one by one.
+    Objects { first: u32, count: u32 },
+    /// Range of packs of triangles tested 4 at once.
+    Triangles { first: u32, count: u32 },
+}
+
+/// Node with up to 4 children, whose bounding boxes are stored by coordinates to be tested at once.
+#[derive(Clone)]
+struct WideNode {
+    /// Lower corners of the children boxes, one lane for each child.
+    min: [F32x4; 3],
+    /// Upper corners of the children boxes, one lane for each child.
+    max: [F32x4; 3],
+    children: [WideChild; 4],
+}
+
+/// Four triangles of a leaf stored by coordinates, unused lanes are masked out by the number of used lanes.
+#[derive(Clone)]
+struct TrianglePack {
+    /// Coordinates of the verticles of each triangle, by verticle and axis.
+    verticles: [[F32x4; 3]; 3],
+    /// Index of the triangle in the first lane, the other triangles follow it.
+    first: u32,
+    /// Number of used lanes.
+    count: u32,
+}
+
+/// Ray moved to the origin and sheared onto the z axis, like in the watertight test of [`Triangle`], for testing packs of triangles.
+struct ShearedRay {
+    /// Axes of the world which become the x, y and z axes, z is the biggest direction of the ray.
+    axes: [usize; 3],
+    /// Origin of the ray along the permuted axes.
+    origin: [F32x4; 3],
+    /// Factors which shear the x and y coordinates by the z coordinate, and scale the z coordinate.
+    shear: [F32x4; 3],
+}
+
+/// Object shaped like a triangle, with its verticles.
+pub(crate) type TriangleObject = (Arc<dyn HittablePdf>, [V3<f32>; 3]);
+
+/// Bvh with 4 children in every node, whose boxes are tested against the ray at once, and with triangles tested 4 at once.
+///
+/// With the `simd` feature the tests use SIMD instructions, otherwise they are done lane by lane.
+#[derive(Clone)]
+pub struct WideBvh {
+    nodes: Vec<WideNode>,
+    /// Objects in the order of leaves, each leaf is a range of them.
+    primitives: Vec<Arc<dyn HittablePdf>>,
+    /// Index of each object in the list the tree was built from, in the order of leaves.
+    order: Vec<usize>,
+    packs: Vec<TrianglePack>,
+}
+
+impl WideBvh {
+    /// Returns wide Bvh built with the surface area heuristic.
+    ///
+    /// `hlist` - Objects in the tree, they are shared, not copied.
+    pub fn new(hlist: &HittableList) -> Self {
+        WideBvh::with_settings(hlist, BvhSettings::default())
+    }
+
+    /// Returns wide Bvh built with given settings.
+    ///
+    /// `hlist` - Objects in the tree, they are shared, not copied.
+    ///
+    /// `settings` - How the tree is built.
+    pub fn with_settings(hlist: &HittableList, settings: BvhSettings) -> Self {
+        WideBvh::collapse(&BvhNode::with_settings(hlist, settings), None)
+    }
+
+    /// Returns wide Bvh of triangles, which are tested 4 at once.
+    ///
+    /// `triangles` - Triangles in the tree, they are shared, not copied.
+    ///
+    /// `settings` - How the tree is built.
+    pub fn from_triangles(triangles: &[Arc<Triangle>], settings: BvhSettings) -> Self {
+        let objects: Vec<TriangleObject> = triangles
+            .iter()
+            .map(|triangle| {
+                (
+                    triangle.clone() as Arc<dyn HittablePdf>,
+                    triangle.verticles(),
+                )
+            })
+            .collect();
+        WideBvh::from_verticles(&objects, settings)
+    }
+
+    /// Returns wide Bvh of objects shaped like triangles, which are tested 4 at once.
+    ///
+    /// `objects` - Objects in the tree with verticles of their triangles, they are shared, not copied.
+    ///
+    /// `settings` - How the tree is built.
+    pub(crate) fn from_verticles(objects: &[TriangleObject], settings: BvhSettings) -> Self {
+        let mut hlist = HittableList::new();
+        let mut verticles = Vec::new();
+        for (object, object_verticles) in objects {
+            hlist.add_shared(object.clone());
+            verticles.push(*object_verticles);
+        }
+
+        WideBvh::collapse(&BvhNode::with_settings(&hlist, settings), Some(&verticles))
+    }
+
+    /// Returns wide copy of the binary tree, by pulling grandchildren with the biggest surface area into each node.
+    ///
+    /// `verticles` - Verticles of all objects by their indices in the list the tree was built from, if they are triangles.
+    fn collapse(tree: &BvhNode, verticles: Option<&[[V3<f32>; 3]]>) -> Self {
+        let mut bvh = WideBvh {
+            nodes: Vec::new(),
+            primitives: Vec::new(),
+            order: Vec::new(),
+            packs: Vec::new(),
+        };
+
+        if let BvhContent::Leaf(objects, _) = tree.content() {
+            if objects.is_empty() {
+                return bvh;
+            }
+        }
+        bvh.add_node(&[tree], verticles);
+        bvh
+    }
+
+    /// Pushes node with given children and their subtrees, returns index of the node.
+    fn add_node(&mut self, children: &[&BvhNode], verticles: Option<&[[V3<f32>; 3]]>) -> u32 {
+        let mut children = children.to_vec();
+        while children.len() < 4 {
+            let widest = children
+                .iter()
+                .enumerate()
+                .filter(|(_, child)| matches!(child.content(), BvhContent::Inner(..)))
+                .max_by(|(_, a), (_, b)| {
+                    a.bounding_box()
+                        .surface_area()
+                        .partial_cmp(&b.bounding_box().surface_area())
+                        .expect("Tried to compare a NaN")
+                })
+                .map(|(index, _)| index);
+
+            match widest {
+                Some(index) => {
+                    if let BvhContent::Inner(left, right) = children[index].content() {
+                        children[index] = left;
+                        children.push(right);
+                    }
+                }
+                None => break,
+            }
+        }
+
+        let index = self.nodes.len();
+        let mut min = [[f32::INFINITY; 4]; 3];
+        let mut max = [[f32::NEG_INFINITY; 4]; 3];
+        for (lane, child) in children.iter().enumerate() {
+            let bounds = child.bounding_box();
+            for (axis, interval) in [bounds.x, bounds.y, bounds.z].iter().enumerate() {
+                min[axis][lane] = interval.min;
+                max[axis][lane] = interval.max;
+            }
+        }
+        self.nodes.push(WideNode {
+            min: min.map(F32x4::new),
+            max: max.map(F32x4::new),
+            children: [WideChild::Empty; 4],
+        });
+
+        for (lane, child) in children.into_iter().enumerate() {
+            let wide_child = match child.content() {
+                BvhContent::Inner(..) => WideChild::Node(self.add_node(&[child], verticles)),
+                BvhContent::Leaf(objects, indices) => self.add_leaf(objects, indices, verticles),
+            };
+            self.nodes[index].children[lane] = wide_child;
+        }
+
+        index as u32
+    }
+
+    /// Pushes objects of a leaf, packed by 4 if they are triangles.
+    fn add_leaf(
+        &mut self,
+        objects: &[Arc<dyn HittablePdf>],
+        indices: &[usize],
+        verticles: Option<&[[V3<f32>; 3]]>,
+    ) -> WideChild {
+        let first = self.primitives.len() as u32;
+        self.primitives.extend(objects.iter().cloned());
+        self.order.extend(indices);
+
+        let triangles: Option<Vec<[V3<f32>; 3]>> =
+            verticles.map(|verticles| indices.iter().map(|&index| verticles[index]).collect());
+
+        match triangles {
+            Some(triangles) => {
+                let first_pack = self.packs.len() as u32;
+                for (chunk_index, chunk) in triangles.chunks(4).enumerate() {
+                    self.packs
+                        .push(TrianglePack::new(chunk, first + 4 * chunk_index as u32));
+                }
+                WideChild::Triangles {
+                    first: first_pack,
+                    count: self.packs.len() as u32 - first_pack,
+                }
+            }
+            None => WideChild::Objects {
+                first,
+                count: objects.len() as u32,
+            },
+        }
+    }
+
+    /// Replaces triangles of the tree and updates bounding boxes of nodes to them, keeping the structure of the tree.
+    ///
+    /// `triangles` - New triangles in the order of leaves, like objects returned by [`objects`](WideBvh::objects), as many as there are in the tree.
+    pub fn refit_triangles(&mut self, triangles: &[Arc<Triangle>]) {
+        let objects: Vec<TriangleObject> = triangles
+            .iter()
+            .map(|triangle| {
+                (
+                    triangle.clone() as Arc<dyn HittablePdf>,
+                    triangle.verticles(),
+                )
+            })
+            .collect();
+        self.refit_verticles(&objects);
+    }
+
+    /// Replaces objects shaped like triangles and updates bounding boxes of nodes to them, keeping the structure of the tree.
+    ///
+    /// `objects` - New objects with verticles of their triangles, in the order of leaves, as many as there are in the tree.
+    pub(crate) fn refit_verticles(&mut self, objects: &[TriangleObject]) {
+        assert_eq!(
+            objects.len(),
+            self.primitives.len(),
+            "Tree was refitted with other number of triangles than it has."
+        );
+
+        for (primitive, (object, _)) in self.primitives.iter_mut().zip(objects) {
+            *primitive = object.clone();
+        }
+        for pack in &mut self.packs {
+            let first = pack.first as usize;
+            let verticles: Vec<[V3<f32>; 3]> = objects[first..first + pack.count as usize]
+                .iter()
+                .map(|(_, verticles)| *verticles)
+                .collect();
+            *pack = TrianglePack::new(&verticles, pack.first);
+        }
+
+        // Children are stored after their parent, so they are updated first going backwards
+        let mut bounds = vec![Aabb::default(); self.nodes.len()];
+        for index in (0..self.nodes.len()).rev() {
+            let mut min = [[f32::INFINITY; 4]; 3];
+            let mut max = [[f32::NEG_INFINITY; 4]; 3];
+            for (lane, child) in self.nodes[index].children.iter().enumerate() {
+                let child_bounds = match *child {
+                    WideChild::Empty => continue,
+                    WideChild::Node(child) => bounds[child as usize].clone(),
+                    WideChild::Objects { first, count } => self.objects_bounds(first, count),
+                    WideChild::Triangles { first, count } => {
+                        let (first, last) = (
+                            &self.packs[first as usize],
+                            &self.packs[(first + count - 1) as usize],
+                        );
+                        self.objects_bounds(first.first, last.first + last.count - first.first)
+                    }
+                };
+                for (axis, interval) in [child_bounds.x, child_bounds.y, child_bounds.z]
+                    .iter()
+                    .enumerate()
+                {
+                    min[axis][lane] = interval.min;
+                    max[axis][lane] = interval.max;
+                }
+                bounds[index] = bounds[index].surrounding_box(child_bounds);
+            }
+
+            let node = &mut self.nodes[index];
+            node.min = min.map(F32x4::new);
+            node.max = max.map(F32x4::new);
+        }
+    }
+
+    /// Returns box around the range of objects.
+    fn objects_bounds(&self, first: u32, count: u32) -> Aabb {
+        self.primitives[first as usize..(first + count) as usize]
+            .iter()
+            .fold(Aabb::default(), |acc, object| {
+                acc.surrounding_box(object.bounding_box())
+            })
+    }
+
+    /// Returns list of all objects in the tree, in the order of leaves.
+    pub fn objects(&self) -> HittableList {
+        HittableList {
+            list: self.primitives.clone(),
+        }
+    }
+
+    /// Returns index of each object in the list the tree was built from, in the order of leaves.
+    pub(crate) fn order(&self) -> &[usize] {
+        &self.order
+    }
+
+    /// Returns statistics of the tree, each leaf counts as a node.
+    ///
+    /// `traversal_cost` - Cost of visiting a node used in the surface area heuristic, relative to the cost of intersecting one object.
+    pub fn stats(&self, traversal_cost: f32) -> BvhStats {
+        let mut stats = BvhStats::default();
+        if !self.nodes.is_empty() {
+            let root_area = self.bounding_box().surface_area().max(f32::EPSILON);
+            let cost = self.collect_stats(0, root_area, &mut stats, 1, traversal_cost);
+            stats.sah_cost = cost / root_area;
+        }
+        stats
+    }
+
+    /// Adds the node to statistics, returns its cost multiplied by its surface area.
+    fn collect_stats(
+        &self,
+        index: u32,
+        area: f32,
+        stats: &mut BvhStats,
+        depth: usize,
+        traversal_cost: f32,
+    ) -> f32 {
+        let node = &self.nodes[index as usize];
+        stats.nodes += 1;
+        stats.depth = stats.depth.max(depth);
+        let mut cost = area * traversal_cost;
+
+        let (min, max) = (node.min.map(F32x4::to_array), node.max.map(F32x4::to_array));
+        for (lane, child) in node.children.iter().enumerate() {
+            let child_area = Aabb::new(
+                V3::new(min[0][lane], min[1][lane], min[2][lane]),
+                V3::new(max[0][lane], max[1][lane], max[2][lane]),
+            )
+            .surface_area();
+
+            let count = match *child {
+                WideChild::Empty => continue,
+                WideChild::Node(child) => {
+                    cost += self.collect_stats(child, child_area, stats, depth + 1, traversal_cost);
+                    continue;
+                }
+                WideChild::Objects { count, .. } => count as usize,
+                WideChild::Triangles { first, count } => self.packs
+                    [first as usize..(first + count) as usize]
+                    .iter()
+                    .map(|pack| pack.count as usize)
+                    .sum(),
+            };
+
+            stats.nodes += 1;
+            stats.leaves += 1;
+            stats.objects += count;
+            stats.max_leaf_size = stats.max_leaf_size.max(count);
+            stats.depth = stats.depth.max(depth + 1);
+            cost += child_area * count as f32;
+        }
+
+        cost
+    }
+
+    /// Returns the closest hit with index of the hit object in the order of leaves.
+    pub(crate) fn closest_hit(&self, ray: &Ray) -> Option<(usize, Hit)> {
+        // Interval of the ray shrinks to the closest hit found, so farther nodes are skipped
+        let mut setting = *ray.setting;
+        let mut closest = None;
+
+        self.traverse(ray, &mut setting, |index, setting| {
+            if let Some(hit) = self.primitives[index].hit(&Ray {
+                setting: &*setting,
+                ..*ray
+            }) {
+                if hit.t <= setting.ray_time.max {
+                    setting.ray_time.max = hit.t;
+                    closest = Some((index, hit));
+                }
+            }
+            false
+        });
+
+        closest
+    }
+
+    /// Returns mask of children whose boxes are hit inside of the interval, and distances to them.
+    fn hit_children(
+        node: &WideNode,
+        origin: &[F32x4; 3],
+        inverse_direction: &[F32x4; 3],
+        interval: (f32, f32),
+    ) -> (u32, [f32; 4]) {
+        let mut near = F32x4::splat(interval.0);
+        let mut far = F32x4::splat(interval.1);
+
+        for axis in 0..3 {
+            let t0 = (node.min[axis] - origin[axis]) * inverse_direction[axis];
+            let t1 = (node.max[axis] - origin[axis]) * inverse_direction[axis];
+            // NaN from a ray lying in the plane of a side is ignored by min and max
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+
+        (near.le(far), near.to_array())
+    }
+
+    /// Returns mask of triangles of the pack which may be hit inside of the interval, by the watertight test done 4 at once.
+    ///
+    /// Operations are the same and in the same order as in the test of [`Triangle`], so every hit it finds is in the mask. Lanes which it decides in double precision are always in the mask.
+    fn hit_pack(pack: &TrianglePack, ray: &ShearedRay, interval: (f32, f32)) -> u32 {
+        let zero = F32x4::splat(0.0);
+        let [kx, ky, kz] = ray.axes;
+        let [shear_x, shear_y, shear_z] = ray.shear;
+
+        let [p0, p1, p2] = pack.verticles.map(|verticle| {
+            let z = verticle[kz] - ray.origin[2];
+            [
+                verticle[kx] - ray.origin[0] + shear_x * z,
+                verticle[ky] - ray.origin[1] + shear_y * z,
+                z,
+            ]
+        });
+
+        let e0 = p1[0] * p2[1] - p1[1] * p2[0];
+        let e1 = p2[0] * p0[1] - p2[1] * p0[0];
+        let e2 = p0[0] * p1[1] - p0[1] * p1[0];
+
+        let is_zero = |e: F32x4| e.le(zero) & zero.le(e);
+        let any_zero = is_zero(e0) | is_zero(e1) | is_zero(e2);
+        let same_sign =
+            (zero.le(e0) & zero.le(e1) & zero.le(e2)) | (e0.le(zero) & e1.le(zero) & e2.le(zero));
+
+        let det = e0 + e1 + e2;
+        let t_scaled = e0 * (p0[2] * shear_z) + e1 * (p1[2] * shear_z) + e2 * (p2[2] * shear_z);
+        let t = t_scaled * (F32x4::splat(1.0) / det);
+        let in_interval = F32x4::splat(interval.0).le(t) & t.le(F32x4::splat(interval.1));
+
+        let used = (1 << pack.count) - 1;
+        (any_zero | (same_sign & !is_zero(det) & in_interval)) & used
+    }
+
+    /// Visits objects in leaves hit by the Ray front to back, until `object` returns true, then returns true.
+    ///
+    /// `setting` - Setting of the Ray, whose time interval `object` can shrink to skip farther nodes.
+    ///
+    /// `object` - Called with index of each object of visited leaves in the order of leaves, in packed leaves only with candidates of the packed test.
+    fn traverse(
+        &self,
+        ray: &Ray,
+        setting: &mut RaySetting,
+        mut object: impl FnMut(usize, &mut RaySetting) -> bool,
+    ) -> bool {
+        if self.nodes.is_empty() {
+            return false;
+        }
+
+        let origin = [
+            F32x4::splat(ray.origin.x),
+            F32x4::splat(ray.origin.y),
+            F32x4::splat(ray.origin.z),
+        ];
+        let sheared = ShearedRay::new(ray);
+        let inverse_direction = [
+            F32x4::splat(1.0 / ray.end.x),
+            F32x4::splat(1.0 / ray.end.y),
+            F32x4::splat(1.0 / ray.end.z),
+        ];
+
+        let mut stack = TraversalStack::<WideChild, STACK_SIZE>::new(WideChild::Empty);
+        let mut current = WideChild::Node(0);
+
+        loop {
+            match current {
+                WideChild::Empty => {}
+                WideChild::Node(index) => {
+                    let node = &self.nodes[index as usize];
+                    let interval = (setting.ray_time.min, setting.ray_time.max);
+                    let (mask, near) =
+                        WideBvh::hit_children(node, &origin, &inverse_direction, interval);
+
+                    // Children are pushed from the farthest, so the nearest one is visited first
+                    let mut hits = [(0.0, WideChild::Empty); 4];
+                    let mut hits_count = 0;
+                    for (lane, child) in node.children.iter().enumerate() {
+                        if mask & 1 << lane != 0 && !matches!(child, WideChild::Empty) {
+                            let mut position = hits_count;
+                            while position > 0 && hits[position - 1].0 < near[lane] {
+                                hits[position] = hits[position - 1];
+                                position -= 1;
+                            }
+                            hits[position] = (near[lane], *child);
+                            hits_count += 1;
+                        }
+                    }
+                    for &(_, child) in &hits[..hits_count] {
+                        stack.push(child);
+                    }
+                }
+                WideChild::Objects { first, count } => {
+                    let first = first as usize;
+                    for index in first..first + count as usize {
+                        if object(index, setting) {
+                            return true;
+                        }
+                    }
+                }
+                WideChild::Triangles { first, count } => {
+                    for pack in &self.packs[first as usize..(first + count) as usize] {
+                        let interval = (setting.ray_time.min, setting.ray_time.max);
+                        let mask = WideBvh::hit_pack(pack, &sheared, interval);
+
+                        // Only candidates are tested fully, to get the shading and the cutout mask
+                        for lane in (0..4).filter(|lane| mask & 1 << lane != 0) {
+                            if object(pack.first as usize + lane, setting) {
+                                return true;
+                            }
+                        }
+                    }
+                }
+            }
+
+            match stack.pop() {
+                Some(next) => current = next,
+                None => return false,
+            }
+        }
+    }
+}
+
+impl TrianglePack {
+    /// Returns pack of up to 4 triangles.
+    ///
+    /// `triangles` - Verticles of the triangles.
+    ///
+    /// `first` - Index of the first triangle among the objects of the tree.
+    fn new(triangles: &[[V3<f32>; 3]], first: u32) -> Self {
+        let mut verticles = [[[0.0; 4]; 3]; 3];
+        for (lane, triangle) in triangles.iter().enumerate() {
+            for (verticle, point) in triangle.iter().enumerate() {
+                for axis in 0..3 {
+                    verticles[verticle][axis][lane] = point[axis as u32];
+                }
+            }
+        }
+
+        TrianglePack {
+            verticles: verticles.map(|verticle| verticle.map(F32x4::new)),
+            first,
+            count: triangles.len() as u32,
+        }
+    }
+}
+
+impl ShearedRay {
+    /// Returns the ray prepared for the watertight test.
+    ///
+    /// `ray` - Ray tested against the triangles.
+    fn new(ray: &Ray) -> Self {
+        let kz = (0..3)
+            .max_by(|&a, &b| {
+                ray.end[a]
+                    .abs()
+                    .partial_cmp(&ray.end[b].abs())
+                    .expect("Tried to compare a NaN")
+            })
+            .unwrap_or(2);
+        let kx = (kz + 1) % 3;
+        let ky = (kx + 1) % 3;
+
+        let direction = [ray.end[kx], ray.end[ky], ray.end[kz]];
+        ShearedRay {
+            axes: [kx as usize, ky as usize, kz as usize],
+            origin: [ray.origin[kx], ray.origin[ky], ray.origin[kz]].map(F32x4::splat),
+            shear: [
+                -direction[0] / direction[2],
+                -direction[1] / direction[2],
+                1.0 / direction[2],
+            ]
+            .map(F32x4::splat),
+        }
+    }
+}
+
+impl Hittable for WideBvh {
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        self.closest_hit(ray).map(|(_, hit)| hit)
+    }
+
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        let mut setting = RaySetting {
+            ray_time: Interval::new(ray.setting.ray_time.min, t_max),
+            ..*ray.setting
+        };
+
+        self.traverse(ray, &mut setting, |index, _| {
+            self.primitives[index].occluded(ray, t_max)
+        })
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> V3<f32> {
+        let mut setting = RaySetting {
+            ray_time: Interval::new(ray.setting.ray_time.min, t_max),
+            ..*ray.setting
+        };
+
+        // Media along the whole segment let light through, it stops at the first surface
+        let mut transmittance = V3::new(1.0, 1.0, 1.0);
+        self.traverse(ray, &mut setting, |index, _| {
+            transmittance =
+                transmittance.hadamard(self.primitives[index].transmittance(ray, t_max));
+            transmittance == V3::default()
+        });
+        transmittance
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let (min, max) = match self.nodes.first() {
+            Some(root) => (root.min.map(F32x4::to_array), root.max.map(F32x4::to_array)),
+            None => return Aabb::default(),
+        };
+
+        // Empty lanes have infinite lower corners, so they don't change the minimum
+        let fold = |values: [f32; 4], operation: fn(f32, f32) -> f32, start: f32| {
+            values.iter().copied().fold(start, operation)
+        };
+        Aabb::new(
+            V3::new(
+                fold(min[0], f32::min, f32::INFINITY),
+                fold(min[1], f32::min, f32::INFINITY),
+                fold(min[2], f32::min, f32::INFINITY),
+            ),
+            V3::new(
+                fold(max[0], f32::max, f32::NEG_INFINITY),
+                fold(max[1], f32::max, f32::NEG_INFINITY),
+                fold(max[2], f32::max, f32::NEG_INFINITY),
+            ),
+        )
+    }
+}
+
+impl Pdf for WideBvh {
+    fn value(&self, origin: V3<f32>, direction: V3<f32>) -> f32 {
+        let weight = 1.0 / self.primitives.len() as f32;
+        self.primitives
+            .iter()
+            .map(|primitive| primitive.value(origin, direction) * weight)
+            .sum()
+    }
+
+    fn generate(&self, origin: V3<f32>) -> V3<f32> {
+        use rand::seq::SliceRandom;
+
+        self.primitives
+            .choose(&mut rand::thread_rng())
+            .expect("WideBvh is empty, thus cannot generate random direction toward it.")
+            .generate(origin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittables::{FlatBvh, SplitMethod};
+    use crate::misc::Interval;
+    use crate::primitive::Sphere;
+    use crate::RaySetting;
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn same_hits_as_tree_test() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(11);
+        let mut point = || {
+            V3::new(
+                rng.gen_range(-10.0..10.0),
+                rng.gen_range(-10.0..10.0),
+                rng.gen_range(-10.0..10.0),
+            )
+        };
+
+        let triangles: Vec<Arc<Triangle>> = (0..300)
+            .map(|_| {
+                let a = point();
+                let b = a + point() * 0.1;
+                let c = a + point() * 0.1;
+                Arc::new(Triangle::new(V3::new(a, b, c), None, 0))
+            })
+            .collect();
+
+        let mut hlist = HittableList::new();
+        for triangle in &triangles {
+            hlist.add_shared(triangle.clone());
+        }
+        let tree = BvhNode::new(&hlist);
+        let wide = WideBvh::from_triangles(&triangles, BvhSettings::default());
+        assert_eq!(wide.stats(1.0).objects, 300);
+
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+        for _ in 0..500 {
+            let ray = Ray::for_test(point(), point(), &setting);
+
+            let expected = tree.hit(&ray).map(|hit| hit.t);
+            let t = wide.hit(&ray).map(|hit| hit.t);
+            assert_eq!(expected, t);
+
+            let occluded = expected.is_some_and(|t| t <= 5.0);
+            assert_eq!(wide.occluded(&ray, 5.0), occluded);
+            assert_eq!(tree.occluded(&ray, 5.0), occluded);
+        }
+    }
+
+    #[test]
+    fn shared_edge_test() {
+        // Square split on its diagonal, both halves end up in the same pack
+        let corners = [
+            V3::new(-300.0, -300.0, 0.0),
+            V3::new(300.0, -300.0, 0.0),
+            V3::new(300.0, 300.0, 0.0),
+            V3::new(-300.0, 300.0, 0.0),
+        ];
+        let triangles = vec![
+            Arc::new(Triangle::new(
+                V3::new(corners[0], corners[1], corners[2]),
+                None,
+                0,
+            )),
+            Arc::new(Triangle::new(
+                V3::new(corners[0], corners[2], corners[3]),
+                None,
+                0,
+            )),
+        ];
+        let mut hlist = HittableList::new();
+        for triangle in &triangles {
+            hlist.add_shared(triangle.clone());
+        }
+        let tree = BvhNode::new(&hlist);
+        let wide = WideBvh::from_triangles(&triangles, BvhSettings::default());
+        assert_eq!(wide.packs.len(), 1);
+
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+        for i in -64..64 {
+            // Points on the diagonal are exactly representable
+            let target = V3::new(i as f32 * 4.5, i as f32 * 4.5, 0.0);
+            let origin = V3::new(
+                rng.gen_range(-500.0..500.0),
+                rng.gen_range(-500.0..500.0),
+                rng.gen_range(1.0..500.0),
+            );
+            let ray = Ray::for_test(origin, target - origin, &setting);
+
+            let t = wide.hit(&ray).map(|hit| hit.t);
+            assert!(t.is_some(), "Ray through the shared edge at {} missed", i);
+            assert_eq!(t, tree.hit(&ray).map(|hit| hit.t));
+        }
+    }
+
+    #[test]
+    fn refit_test() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(5);
+        let mut point = || {
+            V3::new(
+                rng.gen_range(-10.0..10.0),
+                rng.gen_range(-10.0..10.0),
+                rng.gen_range(-10.0..10.0),
+            )
+        };
+
+        let triangles: Vec<Arc<Triangle>> = (0..300)
+            .map(|_| {
+                let a = point();
+                Arc::new(Triangle::new(
+                    V3::new(a, a + point() * 0.1, a + point() * 0.1),
+                    None,
+                    0,
+                ))
+            })
+            .collect();
+        let mut wide = WideBvh::from_triangles(&triangles, BvhSettings::default());
+
+        // Triangles are twisted around the y axis, each by an angle depending on its height
+        let twist = |p: V3<f32>| {
+            p.rot(
+                V3::new(0.0, 1.0, 0.0),
+                (p.y * 0.05).sin(),
+                (p.y * 0.05).cos(),
+            )
+        };
+        let leaves: Vec<Arc<Triangle>> = wide
+            .order()
+            .iter()
+            .map(|&index| {
+                let [a, b, c] = triangles[index].verticles();
+                Arc::new(triangles[index].with_verticles(V3::new(twist(a), twist(b), twist(c))))
+            })
+            .collect();
+        wide.refit_triangles(&leaves);
+        let rebuilt = WideBvh::from_triangles(&leaves, BvhSettings::default());
+
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+        for _ in 0..500 {
+            let ray = Ray::for_test(point(), point(), &setting);
+
+            assert_eq!(
+                wide.hit(&ray).map(|hit| hit.t),
+                rebuilt.hit(&ray).map(|hit| hit.t)
+            );
+        }
+    }
+
+    #[test]
+    fn deep_tree_test() {
+        // Each sphere is less than half the size of the previous one, so every split cuts off just the biggest spheres
+        let mut list = HittableList::new();
+        for i in 0..100 {
+            let size = 0.45f32.powi(i);
+            list.add(Sphere::new(V3::new(size, 0.0, 0.0), size * 0.25, 0));
+        }
+        let settings = BvhSettings {
+            split: SplitMethod::Sah { bins: 2 },
+            ..BvhSettings::default()
+        };
+        let wide = WideBvh::with_settings(&list, settings);
+        let flat = FlatBvh::with_settings(&list, settings);
+
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.0, f32::INFINITY),
+        };
+        let ray = Ray::for_test(V3::new(-1.0, 0.0, 0.0), V3::new(1.0, 0.0, 0.0), &setting);
+        assert_eq!(
+            wide.hit(&ray).map(|hit| hit.t),
+            flat.hit(&ray).map(|hit| hit.t)
+        );
+    }
+}
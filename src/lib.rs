@@ -7,12 +7,15 @@ pub use hit::*;
 pub use ray::*;
 pub use render::*;
 pub use vec3::*;
+pub use wavefront::*;
 
 mod camera;
+mod gltf_loader;
 mod hit;
 mod ray;
 mod render;
 mod vec3;
+mod wavefront;
 
 pub mod hittables;
 pub mod material;
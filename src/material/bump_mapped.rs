@@ -0,0 +1,279 @@
+use crate::hit::Hit;
+use crate::material::*;
+use crate::misc::Onb;
+use crate::ray::Ray;
+use crate::texture::Texture;
+use crate::V3;
+
+/// Way in which the shading normal of the surface is changed.
+#[derive(Clone)]
+pub enum NormalPerturbation {
+    /// Tangent-space normal map, where red, green and blue channels of the texture are components of the normal vector along `dpdu`, `dpdv` and surface normal.
+    NormalMap(Box<dyn Texture + Sync + Send>),
+    /// Height map, the surface is shifted along its normal by the mean of texture channels multiplied by `scale`.
+    BumpMap {
+        /// Texture representing height of the surface.
+        height: Box<dyn Texture + Sync + Send>,
+        /// How much the surface is shifted.
+        scale: f32,
+    },
+}
+
+/// Material with shading normal changed by normal or bump map, the geometry of the object stays the same.
+pub struct BumpMapped {
+    /// Material that is shaded with the changed normal.
+    pub material: Box<dyn MaterialTrait>,
+    /// Normal or bump map applied to the material.
+    pub perturbation: NormalPerturbation,
+}
+
+/// Offset in texture space used to approximate derivatives of the height map.
+const HEIGHT_DELTA: f32 = 0.0005;
+
+impl BumpMapped {
+    /// Returns new bump mapped material.
+    ///
+    /// `material` - Material that is shaded with the changed normal.
+    ///
+    /// `perturbation` - Normal or bump map applied to the material.
+    pub fn new<T: MaterialTrait + 'static>(material: T, perturbation: NormalPerturbation) -> Self {
+        BumpMapped {
+            material: Box::new(material),
+            perturbation,
+        }
+    }
+
+    /// Returns hit event with the shading normal changed by normal or bump map.
+    fn perturb(&self, hit: &Hit) -> Hit {
+        // Maps describe the outer side of the surface, back faces flip the result at the end, which keeps the handedness of the frame
+        let outward = if hit.front_face {
+            hit.normal
+        } else {
+            -hit.normal
+        };
+        let (tangent, bitangent) = tangent_frame(outward, hit.dpdu, hit.dpdv);
+
+        let normal = match &self.perturbation {
+            NormalPerturbation::NormalMap(map) => {
+                let local = map.sample(hit) * 2.0 - V3::new(1.0, 1.0, 1.0);
+                tangent * local.x + bitangent * local.y + outward * local.z
+            }
+            NormalPerturbation::BumpMap { height, scale } => {
+                let height_at = |u: f32, v: f32, point: V3<f32>| {
+                    height.value(u, v, point).into_iter().sum::<f32>() / 3.0
+                };
+
+                // Derivatives keep their lengths, so the height is measured in the same units as the surface
+                let (dpdu, dpdv) = if hit.dpdu.near_zero() || hit.dpdv.near_zero() {
+                    (tangent, bitangent)
+                } else {
+                    (
+                        hit.dpdu - outward * outward.dot(hit.dpdu),
+                        hit.dpdv - outward * outward.dot(hit.dpdv),
+                    )
+                };
+
+                let displacement = height_at(hit.u, hit.v, hit.point);
+                let u_displacement =
+                    height_at(hit.u + HEIGHT_DELTA, hit.v, hit.point + dpdu * HEIGHT_DELTA);
+                let v_displacement =
+                    height_at(hit.u, hit.v + HEIGHT_DELTA, hit.point + dpdv * HEIGHT_DELTA);
+
+                let dpdu =
+                    dpdu + outward * ((u_displacement - displacement) / HEIGHT_DELTA * scale);
+                let dpdv =
+                    dpdv + outward * ((v_displacement - displacement) / HEIGHT_DELTA * scale);
+
+                dpdu.cross(dpdv)
+            }
+        };
+
+        // Shading normal should stay on the same side as the normal the ray hit, so the front face stays the same.
+        let normal = if normal.near_zero() || normal.into_iter().any(f32::is_nan) {
+            hit.normal
+        } else if normal.dot(hit.normal) < 0.0 {
+            -normal.norm()
+        } else {
+            normal.norm()
+        };
+
+        Hit { normal, ..*hit }
+    }
+}
+
+/// Returns unit tangent and bitangent, orthogonal to the normal and oriented along `dpdu` and `dpdv`.
+///
+/// `normal` - Normal of the outer side of the surface.
+///
+/// `dpdu`, `dpdv` - Partial derivatives of the hit point with respect to texture coordinates.
+fn tangent_frame(normal: V3<f32>, dpdu: V3<f32>, dpdv: V3<f32>) -> (V3<f32>, V3<f32>) {
+    let tangent = dpdu - normal * normal.dot(dpdu);
+
+    if tangent.near_zero() {
+        // Primitive doesn't provide tangents, any basis will do
+        let uvw = Onb::build_from_w(&normal);
+        return (uvw.u(), uvw.v());
+    }
+
+    let tangent = tangent.norm();
+    let bitangent = dpdv - normal * normal.dot(dpdv) - tangent * tangent.dot(dpdv);
+
+    let bitangent = if bitangent.near_zero() {
+        normal.cross(tangent)
+    } else {
+        bitangent.norm()
+    };
+
+    (tangent, bitangent)
+}
+
+impl MaterialTrait for BumpMapped {
+    fn scatter<'a>(&self, ray_in: &'a Ray, hit: &Hit) -> Option<ScatterRecord<'a>> {
+        self.material.scatter(ray_in, &self.perturb(hit))
+    }
+
+    fn scattering_pdf(&self, ray_in: &Ray, hit: &Hit, ray_scattered: &Ray) -> f32 {
+        self.material
+            .scattering_pdf(ray_in, &self.perturb(hit), ray_scattered)
+    }
+
+    fn color_emitted(&self, ray_in: &Ray, hit: &Hit) -> V3<f32> {
+        self.material.color_emitted(ray_in, &self.perturb(hit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::misc::Interval;
+    use crate::primitive::{Quad, Triangle};
+    use crate::texture::ConstantTexture;
+    use crate::{Hittable, RaySetting};
+
+    /// Height growing along `u`.
+    #[derive(Clone)]
+    struct Ramp;
+
+    impl Texture for Ramp {
+        fn value(&self, u: f32, _v: f32, _p: V3<f32>) -> V3<f32> {
+            V3::new(u, u, u)
+        }
+    }
+
+    fn bump_map<T: Texture + 'static>(height: T, scale: f32) -> BumpMapped {
+        let white = ConstantTexture {
+            color: V3::new(1.0, 1.0, 1.0),
+        };
+        BumpMapped::new(
+            Lambertian::new(Box::new(white)),
+            NormalPerturbation::BumpMap {
+                height: Box::new(height),
+                scale,
+            },
+        )
+    }
+
+    fn assert_near(a: V3<f32>, b: V3<f32>) {
+        assert!((a - b).length() < 1e-3, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn flat_height_map_test() {
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+        let material = bump_map(
+            ConstantTexture {
+                color: V3::new(0.5, 0.5, 0.5),
+            },
+            1.0,
+        );
+
+        let quad = Quad::new(
+            V3::new(0.0, 0.0, 0.0),
+            V3::new(4.0, 0.0, 0.0),
+            V3::new(0.0, 0.5, 0.0),
+            0,
+        );
+        // Interpolated normals are tilted from the plane of the triangle
+        let tilted = V3::new(0.3, 0.0, 1.0).norm();
+        let triangle = Triangle::new(
+            V3::new(
+                V3::new(0.0, 0.0, 0.0),
+                V3::new(1.0, 0.0, 0.0),
+                V3::new(0.0, 1.0, 0.0),
+            ),
+            Some(V3::new(tilted, tilted, tilted)),
+            0,
+        )
+        .with_uvs(V3::new((0.0, 0.0), (3.0, 0.0), (0.0, 0.2)));
+
+        for z in [1.0, -1.0] {
+            let ray = Ray::for_test(V3::new(0.25, 0.25, z), V3::new(0.0, 0.0, -z), &setting);
+            for hit in [quad.hit(&ray).unwrap(), triangle.hit(&ray).unwrap()] {
+                assert_near(material.perturb(&hit).normal, hit.normal);
+            }
+        }
+    }
+
+    #[test]
+    fn bump_scale_test() {
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+        let material = bump_map(Ramp, 1.0);
+
+        // Height grows by 1 over the side of length 2, so the surface rises by 1/2 per unit of x
+        let quad = Quad::new(
+            V3::new(0.0, 0.0, 0.0),
+            V3::new(2.0, 0.0, 0.0),
+            V3::new(0.0, 1.0, 0.0),
+            0,
+        );
+        let ray = Ray::for_test(V3::new(0.5, 0.5, 1.0), V3::new(0.0, 0.0, -1.0), &setting);
+        let hit = quad.hit(&ray).unwrap();
+        assert_near(
+            material.perturb(&hit).normal,
+            V3::new(-0.5, 0.0, 1.0).norm(),
+        );
+    }
+
+    #[test]
+    fn back_face_test() {
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+        // Normal tilted along the tangent, encoded into colors
+        let local = V3::new(0.6, 0.0, 0.8);
+        let material = BumpMapped::new(
+            Lambertian::new(Box::new(ConstantTexture {
+                color: V3::new(0.5, 0.5, 0.5),
+            })),
+            NormalPerturbation::NormalMap(Box::new(ConstantTexture {
+                color: (local + V3::new(1.0, 1.0, 1.0)) * 0.5,
+            })),
+        );
+        let quad = Quad::new(
+            V3::new(0.0, 0.0, 0.0),
+            V3::new(1.0, 0.0, 0.0),
+            V3::new(0.0, 1.0, 0.0),
+            0,
+        );
+
+        let ray = Ray::for_test(V3::new(0.5, 0.5, 1.0), V3::new(0.0, 0.0, -1.0), &setting);
+        let front = quad.hit(&ray).unwrap();
+        assert_near(material.perturb(&front).normal, local);
+
+        // Back face sees the same surface from the other side, the normal is flipped as a whole
+        let ray = Ray::for_test(V3::new(0.5, 0.5, -1.0), V3::new(0.0, 0.0, 1.0), &setting);
+        let back = quad.hit(&ray).unwrap();
+        assert!(!back.front_face);
+        assert_near(material.perturb(&back).normal, -local);
+    }
+}
@@ -2,47 +2,25 @@ use crate::hit::Hit;
 use crate::material::*;
 use crate::misc::ZeroPdf;
 use crate::ray::Ray;
+use crate::transform::MediumInterface;
 use crate::V3;
 use rand::Rng;
 
 /// Struct representing dielectrics, in form of glass.
 #[derive(Clone)]
 pub struct Dielectric {
-    /// How much Rays are refracted, proportional to the speed of light in this object.
+    /// How much Rays are refracted, proportional to the speed of light in this object, the medium of a medium boundary overrides it.
     pub refractive_index: f32,
 }
 
 impl MaterialTrait for Dielectric {
     fn scatter<'a>(&self, ray: &'a Ray, hit: &Hit) -> Option<ScatterRecord<'a>> {
-        let etai_over_etat = if hit.front_face {
-            1.0 / self.refractive_index
-        } else {
-            self.refractive_index
-        };
-
-        let unit_direction = ray.end.norm();
-
-        let cos_theta = (-unit_direction).dot(hit.normal).min(1.0);
-
-        let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
-
-        let direction: V3<f32> = if (etai_over_etat * sin_theta > 1.0)
-            || (rand::thread_rng().gen_range(0.0..1.0) < schlick(cos_theta, etai_over_etat))
-        {
-            reflect(unit_direction, hit.normal)
-        } else {
-            refract(unit_direction, hit.normal, etai_over_etat)
-        };
-
-        Some(ScatterRecord {
-            specular_ray: Some(Ray {
-                origin: hit.point,
-                end: direction,
-                ..*ray
-            }),
-            attenuation: V3::new(1.0, 1.0, 1.0),
-            pdf: Box::new(ZeroPdf),
-        })
+        Some(scatter_through(
+            ray,
+            hit,
+            hit.interface,
+            self.refractive_index,
+        ))
     }
 
     fn scattering_pdf(&self, _ray_in: &Ray, _hit: &Hit, _ray_scattered: &Ray) -> f32 {
@@ -54,6 +32,69 @@ impl MaterialTrait for Dielectric {
     }
 }
 
+/// Returns record of the Ray reflected from or refracted through the surface of a dielectric object.
+///
+/// `interface` - Medium inside of the object, which the Ray enters or leaves when refracted, if the object is a medium boundary.
+///
+/// `refractive_index` - Refractive index inside of the object, used if there is no medium inside.
+pub(crate) fn scatter_through<'a>(
+    ray: &'a Ray,
+    hit: &Hit,
+    interface: Option<MediumInterface>,
+    refractive_index: f32,
+) -> ScatterRecord<'a> {
+    let inside = interface.map_or(refractive_index, |interface| {
+        interface.interior.refractive_index
+    });
+    // Medium on the other side is the one the Ray is in, without the object itself when leaving it
+    let outside = match interface {
+        Some(interface) if !hit.front_face => ray.media.exited(interface),
+        _ => ray.media,
+    }
+    .current()
+    .refractive_index;
+
+    let etai_over_etat = if hit.front_face {
+        outside / inside
+    } else {
+        inside / outside
+    };
+
+    let unit_direction = ray.end.norm();
+
+    let cos_theta = (-unit_direction).dot(hit.normal).min(1.0);
+
+    let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
+
+    let crossed = match interface {
+        Some(interface) => ray.media.crossed(interface, hit.front_face),
+        None => ray.media,
+    };
+
+    let (direction, media) = if etai_over_etat == 1.0 {
+        // Surface between media with the same refractive index is invisible
+        (unit_direction, crossed)
+    } else if (etai_over_etat * sin_theta > 1.0)
+        || (rand::thread_rng().gen_range(0.0..1.0) < schlick(cos_theta, etai_over_etat))
+    {
+        (reflect(unit_direction, hit.normal), ray.media)
+    } else {
+        (refract(unit_direction, hit.normal, etai_over_etat), crossed)
+    };
+
+    ScatterRecord {
+        specular_ray: Some(Ray {
+            origin: hit.spawn_origin(direction),
+            end: direction,
+            media,
+            ..*ray
+        }),
+        attenuation: V3::new(1.0, 1.0, 1.0),
+        pdf: Box::new(ZeroPdf),
+        invisible: etai_over_etat == 1.0,
+    }
+}
+
 impl Dielectric {
     /// Returns new Dielectric material.
     pub fn new(refractive_index: f32) -> Self {
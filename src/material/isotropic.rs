@@ -17,8 +17,9 @@ impl MaterialTrait for Isotropic {
     fn scatter<'a>(&self, _ray_in: &'a Ray, hit: &Hit) -> Option<ScatterRecord<'a>> {
         Some(ScatterRecord {
             specular_ray: None,
-            attenuation: self.albedo.value(hit.u, hit.v, hit.point),
+            attenuation: self.albedo.sample(hit),
             pdf: Box::new(UniformPdf),
+            invisible: false,
         })
     }
 
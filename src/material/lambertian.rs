@@ -19,8 +19,9 @@ impl MaterialTrait for Lambertian {
     fn scatter<'a>(&self, _ray_in: &'a Ray, hit: &Hit) -> Option<ScatterRecord<'a>> {
         Some(ScatterRecord {
             specular_ray: None,
-            attenuation: self.albedo.value(hit.u, hit.v, hit.point),
+            attenuation: self.albedo.sample(hit),
             pdf: Box::new(CosinePdf::new(&hit.normal)),
+            invisible: false,
         })
     }
 
@@ -28,7 +28,7 @@ impl MaterialTrait for LightSource {
 
     fn color_emitted(&self, _ray_in: &Ray, hit: &Hit) -> V3<f32> {
         if hit.front_face {
-            self.albedo.value(hit.u, hit.v, hit.point)
+            self.albedo.sample(hit)
         } else {
             V3::default()
         }
@@ -2,6 +2,7 @@ use crate::hit::Hit;
 use crate::material::*;
 use crate::misc::ZeroPdf;
 use crate::ray::Ray;
+use crate::texture::Texture;
 use crate::V3;
 
 /// Metalic material.
@@ -11,6 +12,8 @@ pub struct Metalic {
     pub albedo: V3<f32>,
     /// Irregularity of surface.
     pub fuzz: f32,
+    /// Texture multiplying the color, if the color changes over the surface.
+    pub texture: Option<Box<dyn Texture + Sync + Send>>,
 }
 
 impl MaterialTrait for Metalic {
@@ -20,15 +23,19 @@ impl MaterialTrait for Metalic {
         let end = reflected + V3::get_point_on_sphere() * self.fuzz;
 
         let specular_ray = Ray {
-            origin: hit.point,
+            origin: hit.spawn_origin(end),
             end,
             ..*ray_in
         };
 
         Some(ScatterRecord {
             specular_ray: Some(specular_ray),
-            attenuation: self.albedo,
+            attenuation: match &self.texture {
+                Some(texture) => self.albedo.hadamard(texture.sample(hit)),
+                None => self.albedo,
+            },
             pdf: Box::new(ZeroPdf),
+            invisible: false,
         })
     }
 
@@ -44,6 +51,18 @@ impl MaterialTrait for Metalic {
 impl Metalic {
     /// Returns new Dielectric material.
     pub fn new(albedo: V3<f32>, fuzz: f32) -> Self {
-        Metalic { albedo, fuzz }
+        Metalic {
+            albedo,
+            fuzz,
+            texture: None,
+        }
+    }
+
+    /// Returns the material with its color multiplied by the texture.
+    ///
+    /// `texture` - Color of the surface at each point.
+    pub fn with_texture(mut self, texture: Box<dyn Texture + Sync + Send>) -> Self {
+        self.texture = Some(texture);
+        self
     }
 }
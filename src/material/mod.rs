@@ -1,20 +1,29 @@
 //! Ways of scattering/refracting incoming rays.
 
+mod bump_mapped;
 mod dielectric;
 mod isotropic;
 mod lambertian;
 mod light_source;
 mod metalic;
+mod phase_function;
+mod spot_light;
+mod subsurface;
 
+pub use bump_mapped::{BumpMapped, NormalPerturbation};
 pub use dielectric::Dielectric;
 pub use isotropic::Isotropic;
 pub use lambertian::Lambertian;
 pub use light_source::LightSource;
 pub use metalic::Metalic;
+pub use phase_function::*;
+pub use spot_light::SpotLight;
+pub use subsurface::Subsurface;
 
 use crate::hit::Hit;
 use crate::misc::Pdf;
 use crate::ray::Ray;
+use crate::texture::Opacity;
 use crate::V3;
 
 /// Each object that implements Material trait should be able to scatter incoming ray.
@@ -44,6 +53,8 @@ pub struct ScatterRecord<'ray> {
     pub attenuation: V3<f32>,
     /// PDF corresponding to this record.
     pub pdf: Box<dyn Pdf>,
+    /// True if the Ray goes through the surface unchanged, like through a border of media with the same refractive index, which doesn't count as a bounce.
+    pub invisible: bool,
 }
 
 // Various functions needed to compute reflections, refractions etc.
@@ -83,6 +94,8 @@ pub fn schlick(cosine: f32, refractive_index: f32) -> f32 {
 /// Contains materials
 pub struct MaterialContainer {
     materials: Vec<Box<dyn MaterialTrait>>,
+    /// Cutout masks of corresponding materials, None for opaque ones
+    opacities: Vec<Option<Opacity>>,
     none_material: Box<dyn MaterialTrait>,
 }
 
@@ -90,9 +103,32 @@ impl MaterialContainer {
     /// Adds new material to container, returns it's index
     pub fn add<T: MaterialTrait + 'static>(&mut self, mat: T) -> usize {
         self.materials.push(Box::new(mat));
+        self.opacities.push(None);
         self.materials.len() - 1
     }
 
+    /// Adds new material with cutout mask to container, returns it's index. Rays pass through transparent parts of all surfaces using the material.
+    ///
+    /// `mat` - Material to add.
+    ///
+    /// `opacity` - Mask sampled at texture coordinates of the hit.
+    pub fn add_with_opacity<T: MaterialTrait + 'static>(
+        &mut self,
+        mat: T,
+        opacity: Opacity,
+    ) -> usize {
+        let index = self.add(mat);
+        self.opacities[index] = Some(opacity);
+        index
+    }
+
+    /// Returns cutout mask of the material, or None if the material is opaque.
+    ///
+    /// `index` - Index of the material.
+    pub fn opacity(&self, index: usize) -> Option<&Opacity> {
+        self.opacities.get(index)?.as_ref()
+    }
+
     /// Returns material from container, if material doesnt exists under given index, returns default material (magneta)
     pub fn get(&self, index: usize) -> &dyn MaterialTrait {
         match self.materials.get(index) {
@@ -107,6 +143,7 @@ impl Default for MaterialContainer {
     fn default() -> Self {
         MaterialContainer {
             materials: Vec::new(),
+            opacities: Vec::new(),
             none_material: Box::new(Metalic::new(V3::new(0.8, 0.8, 0.9), 1.0)),
         }
     }
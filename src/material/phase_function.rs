@@ -0,0 +1,241 @@
+use crate::hit::Hit;
+use crate::material::*;
+use crate::misc::{Onb, Pdf};
+use crate::ray::Ray;
+use crate::texture::{ConstantTexture, Texture};
+use crate::transform::MediumCoefficients;
+use crate::V3;
+
+use objekt_clonable::*;
+use rand::Rng;
+use std::f32::consts::PI;
+
+#[clonable]
+/// Distribution of directions into which light scatters inside of a medium.
+pub trait PhaseFunction: Send + Sync + Clone {
+    /// Returns density of scattering by the angle between the incoming and the scattered direction, integrates to 1 over the sphere.
+    ///
+    /// `cos_theta` - Cosine of the angle between the direction the light travelled and the direction it scatters to, 1 means no change of direction.
+    fn value(&self, cos_theta: f32) -> f32;
+
+    /// Returns scattered direction sampled proportionally to the phase function.
+    ///
+    /// `direction` - Unit direction the light travelled before scattering.
+    fn sample(&self, direction: V3<f32>) -> V3<f32>;
+}
+
+/// Returns direction with given cosine to `direction` and random azimuth.
+fn direction_with_cosine(direction: V3<f32>, cos_theta: f32) -> V3<f32> {
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = 2.0 * PI * rand::thread_rng().gen::<f32>();
+
+    Onb::build_from_w(&direction).local(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+/// Phase function scattering equally into all directions.
+#[derive(Clone, Copy, Debug)]
+pub struct IsotropicPhase;
+
+impl PhaseFunction for IsotropicPhase {
+    fn value(&self, _cos_theta: f32) -> f32 {
+        1.0 / (4.0 * PI)
+    }
+
+    fn sample(&self, direction: V3<f32>) -> V3<f32> {
+        direction_with_cosine(direction, 1.0 - 2.0 * rand::thread_rng().gen::<f32>())
+    }
+}
+
+/// Henyey-Greenstein phase function, with single parameter controlling how much light scatters forward or backward.
+#[derive(Clone, Copy, Debug)]
+pub struct HenyeyGreenstein {
+    /// Mean cosine of the scattering angle, from -1 for backward through 0 for isotropic to 1 for forward scattering.
+    pub g: f32,
+}
+
+/// Values of `g` closer to zero are treated as isotropic, to avoid division by zero in sampling.
+const ISOTROPIC_G: f32 = 1e-3;
+
+impl PhaseFunction for HenyeyGreenstein {
+    fn value(&self, cos_theta: f32) -> f32 {
+        let g = self.g;
+        let denominator = 1.0 + g * g - 2.0 * g * cos_theta;
+        (1.0 - g * g) / (4.0 * PI * denominator * denominator.sqrt())
+    }
+
+    fn sample(&self, direction: V3<f32>) -> V3<f32> {
+        let g = self.g;
+        let random = rand::thread_rng().gen::<f32>();
+
+        let cos_theta = if g.abs() < ISOTROPIC_G {
+            1.0 - 2.0 * random
+        } else {
+            let square = (1.0 - g * g) / (1.0 - g + 2.0 * g * random);
+            ((1.0 + g * g - square * square) / (2.0 * g)).clamp(-1.0, 1.0)
+        };
+
+        direction_with_cosine(direction, cos_theta)
+    }
+}
+
+/// Blend of two Henyey-Greenstein lobes, usually one forward and one backward, which fits media like skin or clouds better than single lobe.
+#[derive(Clone, Copy, Debug)]
+pub struct DoubleHenyeyGreenstein {
+    /// First lobe.
+    pub first: HenyeyGreenstein,
+    /// Second lobe.
+    pub second: HenyeyGreenstein,
+    /// Weight of the first lobe, from 0 to 1, the second lobe gets the rest.
+    pub weight: f32,
+}
+
+impl DoubleHenyeyGreenstein {
+    /// Returns new double lobe phase function.
+    ///
+    /// `g1`, `g2` - Mean cosines of the lobes.
+    ///
+    /// `weight` - Weight of the first lobe.
+    pub fn new(g1: f32, g2: f32, weight: f32) -> Self {
+        DoubleHenyeyGreenstein {
+            first: HenyeyGreenstein { g: g1 },
+            second: HenyeyGreenstein { g: g2 },
+            weight,
+        }
+    }
+}
+
+impl PhaseFunction for DoubleHenyeyGreenstein {
+    fn value(&self, cos_theta: f32) -> f32 {
+        self.weight * self.first.value(cos_theta)
+            + (1.0 - self.weight) * self.second.value(cos_theta)
+    }
+
+    fn sample(&self, direction: V3<f32>) -> V3<f32> {
+        if rand::thread_rng().gen::<f32>() < self.weight {
+            self.first.sample(direction)
+        } else {
+            self.second.sample(direction)
+        }
+    }
+}
+
+/// Pdf of directions scattered by a phase function.
+struct PhasePdf {
+    phase: Box<dyn PhaseFunction>,
+    /// Unit direction the light travelled before scattering.
+    direction: V3<f32>,
+}
+
+impl Pdf for PhasePdf {
+    fn value(&self, _origin: V3<f32>, direction: V3<f32>) -> f32 {
+        self.phase.value(self.direction.dot(direction.norm()))
+    }
+
+    fn generate(&self, _origin: V3<f32>) -> V3<f32> {
+        self.phase.sample(self.direction)
+    }
+}
+
+/// Material of particles in a medium, scattering light by a phase function.
+#[derive(Clone)]
+pub struct PhaseMaterial {
+    /// Fraction of light scattered, not absorbed, at each collision with a particle.
+    pub albedo: Box<dyn Texture + Sync + Send>,
+    /// Distribution of scattered directions.
+    pub phase: Box<dyn PhaseFunction>,
+}
+
+impl PhaseMaterial {
+    /// Returns new phase material.
+    ///
+    /// `albedo` - Fraction of light scattered at each collision.
+    ///
+    /// `phase` - Distribution of scattered directions.
+    pub fn new<P: PhaseFunction + 'static>(
+        albedo: Box<dyn Texture + Sync + Send>,
+        phase: P,
+    ) -> Self {
+        PhaseMaterial {
+            albedo,
+            phase: Box::new(phase),
+        }
+    }
+
+    /// Returns phase material with albedo given by scattering and absorption coefficients of the medium.
+    ///
+    /// `coefficients` - Scattering and absorption coefficients of the medium.
+    ///
+    /// `phase` - Distribution of scattered directions.
+    pub fn from_coefficients<P: PhaseFunction + 'static>(
+        coefficients: &MediumCoefficients,
+        phase: P,
+    ) -> Self {
+        PhaseMaterial::new(
+            Box::new(ConstantTexture {
+                color: coefficients.albedo(),
+            }),
+            phase,
+        )
+    }
+}
+
+impl MaterialTrait for PhaseMaterial {
+    fn scatter<'a>(&self, ray_in: &'a Ray, hit: &Hit) -> Option<ScatterRecord<'a>> {
+        Some(ScatterRecord {
+            specular_ray: None,
+            attenuation: self.albedo.sample(hit),
+            pdf: Box::new(PhasePdf {
+                phase: self.phase.clone(),
+                direction: ray_in.end.norm(),
+            }),
+            invisible: false,
+        })
+    }
+
+    fn scattering_pdf(&self, ray_in: &Ray, _hit: &Hit, ray_scattered: &Ray) -> f32 {
+        self.phase
+            .value(ray_in.end.norm().dot(ray_scattered.end.norm()))
+    }
+
+    fn color_emitted(&self, _ray_in: &Ray, _hit: &Hit) -> V3<f32> {
+        V3::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns integral of the phase function over the sphere.
+    fn integral(phase: &dyn PhaseFunction) -> f32 {
+        let steps = 20000;
+        (0..steps)
+            .map(|i| {
+                let cos_theta = -1.0 + (i as f32 + 0.5) * 2.0 / steps as f32;
+                phase.value(cos_theta) * 2.0 * PI * 2.0 / steps as f32
+            })
+            .sum()
+    }
+
+    #[test]
+    fn normalization_test() {
+        assert!((integral(&IsotropicPhase) - 1.0).abs() < 1e-3);
+        assert!((integral(&HenyeyGreenstein { g: 0.7 }) - 1.0).abs() < 1e-3);
+        assert!((integral(&DoubleHenyeyGreenstein::new(0.8, -0.3, 0.6)) - 1.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn sampling_mean_cosine_test() {
+        let direction = V3::new(0.0, 0.0, 1.0);
+        let phase = HenyeyGreenstein { g: 0.6 };
+        let samples = 100000;
+
+        let mean = (0..samples)
+            .map(|_| phase.sample(direction).dot(direction))
+            .sum::<f32>()
+            / samples as f32;
+
+        // Mean cosine of Henyey-Greenstein distribution is g
+        assert!((mean - 0.6).abs() < 0.02);
+    }
+}
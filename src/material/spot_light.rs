@@ -0,0 +1,57 @@
+use crate::hit::Hit;
+use crate::material::*;
+use crate::ray::Ray;
+use crate::V3;
+
+/// Emits light only into a cone, falling off smoothly between its inner and outer angle.
+#[derive(Clone)]
+pub struct SpotLight {
+    /// Color of the light inside of the inner cone.
+    pub color: V3<f32>,
+    /// Axis of the cone, unit vector.
+    pub direction: V3<f32>,
+    /// Cosine of the angle to which the light is full.
+    pub cos_inner: f32,
+    /// Cosine of the angle behind which there is no light.
+    pub cos_outer: f32,
+}
+
+impl MaterialTrait for SpotLight {
+    fn scatter<'a>(&self, _ray_in: &'a Ray, _hit: &Hit) -> Option<ScatterRecord<'a>> {
+        None
+    }
+
+    fn scattering_pdf(&self, _ray_in: &Ray, _hit: &Hit, _ray_scattered: &Ray) -> f32 {
+        0.0
+    }
+
+    fn color_emitted(&self, ray_in: &Ray, hit: &Hit) -> V3<f32> {
+        if !hit.front_face {
+            return V3::default();
+        }
+
+        // Light goes against the ray which sees it
+        let cosine = -ray_in.end.norm().dot(self.direction);
+        let falloff = ((cosine - self.cos_outer) / (self.cos_inner - self.cos_outer).max(1e-6))
+            .clamp(0.0, 1.0);
+        self.color * (falloff * falloff)
+    }
+}
+
+impl SpotLight {
+    /// Returns new spot light.
+    ///
+    /// `color` - Color of the light inside of the inner cone.
+    ///
+    /// `direction` - Axis of the cone.
+    ///
+    /// `inner_angle`, `outer_angle` - Angles from the axis in radians, between which the light falls off.
+    pub fn new(color: V3<f32>, direction: V3<f32>, inner_angle: f32, outer_angle: f32) -> Self {
+        SpotLight {
+            color,
+            direction: direction.norm(),
+            cos_inner: inner_angle.cos(),
+            cos_outer: outer_angle.cos(),
+        }
+    }
+}
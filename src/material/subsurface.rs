@@ -0,0 +1,109 @@
+use crate::hit::Hit;
+use crate::material::dielectric::scatter_through;
+use crate::material::*;
+use crate::ray::Ray;
+use crate::texture::ConstantTexture;
+use crate::transform::{Medium, MediumInterface};
+use crate::V3;
+
+/// Material of translucent objects like skin, wax or marble, light refracts into the object and walks randomly between particles inside, until it leaves the object or is absorbed.
+///
+/// Object with this material has to be closed, like a mesh without holes, with normals pointing outwards.
+#[derive(Clone)]
+pub struct Subsurface {
+    /// Refractive index of the surface.
+    pub refractive_index: f32,
+    /// Mean distance light travels inside the object between collisions with particles, separately for each color channel.
+    pub mean_free_path: V3<f32>,
+    /// Material of the particles inside the object, usually made by [`Subsurface::phase_material`].
+    pub phase_function: usize,
+}
+
+impl Subsurface {
+    /// Returns new subsurface material.
+    ///
+    /// `refractive_index` - Refractive index of the surface.
+    ///
+    /// `mean_free_path` - Mean distance between collisions inside the object, longer distances make the object more translucent.
+    ///
+    /// `phase_function` - Material of the particles inside the object.
+    pub fn new(refractive_index: f32, mean_free_path: V3<f32>, phase_function: usize) -> Self {
+        Subsurface {
+            refractive_index,
+            mean_free_path,
+            phase_function,
+        }
+    }
+
+    /// Returns material of the particles inside the object, with albedo of single collision chosen so that thick object has the given color.
+    ///
+    /// `albedo` - Color of the object after many collisions inside.
+    ///
+    /// `phase` - Distribution of scattered directions.
+    pub fn phase_material<P: PhaseFunction + 'static>(albedo: V3<f32>, phase: P) -> PhaseMaterial {
+        PhaseMaterial::new(
+            Box::new(ConstantTexture {
+                color: albedo.into_iter().map(single_scattering_albedo).collect(),
+            }),
+            phase,
+        )
+    }
+
+    /// Returns medium inside of the object.
+    fn interface(&self) -> MediumInterface {
+        MediumInterface {
+            interior: Medium {
+                refractive_index: self.refractive_index,
+                extinction: self
+                    .mean_free_path
+                    .into_iter()
+                    .map(|distance| 1.0 / distance)
+                    .collect(),
+                phase_function: self.phase_function,
+            },
+            priority: 0,
+        }
+    }
+}
+
+/// Returns albedo of single collision, for which random walk gives the albedo of many collisions, fit from "Practical and Controllable Subsurface Scattering for Production Path Tracing" by Chiang et al.
+///
+/// `albedo` - Albedo of many collisions, from 0 to 1.
+fn single_scattering_albedo(albedo: f32) -> f32 {
+    let albedo = albedo.clamp(0.0, 1.0);
+    1.0 - (4.09712 + 4.20863 * albedo
+        - (9.59217 + 41.6808 * albedo + 17.7126 * albedo * albedo).sqrt())
+    .powi(2)
+}
+
+impl MaterialTrait for Subsurface {
+    fn scatter<'a>(&self, ray: &'a Ray, hit: &Hit) -> Option<ScatterRecord<'a>> {
+        Some(scatter_through(
+            ray,
+            hit,
+            Some(self.interface()),
+            self.refractive_index,
+        ))
+    }
+
+    fn scattering_pdf(&self, _ray_in: &Ray, _hit: &Hit, _ray_scattered: &Ray) -> f32 {
+        0.0
+    }
+
+    fn color_emitted(&self, _ray_in: &Ray, _hit: &Hit) -> V3<f32> {
+        V3::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn single_scattering_albedo_test() {
+        assert!(single_scattering_albedo(0.0).abs() < 1e-3);
+        assert!((single_scattering_albedo(1.0) - 1.0).abs() < 1e-3);
+        // Many collisions darken the object, so single collision has to absorb less
+        assert!(single_scattering_albedo(0.5) > 0.5);
+    }
+}
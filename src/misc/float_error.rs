@@ -0,0 +1,69 @@
+//! Bounds of rounding errors of floating point computations, used to start rays right next to surfaces without hitting them again.
+
+use crate::V3;
+
+/// Returns bound of the relative error of `n` consecutive floating point operations.
+///
+/// `n` - Number of operations, each rounding the result once.
+pub fn gamma(n: u32) -> f32 {
+    let epsilon = f32::EPSILON * 0.5;
+    (n as f32 * epsilon) / (1.0 - n as f32 * epsilon)
+}
+
+/// Returns origin of a ray leaving a surface, moved along the normal just past the error of the hit point, so the ray can't hit the same surface again.
+///
+/// `point` - Computed hit point on the surface.
+///
+/// `error` - Bound of the absolute error of the hit point along each axis.
+///
+/// `normal` - Normal vector to the surface, of unit length.
+///
+/// `direction` - Direction of the ray leaving the surface, the origin is moved to the side it goes to.
+pub fn offset_ray_origin(
+    point: V3<f32>,
+    error: V3<f32>,
+    normal: V3<f32>,
+    direction: V3<f32>,
+) -> V3<f32> {
+    let distance = normal.abs().dot(error);
+    let offset = if direction.dot(normal) < 0.0 {
+        normal * -distance
+    } else {
+        normal * distance
+    };
+
+    // Rounding of the sum could bring the origin back toward the surface, so it is rounded away from it
+    let away = |coordinate: f32, offset: f32| {
+        if offset > 0.0 {
+            coordinate.next_up()
+        } else if offset < 0.0 {
+            coordinate.next_down()
+        } else {
+            coordinate
+        }
+    };
+    let origin = point + offset;
+    V3::new(
+        away(origin.x, offset.x),
+        away(origin.y, offset.y),
+        away(origin.z, offset.z),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset_test() {
+        let point = V3::new(1000.0, 1.0, -3.0);
+        let error = V3::new(1e-3, 1e-3, 1e-3);
+        let normal = V3::new(0.0, 1.0, 0.0);
+
+        let above = offset_ray_origin(point, error, normal, V3::new(1.0, 1.0, 0.0));
+        let below = offset_ray_origin(point, error, normal, V3::new(1.0, -1.0, 0.0));
+        assert!(above.y > 1.0009 && below.y < 0.9991);
+        assert_eq!(above.x, point.x);
+        assert!(gamma(3) > 3.0 * f32::EPSILON * 0.5);
+    }
+}
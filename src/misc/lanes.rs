@@ -0,0 +1,174 @@
+//! Four f32 values processed together, with SIMD instructions if the `simd` feature is enabled and plain arrays otherwise.
+
+use std::ops::{Add, Div, Mul, Sub};
+
+#[cfg(feature = "simd")]
+use wide::{f32x4, CmpLe};
+
+/// Four f32 values, called lanes, on which every operation is done lane by lane.
+#[derive(Clone, Copy, Debug)]
+pub struct F32x4 {
+    #[cfg(feature = "simd")]
+    lanes: f32x4,
+    #[cfg(not(feature = "simd"))]
+    lanes: [f32; 4],
+}
+
+#[cfg(feature = "simd")]
+impl F32x4 {
+    /// Returns lanes with given values.
+    ///
+    /// `lanes` - Values of the lanes.
+    pub fn new(lanes: [f32; 4]) -> Self {
+        F32x4 {
+            lanes: f32x4::new(lanes),
+        }
+    }
+
+    /// Returns lanes with the same value.
+    ///
+    /// `value` - Value of all lanes.
+    pub fn splat(value: f32) -> Self {
+        F32x4 {
+            lanes: f32x4::splat(value),
+        }
+    }
+
+    /// Returns values of the lanes.
+    pub fn to_array(self) -> [f32; 4] {
+        self.lanes.to_array()
+    }
+
+    /// Returns smaller value of each pair of lanes.
+    ///
+    /// `other` - Lanes to compare with.
+    pub fn min(self, other: Self) -> Self {
+        F32x4 {
+            lanes: self.lanes.min(other.lanes),
+        }
+    }
+
+    /// Returns bigger value of each pair of lanes.
+    ///
+    /// `other` - Lanes to compare with.
+    pub fn max(self, other: Self) -> Self {
+        F32x4 {
+            lanes: self.lanes.max(other.lanes),
+        }
+    }
+
+    /// Returns absolute value of each lane.
+    pub fn abs(self) -> Self {
+        F32x4 {
+            lanes: self.lanes.abs(),
+        }
+    }
+
+    /// Returns mask with bit of each lane set, if the lane is less than or equal to the lane of the other value.
+    ///
+    /// `other` - Lanes to compare with.
+    pub fn le(self, other: Self) -> u32 {
+        self.lanes.cmp_le(other.lanes).move_mask() as u32
+    }
+}
+
+#[cfg(not(feature = "simd"))]
+impl F32x4 {
+    /// Returns lanes with given values.
+    ///
+    /// `lanes` - Values of the lanes.
+    pub fn new(lanes: [f32; 4]) -> Self {
+        F32x4 { lanes }
+    }
+
+    /// Returns lanes with the same value.
+    ///
+    /// `value` - Value of all lanes.
+    pub fn splat(value: f32) -> Self {
+        F32x4 { lanes: [value; 4] }
+    }
+
+    /// Returns values of the lanes.
+    pub fn to_array(self) -> [f32; 4] {
+        self.lanes
+    }
+
+    /// Returns smaller value of each pair of lanes.
+    ///
+    /// `other` - Lanes to compare with.
+    pub fn min(self, other: Self) -> Self {
+        self.zip(other, f32::min)
+    }
+
+    /// Returns bigger value of each pair of lanes.
+    ///
+    /// `other` - Lanes to compare with.
+    pub fn max(self, other: Self) -> Self {
+        self.zip(other, f32::max)
+    }
+
+    /// Returns absolute value of each lane.
+    pub fn abs(self) -> Self {
+        F32x4 {
+            lanes: self.lanes.map(f32::abs),
+        }
+    }
+
+    /// Returns mask with bit of each lane set, if the lane is less than or equal to the lane of the other value.
+    ///
+    /// `other` - Lanes to compare with.
+    pub fn le(self, other: Self) -> u32 {
+        (0..4)
+            .filter(|&lane| self.lanes[lane] <= other.lanes[lane])
+            .fold(0, |mask, lane| mask | 1 << lane)
+    }
+
+    fn zip(self, other: Self, operation: impl Fn(f32, f32) -> f32) -> Self {
+        let mut lanes = self.lanes;
+        for (lane, value) in lanes.iter_mut().zip(other.lanes) {
+            *lane = operation(*lane, value);
+        }
+        F32x4 { lanes }
+    }
+}
+
+macro_rules! impl_operation {
+    ($operation:ident, $method:ident, $symbol:tt) => {
+        impl $operation for F32x4 {
+            type Output = F32x4;
+
+            #[cfg(feature = "simd")]
+            fn $method(self, other: F32x4) -> F32x4 {
+                F32x4 {
+                    lanes: self.lanes $symbol other.lanes,
+                }
+            }
+
+            #[cfg(not(feature = "simd"))]
+            fn $method(self, other: F32x4) -> F32x4 {
+                self.zip(other, |a, b| a $symbol b)
+            }
+        }
+    };
+}
+
+impl_operation!(Add, add, +);
+impl_operation!(Sub, sub, -);
+impl_operation!(Mul, mul, *);
+impl_operation!(Div, div, /);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lanes_test() {
+        let a = F32x4::new([1.0, -2.0, 3.0, f32::INFINITY]);
+        let b = F32x4::splat(2.0);
+
+        assert_eq!((a * b - b).to_array(), [0.0, -6.0, 4.0, f32::INFINITY]);
+        assert_eq!(a.min(b).to_array(), [1.0, -2.0, 2.0, 2.0]);
+        assert_eq!(a.abs().max(b).to_array(), [2.0, 2.0, 3.0, f32::INFINITY]);
+        assert_eq!(a.le(b), 0b0011);
+    }
+}
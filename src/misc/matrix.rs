@@ -0,0 +1,161 @@
+//! 4x4 matrix for affine transformations.
+
+use crate::V3;
+use std::ops::Mul;
+
+/// Matrix with 4 rows and 4 columns, acting on column vectors in homogeneous coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Matrix4 {
+    /// Elements of the matrix, indexed by row and then column.
+    pub m: [[f32; 4]; 4],
+}
+
+impl Matrix4 {
+    /// Returns identity matrix.
+    pub const fn identity() -> Self {
+        Matrix4 {
+            m: [
+                [1.0, 0.0, 0.0, 0.0],
+                [0.0, 1.0, 0.0, 0.0],
+                [0.0, 0.0, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        }
+    }
+
+    /// Returns transposed matrix.
+    pub fn transpose(&self) -> Self {
+        let mut m = [[0.0; 4]; 4];
+        for (row, values) in m.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = self.m[column][row];
+            }
+        }
+        Matrix4 { m }
+    }
+
+    /// Returns determinant of the upper left 3x3 part, which tells how the matrix changes volumes.
+    pub fn determinant3(&self) -> f32 {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// Returns inverse matrix computed with Gauss-Jordan elimination, or None if the matrix is singular or has elements which aren't finite.
+    pub fn inverse(&self) -> Option<Self> {
+        if self.m.iter().flatten().any(|value| !value.is_finite()) {
+            return None;
+        }
+
+        let mut m = self.m;
+        let mut inverse = Matrix4::identity().m;
+
+        for column in 0..4 {
+            // Partial pivoting keeps the elimination stable
+            let pivot =
+                (column..4).max_by(|&a, &b| m[a][column].abs().total_cmp(&m[b][column].abs()))?;
+            if m[pivot][column].abs() < f32::EPSILON {
+                return None;
+            }
+            m.swap(column, pivot);
+            inverse.swap(column, pivot);
+
+            let scale = 1.0 / m[column][column];
+            for i in 0..4 {
+                m[column][i] *= scale;
+                inverse[column][i] *= scale;
+            }
+
+            for row in 0..4 {
+                if row != column {
+                    let factor = m[row][column];
+                    for i in 0..4 {
+                        m[row][i] -= factor * m[column][i];
+                        inverse[row][i] -= factor * inverse[column][i];
+                    }
+                }
+            }
+        }
+
+        Some(Matrix4 { m: inverse })
+    }
+
+    /// Returns point transformed by the matrix, translation is applied.
+    ///
+    /// `p` - Point to transform.
+    pub fn transform_point(&self, p: V3<f32>) -> V3<f32> {
+        let m = &self.m;
+        V3::new(
+            m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3],
+            m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3],
+            m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3],
+        )
+    }
+
+    /// Returns vector transformed by the matrix, translation is ignored.
+    ///
+    /// `v` - Vector to transform.
+    pub fn transform_vector(&self, v: V3<f32>) -> V3<f32> {
+        let m = &self.m;
+        V3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+}
+
+impl Default for Matrix4 {
+    fn default() -> Self {
+        Matrix4::identity()
+    }
+}
+
+impl Mul for Matrix4 {
+    type Output = Matrix4;
+
+    fn mul(self, other: Matrix4) -> Matrix4 {
+        let mut m = [[0.0; 4]; 4];
+        for (row, values) in m.iter_mut().enumerate() {
+            for (column, value) in values.iter_mut().enumerate() {
+                *value = (0..4).map(|i| self.m[row][i] * other.m[i][column]).sum();
+            }
+        }
+        Matrix4 { m }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inverse_test() {
+        let matrix = Matrix4 {
+            m: [
+                [2.0, 0.5, 0.0, 1.0],
+                [0.0, 1.0, 3.0, -2.0],
+                [1.0, 0.0, 1.0, 0.5],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        };
+
+        let product = matrix * matrix.inverse().unwrap();
+        for (row, values) in product.m.iter().enumerate() {
+            for (column, value) in values.iter().enumerate() {
+                let expected = if row == column { 1.0 } else { 0.0 };
+                assert!((value - expected).abs() < 1e-5);
+            }
+        }
+
+        assert!(Matrix4 { m: [[0.0; 4]; 4] }.inverse().is_none());
+
+        // Matrices from files may overflow or be computed from overflowed numbers
+        for value in [f32::NAN, f32::INFINITY] {
+            let mut broken = matrix;
+            broken.m[1][2] = value;
+            assert!(broken.inverse().is_none());
+        }
+    }
+}
@@ -2,8 +2,12 @@
 
 mod algs;
 mod filter;
+mod float_error;
 mod interval;
+mod lanes;
+mod matrix;
 mod multizip;
+mod noise;
 mod onb;
 mod pdf;
 mod perlin;
@@ -11,8 +15,12 @@ mod ppm;
 
 pub use algs::*;
 pub use filter::*;
+pub use float_error::*;
 pub use interval::*;
+pub use lanes::*;
+pub use matrix::*;
 pub use multizip::*;
+pub use noise::*;
 pub use onb::*;
 pub use pdf::*;
 pub use perlin::*;
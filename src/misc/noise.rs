@@ -0,0 +1,404 @@
+use crate::V3;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+/// Gradients on the edges of a cube, used by 2D and 3D noise.
+const GRADIENTS_3: [[f32; 4]; 12] = [
+    [1.0, 1.0, 0.0, 0.0],
+    [-1.0, 1.0, 0.0, 0.0],
+    [1.0, -1.0, 0.0, 0.0],
+    [-1.0, -1.0, 0.0, 0.0],
+    [1.0, 0.0, 1.0, 0.0],
+    [-1.0, 0.0, 1.0, 0.0],
+    [1.0, 0.0, -1.0, 0.0],
+    [-1.0, 0.0, -1.0, 0.0],
+    [0.0, 1.0, 1.0, 0.0],
+    [0.0, -1.0, 1.0, 0.0],
+    [0.0, 1.0, -1.0, 0.0],
+    [0.0, -1.0, -1.0, 0.0],
+];
+
+/// Scale of 4D Perlin noise. Noise with unit gradients is bounded by half of the square root of its dimension, 4D gradients are √3 long, so the scaled noise stays in <-1, 1>.
+const PERLIN_4D_SCALE: f32 = 0.577_350_26;
+
+/// Returns one of 32 gradients on the edges of a 4D hypercube, with one zero and three ±1 components.
+fn gradient_4(hash: usize) -> [f32; 4] {
+    let zero = (hash >> 3) & 3;
+    let mut signs = hash & 7;
+    let mut gradient = [0.0; 4];
+
+    for (i, component) in gradient.iter_mut().enumerate() {
+        if i != zero {
+            *component = if signs & 1 == 0 { 1.0 } else { -1.0 };
+            signs >>= 1;
+        }
+    }
+
+    gradient
+}
+
+/// Seedable gradient noise, improved Perlin noise and simplex noise in 2, 3 and 4 dimensions.
+///
+/// All variants return values roughly in <-1, 1> range, which are zero on average.
+#[derive(Clone)]
+pub struct Noise {
+    /// Permutation of 0..256, repeated twice so lookups don't have to wrap.
+    permutation: Vec<u8>,
+}
+
+impl Noise {
+    /// Returns new noise, the same seed always gives the same noise.
+    ///
+    /// `seed` - Seed of the permutation table.
+    pub fn new(seed: u64) -> Noise {
+        let mut permutation: Vec<u8> = (0..=255).collect();
+        permutation.shuffle(&mut StdRng::seed_from_u64(seed));
+        permutation.extend_from_within(..);
+
+        Noise { permutation }
+    }
+
+    /// Returns pseudorandom number from 0 to 255 for the lattice point.
+    fn hash(&self, cell: &[i32]) -> usize {
+        cell.iter().fold(0, |hash, &coordinate| {
+            self.permutation[hash + (coordinate & 255) as usize] as usize
+        })
+    }
+
+    /// Returns gradient of the lattice point, only the first `N` components are used.
+    fn gradient<const N: usize>(&self, cell: &[i32; N]) -> [f32; 4] {
+        let hash = self.hash(cell);
+        if N < 4 {
+            GRADIENTS_3[hash % 12]
+        } else {
+            gradient_4(hash)
+        }
+    }
+
+    /// Returns improved Perlin noise, which is smooth in value and first two derivatives.
+    fn perlin<const N: usize>(&self, point: [f32; N]) -> f32 {
+        let cell = point.map(|coordinate| coordinate.floor());
+        let offset: [f32; N] = std::array::from_fn(|i| point[i] - cell[i]);
+        let cell = cell.map(|coordinate| coordinate as i32);
+        let fade = offset.map(|t| t * t * t * (t * (t * 6.0 - 15.0) + 10.0));
+
+        (0..1 << N)
+            .map(|corner: usize| {
+                let mut corner_cell = cell;
+                let mut distance = offset;
+                let mut weight = 1.0;
+
+                for i in 0..N {
+                    if corner >> i & 1 == 1 {
+                        corner_cell[i] += 1;
+                        distance[i] -= 1.0;
+                        weight *= fade[i];
+                    } else {
+                        weight *= 1.0 - fade[i];
+                    }
+                }
+
+                let gradient = self.gradient(&corner_cell);
+                weight * (0..N).map(|i| gradient[i] * distance[i]).sum::<f32>()
+            })
+            .sum()
+    }
+
+    /// Returns simplex noise, which is cheaper than Perlin noise in higher dimensions and has no axis aligned artifacts.
+    fn simplex<const N: usize>(&self, point: [f32; N]) -> f32 {
+        let n = N as f32;
+        // Factors skewing the space into hypercubes made of simplices, and back
+        let skew = ((n + 1.0).sqrt() - 1.0) / n;
+        let unskew = (1.0 - 1.0 / (n + 1.0).sqrt()) / n;
+        let (radius, scale) = match N {
+            2 => (0.5, 70.0),
+            3 => (0.6, 32.0),
+            _ => (0.6, 27.0),
+        };
+
+        let skewed = point.iter().sum::<f32>() * skew;
+        let cell = point.map(|coordinate| (coordinate + skewed).floor() as i32);
+        let unskewed = cell.iter().sum::<i32>() as f32 * unskew;
+        let offset: [f32; N] = std::array::from_fn(|i| point[i] - (cell[i] as f32 - unskewed));
+
+        // Simplex containing the point is found by ordering offset components
+        let mut order: [usize; N] = std::array::from_fn(|i| i);
+        order.sort_by(|&a, &b| offset[b].total_cmp(&offset[a]));
+
+        let mut vertex = cell;
+        let mut total = 0.0;
+        for k in 0..=N {
+            if k > 0 {
+                vertex[order[k - 1]] += 1;
+            }
+
+            let distance: [f32; N] = std::array::from_fn(|i| {
+                offset[i] - (vertex[i] - cell[i]) as f32 + k as f32 * unskew
+            });
+            let t = radius - distance.iter().map(|d| d * d).sum::<f32>();
+
+            if t > 0.0 {
+                let gradient = self.gradient(&vertex);
+                let dot = (0..N).map(|i| gradient[i] * distance[i]).sum::<f32>();
+                total += t * t * t * t * dot;
+            }
+        }
+
+        total * scale
+    }
+
+    /// Returns 2D improved Perlin noise.
+    pub fn perlin_2d(&self, x: f32, y: f32) -> f32 {
+        self.perlin([x, y])
+    }
+
+    /// Returns 3D improved Perlin noise.
+    pub fn perlin_3d(&self, point: V3<f32>) -> f32 {
+        self.perlin([point.x, point.y, point.z])
+    }
+
+    /// Returns 4D improved Perlin noise, the fourth dimension is usually time for animated noise.
+    pub fn perlin_4d(&self, point: V3<f32>, w: f32) -> f32 {
+        self.perlin([point.x, point.y, point.z, w]) * PERLIN_4D_SCALE
+    }
+
+    /// Returns 2D simplex noise.
+    pub fn simplex_2d(&self, x: f32, y: f32) -> f32 {
+        self.simplex([x, y])
+    }
+
+    /// Returns 3D simplex noise.
+    pub fn simplex_3d(&self, point: V3<f32>) -> f32 {
+        self.simplex([point.x, point.y, point.z])
+    }
+
+    /// Returns 4D simplex noise, the fourth dimension is usually time for animated noise.
+    pub fn simplex_4d(&self, point: V3<f32>, w: f32) -> f32 {
+        self.simplex([point.x, point.y, point.z, w])
+    }
+}
+
+/// Noise function from which fractal noise is built.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoiseBasis {
+    /// Improved Perlin noise.
+    Perlin,
+    /// Simplex noise.
+    Simplex,
+}
+
+/// How octaves of fractal noise are combined.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FractalMode {
+    /// Fractal Brownian motion, plain sum of octaves.
+    Fbm,
+    /// Sum of inverted absolute values of octaves, which makes sharp ridges.
+    Ridged,
+    /// Sum of absolute values of octaves, which makes round billows.
+    Billow,
+}
+
+/// Sum of octaves of noise with growing frequency and falling amplitude, optionally with warped domain.
+///
+/// Returns values roughly in <-1, 1> range.
+#[derive(Clone)]
+pub struct FractalNoise {
+    /// Source of the noise.
+    pub noise: Noise,
+    /// Noise function used for octaves.
+    pub basis: NoiseBasis,
+    /// How octaves are combined.
+    pub mode: FractalMode,
+    /// Number of summed octaves.
+    pub octaves: u32,
+    /// How much frequency grows with each octave.
+    pub lacunarity: f32,
+    /// How much amplitude falls with each octave.
+    pub gain: f32,
+    /// Strength of domain warping, the point is moved by fractal noise before it is evaluated, 0 turns warping off.
+    pub warp: f32,
+    /// Time of animated noise, which uses one dimension more, or `None` for static noise.
+    pub time: Option<f32>,
+}
+
+impl FractalNoise {
+    /// Returns Perlin fBm with 6 octaves, lacunarity 2 and gain 0.5.
+    ///
+    /// `seed` - Seed of the noise.
+    pub fn new(seed: u64) -> Self {
+        FractalNoise {
+            noise: Noise::new(seed),
+            basis: NoiseBasis::Perlin,
+            mode: FractalMode::Fbm,
+            octaves: 6,
+            lacunarity: 2.0,
+            gain: 0.5,
+            warp: 0.0,
+            time: None,
+        }
+    }
+
+    /// Returns fractal noise using given noise function.
+    pub fn with_basis(mut self, basis: NoiseBasis) -> Self {
+        self.basis = basis;
+        self
+    }
+
+    /// Returns fractal noise combining octaves in given way.
+    pub fn with_mode(mut self, mode: FractalMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Returns fractal noise with given octaves.
+    ///
+    /// `octaves` - Number of summed octaves.
+    ///
+    /// `lacunarity` - How much frequency grows with each octave.
+    ///
+    /// `gain` - How much amplitude falls with each octave.
+    pub fn with_octaves(mut self, octaves: u32, lacunarity: f32, gain: f32) -> Self {
+        self.octaves = octaves;
+        self.lacunarity = lacunarity;
+        self.gain = gain;
+        self
+    }
+
+    /// Returns fractal noise with warped domain.
+    ///
+    /// `warp` - How far the point is moved by the noise.
+    pub fn with_warp(mut self, warp: f32) -> Self {
+        self.warp = warp;
+        self
+    }
+
+    /// Returns fractal noise animated in time.
+    ///
+    /// `time` - Time at which the noise is evaluated, noise changes smoothly with it.
+    pub fn with_time(mut self, time: f32) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    /// Returns value of the noise at the point.
+    pub fn value(&self, point: V3<f32>) -> f32 {
+        if self.warp == 0.0 {
+            return self.octaves_sum(point);
+        }
+
+        // Offsets decorrelate noise used for each axis of the warp
+        let offset = V3::new(
+            self.octaves_sum(point + V3::new(31.4, 0.0, 0.0)),
+            self.octaves_sum(point + V3::new(0.0, 27.1, 0.0)),
+            self.octaves_sum(point + V3::new(0.0, 0.0, 18.3)),
+        );
+
+        self.octaves_sum(point + offset * self.warp)
+    }
+
+    fn basis_value(&self, point: V3<f32>) -> f32 {
+        match (self.basis, self.time) {
+            (NoiseBasis::Perlin, None) => self.noise.perlin_3d(point),
+            (NoiseBasis::Perlin, Some(time)) => self.noise.perlin_4d(point, time),
+            (NoiseBasis::Simplex, None) => self.noise.simplex_3d(point),
+            (NoiseBasis::Simplex, Some(time)) => self.noise.simplex_4d(point, time),
+        }
+    }
+
+    fn octaves_sum(&self, point: V3<f32>) -> f32 {
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        let mut sum = 0.0;
+        let mut amplitude_sum = 0.0;
+
+        for _ in 0..self.octaves {
+            let value = self.basis_value(point * frequency);
+            sum += amplitude
+                * match self.mode {
+                    FractalMode::Fbm => value,
+                    FractalMode::Ridged => {
+                        let ridge = 1.0 - value.abs();
+                        ridge * ridge * 2.0 - 1.0
+                    }
+                    FractalMode::Billow => value.abs() * 2.0 - 1.0,
+                };
+            amplitude_sum += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+
+        if amplitude_sum > 0.0 {
+            sum / amplitude_sum
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn noise_seed_test() {
+        let point = V3::new(1.3, 2.7, -0.4);
+
+        assert_eq!(
+            Noise::new(7).perlin_3d(point),
+            Noise::new(7).perlin_3d(point)
+        );
+        assert_ne!(
+            Noise::new(7).perlin_3d(point),
+            Noise::new(8).perlin_3d(point)
+        );
+    }
+
+    #[test]
+    fn noise_range_test() {
+        let noise = Noise::new(1);
+
+        for i in 0..2000 {
+            let t = i as f32 * 0.173;
+            let point = V3::new(t.sin() * 9.1, t * 0.37, (t * 1.7).cos() * 5.3);
+
+            // Perlin noise is zero on the lattice
+            assert!(noise.perlin_3d(point.floor()).abs() < 1e-6);
+
+            for value in [
+                noise.perlin_2d(point.x, point.y),
+                noise.perlin_3d(point),
+                noise.simplex_2d(point.x, point.y),
+                noise.simplex_3d(point),
+                noise.simplex_4d(point, t),
+            ] {
+                assert!(value.abs() <= 1.1, "{}", value);
+            }
+        }
+    }
+
+    #[test]
+    fn perlin_4d_bound_test() {
+        let noise = Noise::new(1);
+        let mut rng = StdRng::seed_from_u64(3);
+
+        for _ in 0..20000 {
+            let mut coordinate = || rng.gen_range(-64.0..64.0);
+            let (point, w) = (
+                V3::new(coordinate(), coordinate(), coordinate()),
+                coordinate(),
+            );
+
+            // Centers of cells are where the gradients can add up the most
+            let center = point.floor() + V3::new(0.5, 0.5, 0.5);
+            for value in [
+                noise.perlin_4d(point, w),
+                noise.perlin_4d(center, w.floor() + 0.5),
+            ] {
+                assert!(value.abs() <= 1.0, "{}", value);
+            }
+        }
+    }
+}
@@ -30,7 +30,7 @@ impl Pdf for ZeroPdf {
 pub struct UniformPdf;
 impl Pdf for UniformPdf {
     fn value(&self, _origin: V3<f32>, _direction: V3<f32>) -> f32 {
-        1.0 / 4.0 * std::f32::consts::PI
+        1.0 / (4.0 * std::f32::consts::PI)
     }
 
     fn generate(&self, _origin: V3<f32>) -> V3<f32> {
@@ -2,6 +2,9 @@ use crate::V3;
 
 use arr_macro::arr;
 use permutation_iterator::Permutor;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
 
 //#[macro_use]
 use itertools::*;
@@ -13,7 +16,7 @@ pub struct Perlin {
 }
 
 impl Perlin {
-    /// Returns new perlin n
+    /// Returns new randomized perlin noise.
     pub fn new() -> Perlin {
         let mut hashed_iter_x = Permutor::new(256_u64);
         let mut hashed_iter_y = Permutor::new(256_u64);
@@ -29,6 +32,38 @@ impl Perlin {
         }
     }
 
+    /// Returns new perlin noise, the same seed always gives the same noise.
+    ///
+    /// `seed` - Seed of random gradients and permutations.
+    pub fn with_seed(seed: u64) -> Perlin {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let mut permutation = || {
+            let mut permutation: [u32; 256] = arr![0; 256];
+            permutation
+                .iter_mut()
+                .enumerate()
+                .for_each(|(i, value)| *value = i as u32);
+            permutation.shuffle(&mut rng);
+            permutation
+        };
+        let permutate = (permutation(), permutation(), permutation());
+
+        let mut gradient = || {
+            V3::new(
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+                rng.gen_range(-1.0..1.0),
+            )
+            .norm()
+        };
+
+        Perlin {
+            perlin: arr![gradient(); 256],
+            permutate,
+        }
+    }
+
     /// Returns grayscale value of noise at specific point.
     ///
     /// `point` - Point from which noise value is taken.
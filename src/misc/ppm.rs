@@ -29,8 +29,7 @@ impl Ppm {
     pub fn new_from_vec(height: u32, width: u32, rgb: Vec<u8>) -> Result<Ppm, &'static str> {
         let size = 3 * height * width;
 
-        use std::convert::TryInto;
-        if size != rgb.len().try_into().unwrap() {
+        if size as usize != rgb.len() {
             Err("Invalid rgb vector size.")
         } else {
             Ok(Ppm {
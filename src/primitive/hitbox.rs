@@ -91,6 +91,10 @@ impl Hittable for HitBox {
         self.sides.hit(ray)
     }
 
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        self.sides.occluded(ray, t_max)
+    }
+
     fn bounding_box(&self) -> Aabb {
         Aabb {
             x: Interval::new(self.box_min.x, self.box_max.x),
@@ -1,22 +1,44 @@
-use std::io::{Error, ErrorKind};
-
 use crate::hit::*;
-use crate::hittables::{Aabb, BvhNode, HittableList};
-use crate::misc::Pdf;
-use crate::primitive::triangle::*;
+use crate::hittables::{Aabb, BvhSettings, BvhStats, HittableList, TriangleObject, WideBvh};
+use crate::material::{Lambertian, MaterialContainer};
+use crate::misc::{HittablePdf, Interval, Pdf};
+use crate::primitive::mesh_data::{MeshData, MeshTriangle};
+use crate::primitive::obj_loader::{self, Face, ObjFile};
 use crate::ray::*;
-use crate::V3;
+use crate::texture::ConstantTexture;
+use crate::transform::MediumStack;
+use crate::{RayCone, RaySetting, V3};
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Error;
+use std::path::Path;
+use std::sync::Arc;
 
 /// Mesh of triangles, or polygon model
 #[derive(Clone)]
 pub struct Mesh {
-    triangles: BvhNode,
+    /// Buffers of verticles, shared by the triangles in the Bvh. Positions are taken out of them.
+    data: Arc<MeshData>,
+    /// Positions of verticles, shared by the triangles apart from the other buffers, so moving verticles doesn't copy them.
+    positions: Arc<[V3<f32>]>,
+    bvh: WideBvh,
     bounding_box: Aabb,
+    /// Settings the Bvh was built with.
+    settings: BvhSettings,
+    /// Cost of the Bvh by the surface area heuristic right after it was built.
+    built_cost: f32,
+    /// Sum of the areas of triangles up to each of them, to pick triangles by their area.
+    area_sums: Vec<f32>,
 }
 
 impl Hittable for Mesh {
     fn hit(&self, ray: &Ray) -> Option<Hit> {
-        self.triangles.hit(ray)
+        self.bvh.hit(ray)
+    }
+
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        self.bvh.occluded(ray, t_max)
     }
 
     fn bounding_box(&self) -> Aabb {
@@ -25,12 +47,48 @@ impl Hittable for Mesh {
 }
 
 impl Pdf for Mesh {
-    fn value(&self, _origin: V3<f32>, _direction: V3<f32>) -> f32 {
-        todo!()
+    fn value(&self, origin: V3<f32>, direction: V3<f32>) -> f32 {
+        let ray = Ray {
+            origin,
+            end: direction,
+            time: 1.0,
+            cone: RayCone::default(),
+            channel: None,
+            media: MediumStack::default(),
+            setting: &RaySetting {
+                depth: 32,
+                background_color: V3::default(),
+                ray_time: Interval {
+                    min: 0.001,
+                    max: f32::INFINITY,
+                },
+            },
+            materials: None,
+        };
+
+        // Triangle is picked with probability of its share of the whole area
+        match self.bvh.closest_hit(&ray) {
+            Some((leaf, _)) => {
+                let index = self.bvh.order()[leaf];
+                let previous = index.checked_sub(1).map_or(0.0, |i| self.area_sums[i]);
+                let share = (self.area_sums[index] - previous) / self.total_area();
+                self.triangle(index).value(origin, direction) * share
+            }
+            None => 0.0,
+        }
     }
 
-    fn generate(&self, _origin: V3<f32>) -> V3<f32> {
-        todo!()
+    fn generate(&self, origin: V3<f32>) -> V3<f32> {
+        use rand::Rng;
+
+        let last = self
+            .area_sums
+            .len()
+            .checked_sub(1)
+            .expect("Mesh is empty, thus cannot generate random direction toward it.");
+        let area = rand::thread_rng().gen::<f32>() * self.total_area();
+        let index = self.area_sums.partition_point(|&sum| sum <= area).min(last);
+        self.triangle(index).generate(origin)
     }
 }
 
@@ -40,55 +98,257 @@ impl Mesh {
     /// `file_path` - Path to the .obj file.
     ///
     /// `material` - Material of the model.
-    pub fn new(file_path: &str, material: usize) -> Result<Self, std::io::Error> {
-        use std::fs;
-        let file_to_parse = fs::read_to_string(file_path)?;
-
-        let mut triangles_list = HittableList::new();
-        let obj_file = wavefront_obj::obj::parse(file_to_parse).map_err(|_err| {
-            Error::new(
-                ErrorKind::InvalidInput,
-                "MESH CREATION FALIED. WE ARE DOOMED",
-            )
-        })?;
-
-        for object in obj_file.objects {
-            for primitive in object.geometry {
-                for shape in primitive.shapes.iter() {
-                    //.take(700) {
-                    if let wavefront_obj::obj::Primitive::Triangle(x0, x1, x2) = shape.primitive {
-                        triangles_list.add(Triangle::new(
-                            V3::new(
-                                object.vertices[x0.0].into(),
-                                object.vertices[x1.0].into(),
-                                object.vertices[x2.0].into(),
-                            ),
-                            match x0.2 {
-                                Some(_normal) => Some(V3::new(
-                                    object.normals[x0.2.unwrap()].into(),
-                                    object.normals[x1.2.unwrap()].into(),
-                                    object.normals[x2.2.unwrap()].into(),
-                                )),
-                                None => None,
-                            },
-                            material,
-                        ))
-                    };
-                }
+    pub fn new(file_path: &str, material: usize) -> Result<Self, Error> {
+        let obj = obj_loader::parse_obj(&fs::read_to_string(file_path)?)?;
+
+        let faces: Vec<(&Face, usize)> = obj
+            .groups
+            .iter()
+            .flat_map(|group| &group.faces)
+            .map(|face| (face, material))
+            .collect();
+
+        Mesh::from_data(Self::mesh_data(&obj, &faces))
+    }
+
+    /// Returns the model with its triangles organized by Bvh built with other settings.
+    ///
+    /// `settings` - How the Bvh is built.
+    pub fn with_bvh_settings(mut self, settings: BvhSettings) -> Self {
+        self.settings = settings;
+        self.build();
+        self
+    }
+
+    /// Returns the model with smooth normals, averaged from triangles around each verticle, like from [`compute_normals`](MeshData::compute_normals).
+    ///
+    /// Files without normals and smoothing groups are otherwise shaded flat.
+    pub fn with_smooth_normals(mut self) -> Self {
+        let mut data = MeshData {
+            positions: self.positions.to_vec(),
+            ..(*self.data).clone()
+        };
+        data.compute_normals();
+        data.positions = Vec::new();
+        self.data = Arc::new(data);
+        self.build();
+        self
+    }
+
+    /// Builds the Bvh over the current triangles.
+    fn build(&mut self) {
+        let triangles = self.triangles(0..self.data.triangle_count());
+        self.bvh = WideBvh::from_verticles(&triangles, self.settings);
+        self.bounding_box = self.bvh.bounding_box();
+        self.built_cost = self.bvh.stats(self.settings.traversal_cost).sah_cost;
+    }
+
+    /// Returns triangle referencing the current buffers.
+    ///
+    /// `index` - Index of the triangle.
+    fn triangle(&self, index: usize) -> MeshTriangle {
+        MeshTriangle::new(self.data.clone(), self.positions.clone(), index as u32)
+    }
+
+    /// Returns triangles referencing the current buffers, with their verticles.
+    ///
+    /// `indices` - Indices of the triangles.
+    fn triangles(&self, indices: impl Iterator<Item = usize>) -> Vec<TriangleObject> {
+        indices
+            .map(|index| {
+                let triangle = self.triangle(index);
+                let verticles = triangle.verticles();
+                (Arc::new(triangle) as Arc<dyn HittablePdf>, verticles)
+            })
+            .collect()
+    }
+
+    /// Returns current positions of verticles, in the order of [`positions`](MeshData::positions) the model was made from.
+    pub fn positions(&self) -> &[V3<f32>] {
+        &self.positions
+    }
+
+    /// Moves verticles to new positions and refits the Bvh to them, or builds it again, if refitting made it too slow to trace. Returns true if the Bvh was built again.
+    ///
+    /// Normals and texture coordinates of the verticles stay the same.
+    ///
+    /// `positions` - New positions of all verticles, in the order of [`positions`](MeshData::positions).
+    pub fn update_positions(&mut self, positions: &[V3<f32>]) -> bool {
+        assert_eq!(
+            positions.len(),
+            self.positions.len(),
+            "Mesh was updated with other number of verticles than it has."
+        );
+
+        // Triangles in the Bvh keep the old positions until they are replaced
+        self.positions = positions.into();
+        let leaves = self.triangles(self.bvh.order().iter().copied());
+        self.bvh.refit_verticles(&leaves);
+        self.sum_areas();
+
+        let cost = self.bvh.stats(self.settings.traversal_cost).sah_cost;
+        if self.settings.needs_rebuild(self.built_cost, cost) {
+            self.build();
+            true
+        } else {
+            self.bounding_box = self.bvh.bounding_box();
+            false
+        }
+    }
+
+    /// Sums up the areas of the current triangles.
+    fn sum_areas(&mut self) {
+        let mut sum = 0.0;
+        self.area_sums = (0..self.data.triangle_count())
+            .map(|triangle| {
+                let [a, b, c] = self.data.indices[triangle].map(|i| self.positions[i as usize]);
+                sum += (b - a).cross(c - a).length() / 2.0;
+                sum
+            })
+            .collect();
+    }
+
+    /// Returns area of all triangles.
+    fn total_area(&self) -> f32 {
+        self.area_sums.last().copied().unwrap_or(0.0)
+    }
+
+    /// Returns statistics of the Bvh organizing the triangles.
+    ///
+    /// `traversal_cost` - Cost of visiting a node, relative to the cost of intersecting one triangle.
+    pub fn bvh_stats(&self, traversal_cost: f32) -> BvhStats {
+        self.bvh.stats(traversal_cost)
+    }
+
+    /// Returns model made of indexed triangles, if its buffers match each other.
+    ///
+    /// `data` - Buffers of verticles and indices of the triangles.
+    pub fn from_data(mut data: MeshData) -> Result<Self, Error> {
+        data.validate()?;
+
+        let positions = std::mem::take(&mut data.positions).into();
+        let mut mesh = Mesh {
+            data: Arc::new(data),
+            positions,
+            bvh: WideBvh::new(&HittableList::new()),
+            bounding_box: Aabb::default(),
+            settings: BvhSettings::default(),
+            built_cost: 0.0,
+            area_sums: Vec::new(),
+        };
+        mesh.build();
+        mesh.sum_areas();
+        Ok(mesh)
+    }
+
+    /// Returns models of all objects and groups of the .obj file with their names, if they could be made.
+    ///
+    /// Materials from .mtl files of the model are added to the container, faces without any get a gray diffuse material.
+    ///
+    /// `file_path` - Path to the .obj file.
+    ///
+    /// `materials` - Container to which materials of the model are added.
+    pub fn load_obj(
+        file_path: &str,
+        materials: &mut MaterialContainer,
+    ) -> Result<Vec<(String, Mesh)>, Error> {
+        let obj = obj_loader::parse_obj(&fs::read_to_string(file_path)?)?;
+        let directory = Path::new(file_path).parent().unwrap_or(Path::new(""));
+
+        let mut library = HashMap::new();
+        for path in &obj.material_libraries {
+            let path = directory.join(path);
+            let mtl_directory = path.parent().unwrap_or(Path::new("")).to_owned();
+            for material in obj_loader::parse_mtl(&fs::read_to_string(&path)?)? {
+                library.insert(material.name.clone(), (material, mtl_directory.clone()));
             }
         }
 
-        let bounding_box = triangles_list.bounding_box();
+        // Only materials used by faces are added, each of them once
+        let mut default = None;
+        let mut default_material = |materials: &mut MaterialContainer| {
+            *default.get_or_insert_with(|| {
+                materials.add(Lambertian::new(Box::new(ConstantTexture {
+                    color: V3::new(0.8, 0.8, 0.8),
+                })))
+            })
+        };
+        let mut used = Vec::new();
+        for name in &obj.material_names {
+            used.push(match library.get(name) {
+                Some((material, directory)) => {
+                    obj_loader::add_material(material, directory, materials)?
+                }
+                None => default_material(materials),
+            });
+        }
 
-        Ok(Mesh {
-            triangles: BvhNode::new(&triangles_list),
-            bounding_box,
-        })
+        obj.groups
+            .iter()
+            .map(|group| {
+                let faces: Vec<(&Face, usize)> = group
+                    .faces
+                    .iter()
+                    .map(|face| match face.material {
+                        Some(index) => (face, used[index]),
+                        None => (face, default_material(materials)),
+                    })
+                    .collect();
+                let data = Self::mesh_data(&obj, &faces);
+                Ok((group.name.clone(), Mesh::from_data(data)?))
+            })
+            .collect()
     }
-}
 
-impl From<wavefront_obj::obj::Vertex> for V3<f32> {
-    fn from(vertex: wavefront_obj::obj::Vertex) -> V3<f32> {
-        V3::new(vertex.x as f32, vertex.y as f32, vertex.z as f32)
+    /// Returns indexed triangles of the faces, corners with the same position, texture coordinates and normal share a verticle.
+    ///
+    /// Normals and texture coordinates are used if all corners have them. Without normals, smooth normals are computed within each smoothing group, faces out of them keep their flat normals. Without normals and smoothing groups, triangles are shaded flat, unless the model is made [`with_smooth_normals`](Mesh::with_smooth_normals).
+    fn mesh_data(obj: &ObjFile, faces: &[(&Face, usize)]) -> MeshData {
+        let corners = || faces.iter().flat_map(|(face, _)| &face.corners);
+        let has_normals = corners().all(|corner| corner.normal.is_some());
+        let has_uvs = corners().all(|corner| corner.uv.is_some());
+        let is_smooth = !has_normals && faces.iter().any(|(face, _)| face.smoothing_group != 0);
+
+        let mut data = MeshData::default();
+        // Smoothing group of each verticle, flat faces get groups of their own after all numbers of the file
+        let mut groups = Vec::new();
+        let mut verticles = HashMap::new();
+        for (face_index, &(face, material)) in faces.iter().enumerate() {
+            let group = match face.smoothing_group {
+                _ if !is_smooth => 0,
+                0 => (1 << 32) + face_index as u64,
+                group => u64::from(group),
+            };
+            let points: Vec<V3<f32>> = face
+                .corners
+                .iter()
+                .map(|corner| obj.positions[corner.position])
+                .collect();
+
+            for triangle in obj_loader::triangulate(&points) {
+                let indices = triangle.map(|index| {
+                    let corner = face.corners[index];
+                    let uv = corner.uv.filter(|_| has_uvs);
+                    let normal = corner.normal.filter(|_| has_normals);
+                    *verticles
+                        .entry((corner.position, uv, normal, group))
+                        .or_insert_with(|| {
+                            data.positions.push(obj.positions[corner.position]);
+                            data.uvs.extend(uv.map(|uv| obj.uvs[uv]));
+                            data.normals
+                                .extend(normal.map(|normal| obj.normals[normal]));
+                            groups.push(group);
+                            data.positions.len() as u32 - 1
+                        })
+                });
+                data.indices.push(indices);
+                data.materials.push(material);
+            }
+        }
+
+        if is_smooth {
+            data.compute_welded_normals(&groups);
+        }
+        data
     }
 }
@@ -0,0 +1,575 @@
+use crate::hit::*;
+use crate::hittables::Aabb;
+use crate::misc::{Interval, Pdf};
+use crate::primitive::triangle::{self, TriangleShading};
+use crate::ray::*;
+use crate::transform::MediumStack;
+use crate::{RayCone, RaySetting, V3};
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+
+/// Indexed triangles, verticles are stored once in buffers shared by all triangles using them.
+#[derive(Clone, Default)]
+pub struct MeshData {
+    /// Positions of verticles.
+    pub positions: Vec<V3<f32>>,
+    /// Normals of corresponding verticles, empty if triangles are shaded flat.
+    pub normals: Vec<V3<f32>>,
+    /// Texture coordinates of corresponding verticles, empty if barycentric coordinates are used instead.
+    pub uvs: Vec<(f32, f32)>,
+    /// Tangents of corresponding verticles along increasing `u`, with the sign telling if `normal × tangent` goes along increasing `v`. Empty if tangents are computed from texture coordinates of each triangle.
+    pub tangents: Vec<(V3<f32>, f32)>,
+    /// Indices of verticles of each triangle, CCW.
+    pub indices: Vec<[u32; 3]>,
+    /// Material of each triangle.
+    pub materials: Vec<usize>,
+}
+
+impl MeshData {
+    /// Returns number of triangles.
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Returns positions of verticles of the triangle, CCW.
+    ///
+    /// `triangle` - Index of the triangle.
+    pub fn verticles(&self, triangle: usize) -> [V3<f32>; 3] {
+        self.indices[triangle].map(|index| self.positions[index as usize])
+    }
+
+    /// Replaces normals with smooth ones, averaged from triangles around each verticle and weighted by their areas.
+    ///
+    /// Verticles at the same position share the normal, so seams of texture coordinates aren't visible.
+    pub fn compute_normals(&mut self) {
+        self.compute_welded_normals(&vec![0; self.positions.len()]);
+    }
+
+    /// Replaces normals with smooth ones, like [`compute_normals`](MeshData::compute_normals), but verticles at the same position share the normal only if they are in the same smoothing group.
+    ///
+    /// Verticle whose triangles cancel out, or are degenerate, gets the normal of its first triangle which isn't.
+    ///
+    /// `groups` - Smoothing group of each verticle, verticles used by a single flat triangle need a group of their own.
+    pub(crate) fn compute_welded_normals(&mut self, groups: &[u64]) {
+        let key = |index: usize| {
+            let point = self.positions[index];
+            (
+                [point.x.to_bits(), point.y.to_bits(), point.z.to_bits()],
+                groups[index],
+            )
+        };
+
+        let mut sums: HashMap<([u32; 3], u64), V3<f32>> = HashMap::new();
+        let mut fallbacks = vec![V3::default(); self.positions.len()];
+        for triangle in 0..self.triangle_count() {
+            let [a, b, c] = self.verticles(triangle);
+            // Length of the cross product is twice the area of the triangle
+            let normal = (b - a).cross(c - a);
+            for index in self.indices[triangle] {
+                let index = index as usize;
+                let sum = sums.entry(key(index)).or_default();
+                *sum = *sum + normal;
+                if fallbacks[index] == V3::default() && normal.length() > 0.0 {
+                    fallbacks[index] = normal.norm();
+                }
+            }
+        }
+
+        self.normals = (0..self.positions.len())
+            .map(|index| match sums.get(&key(index)) {
+                Some(&sum) if sum.length() > 0.0 => sum.norm(),
+                _ => fallbacks[index],
+            })
+            .collect();
+    }
+
+    /// Checks that all buffers match the positions and all indices point into them.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        if !self.normals.is_empty() && self.normals.len() != self.positions.len() {
+            return Err(invalid_data(
+                "Mesh has other number of normals than verticles.",
+            ));
+        }
+        if !self.uvs.is_empty() && self.uvs.len() != self.positions.len() {
+            return Err(invalid_data(
+                "Mesh has other number of texture coordinates than verticles.",
+            ));
+        }
+        if !self.tangents.is_empty()
+            && (self.tangents.len() != self.positions.len() || self.normals.is_empty())
+        {
+            return Err(invalid_data(
+                "Mesh has other number of tangents than verticles, or tangents without normals.",
+            ));
+        }
+        if self.materials.len() != self.indices.len() {
+            return Err(invalid_data(
+                "Mesh has other number of materials than triangles.",
+            ));
+        }
+        if !self
+            .indices
+            .iter()
+            .flatten()
+            .all(|&index| (index as usize) < self.positions.len())
+        {
+            return Err(invalid_data(
+                "Mesh has index of a verticle it doesn't have.",
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Triangle of a mesh, referencing its verticles in the shared buffers.
+#[derive(Clone)]
+pub(crate) struct MeshTriangle {
+    /// Buffers of the mesh, except positions.
+    data: Arc<MeshData>,
+    positions: Arc<[V3<f32>]>,
+    index: u32,
+}
+
+impl MeshTriangle {
+    /// Returns triangle of the mesh.
+    ///
+    /// `data` - Buffers of the mesh, its positions aren't used.
+    ///
+    /// `positions` - Positions of verticles of the mesh.
+    ///
+    /// `index` - Index of the triangle in the mesh.
+    pub(crate) fn new(data: Arc<MeshData>, positions: Arc<[V3<f32>]>, index: u32) -> Self {
+        MeshTriangle {
+            data,
+            positions,
+            index,
+        }
+    }
+
+    /// Returns positions of verticles of the triangle, CCW.
+    pub(crate) fn verticles(&self) -> [V3<f32>; 3] {
+        self.data.indices[self.index as usize].map(|index| self.positions[index as usize])
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        let verticles = self.verticles();
+        let coordinates = triangle::intersect(&verticles, ray, ray.setting.ray_time)?;
+
+        let indices = self.data.indices[self.index as usize].map(|index| index as usize);
+        let normals =
+            (!self.data.normals.is_empty()).then(|| indices.map(|i| self.data.normals[i]));
+        let uvs = (!self.data.uvs.is_empty()).then(|| indices.map(|i| self.data.uvs[i]));
+
+        // Tangents from the buffers give directions, lengths still follow texture coordinates
+        let mut tangents = triangle::tangents(&verticles, uvs);
+        if let (Some(normals), false) = (normals, self.data.tangents.is_empty()) {
+            let (_, u, v, w) = coordinates;
+            let [a, b, c] = indices.map(|i| self.data.tangents[i]);
+            let tangent = a.0 * w + b.0 * u + c.0 * v;
+            let normal = normals[0] * w + normals[1] * u + normals[2] * v;
+            tangents = (
+                tangent.norm() * tangents.0.length(),
+                normal.cross(tangent).norm() * a.1 * tangents.1.length(),
+            );
+        }
+
+        TriangleShading {
+            verticles,
+            normals,
+            uvs,
+            tangents,
+            material: self.data.materials[self.index as usize],
+        }
+        .shade(ray, coordinates)
+    }
+
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        // Cutout mask needs the texture coordinates of the hit
+        if ray
+            .opacity(self.data.materials[self.index as usize])
+            .is_some()
+        {
+            return hit_before(self, ray, t_max).is_some();
+        }
+        let interval = Interval::new(ray.setting.ray_time.min, t_max);
+        triangle::intersect(&self.verticles(), ray, interval).is_some()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [a, b, c] = self.verticles();
+        let (min, max) = (a.min(b).min(c), a.max(b).max(c));
+        Aabb {
+            x: Interval::new(min.x, max.x),
+            y: Interval::new(min.y, max.y),
+            z: Interval::new(min.z, max.z),
+        }
+    }
+}
+
+impl Pdf for MeshTriangle {
+    fn value(&self, origin: V3<f32>, direction: V3<f32>) -> f32 {
+        let ray = Ray {
+            origin,
+            end: direction,
+            time: 1.0,
+            cone: RayCone::default(),
+            channel: None,
+            media: MediumStack::default(),
+            setting: &RaySetting {
+                depth: 32,
+                background_color: V3::default(),
+                ray_time: Interval {
+                    min: 0.001,
+                    max: f32::INFINITY,
+                },
+            },
+            materials: None,
+        };
+
+        let verticles = self.verticles();
+        match triangle::intersect(&verticles, &ray, ray.setting.ray_time) {
+            Some((t, ..)) => {
+                let [a, b, c] = verticles;
+                let normal = (b - a).cross(c - a);
+                let area = normal.length() / 2.0;
+                let distance_squared = t * t * direction.length().powi(2);
+                let cosine = (direction.dot(normal) / (direction.length() * normal.length())).abs();
+                distance_squared / (cosine * area)
+            }
+            None => 0.0,
+        }
+    }
+
+    fn generate(&self, origin: V3<f32>) -> V3<f32> {
+        // Square root of the first number spreads points uniformly over the area of the triangle
+        use rand::Rng;
+        let mut rng = rand::thread_rng();
+        let s = rng.gen::<f32>().sqrt();
+        let r = rng.gen::<f32>();
+
+        let [a, b, c] = self.verticles();
+        let random_point = a * (1.0 - s) + b * (s * (1.0 - r)) + c * (s * r);
+        random_point - origin
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittables::{BvhNode, HittableList};
+    use crate::primitive::{Mesh, Triangle};
+    use rand::{Rng, SeedableRng};
+
+    #[test]
+    fn compute_normals_test() {
+        // Octahedron, whose last verticle repeats the first one like on a seam of texture coordinates
+        let mut data = MeshData {
+            positions: vec![
+                V3::new(1.0, 0.0, 0.0),
+                V3::new(0.0, 1.0, 0.0),
+                V3::new(-1.0, 0.0, 0.0),
+                V3::new(0.0, -1.0, 0.0),
+                V3::new(0.0, 0.0, 1.0),
+                V3::new(0.0, 0.0, -1.0),
+                V3::new(1.0, 0.0, 0.0),
+            ],
+            indices: vec![
+                [0, 1, 4],
+                [1, 2, 4],
+                [2, 3, 4],
+                [3, 6, 4],
+                [1, 0, 5],
+                [2, 1, 5],
+                [3, 2, 5],
+                [6, 3, 5],
+            ],
+            materials: vec![0; 8],
+            ..MeshData::default()
+        };
+        data.validate().unwrap();
+        data.compute_normals();
+
+        // Normals of a regular solid point away from its center, verticles on the seam share them
+        for (position, normal) in data.positions.iter().zip(&data.normals) {
+            assert!((*position - *normal).length() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn welded_normals_test() {
+        // Roof of two triangles sharing the ridge, and a sheet folded back onto itself
+        let mut data = MeshData {
+            positions: vec![
+                V3::new(0.0, 0.0, 0.0),
+                V3::new(0.0, 0.0, 1.0),
+                V3::new(1.0, 1.0, 0.0),
+                V3::new(1.0, 1.0, 1.0),
+                V3::new(2.0, 0.0, 0.0),
+                V3::new(2.0, 0.0, 1.0),
+                V3::new(1.0, 1.0, 0.0),
+                V3::new(1.0, 1.0, 1.0),
+            ],
+            indices: vec![[0, 1, 3], [0, 3, 2], [6, 7, 4], [7, 5, 4]],
+            materials: vec![0; 4],
+            ..MeshData::default()
+        };
+
+        // Verticles on the ridge in the same group share the averaged normal
+        data.compute_welded_normals(&[1; 8]);
+        assert!((data.normals[2] - V3::new(0.0, 1.0, 0.0)).length() < 1e-6);
+        assert_eq!(data.normals[2], data.normals[6]);
+
+        // Verticles in other groups keep normals of their own triangles
+        data.compute_welded_normals(&[1, 1, 1, 1, 2, 2, 2, 2]);
+        let sqrt_half = 0.5f32.sqrt();
+        assert!((data.normals[2] - V3::new(-sqrt_half, sqrt_half, 0.0)).length() < 1e-6);
+        assert!((data.normals[6] - V3::new(sqrt_half, sqrt_half, 0.0)).length() < 1e-6);
+
+        // Normals of the sheet cancel out, so its verticles fall back to their first triangle
+        let mut sheet = MeshData {
+            positions: vec![
+                V3::new(0.0, 0.0, 0.0),
+                V3::new(1.0, 0.0, 0.0),
+                V3::new(0.0, 1.0, 0.0),
+            ],
+            indices: vec![[0, 1, 2], [0, 2, 1]],
+            materials: vec![0; 2],
+            ..MeshData::default()
+        };
+        sheet.compute_normals();
+        assert!(sheet
+            .normals
+            .iter()
+            .all(|&normal| normal == V3::new(0.0, 0.0, 1.0)));
+    }
+
+    #[test]
+    fn smooth_normals_test() {
+        // Roof of two slopes without normals, sharing the verticles of the ridge
+        let data = MeshData {
+            positions: vec![
+                V3::new(0.0, 0.0, 0.0),
+                V3::new(0.0, 0.0, 1.0),
+                V3::new(1.0, 1.0, 0.0),
+                V3::new(1.0, 1.0, 1.0),
+                V3::new(2.0, 0.0, 0.0),
+                V3::new(2.0, 0.0, 1.0),
+            ],
+            indices: vec![[0, 1, 3], [0, 3, 2], [2, 3, 5], [2, 5, 4]],
+            materials: vec![0; 4],
+            ..MeshData::default()
+        };
+        let flat = Mesh::from_data(data).unwrap();
+        let smooth = flat.clone().with_smooth_normals();
+
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+        let ray = Ray::for_test(V3::new(0.999, 5.0, 0.5), V3::new(0.0, -1.0, 0.0), &setting);
+
+        // Next to the ridge the flat slope tilts, the smooth one is almost level
+        let sqrt_half = 0.5f32.sqrt();
+        let normal = flat.hit(&ray).unwrap().normal;
+        assert!((normal - V3::new(-sqrt_half, sqrt_half, 0.0)).length() < 1e-6);
+        assert!(smooth.hit(&ray).unwrap().normal.norm().y > 0.99);
+    }
+
+    #[test]
+    fn invalid_data_test() {
+        let data = MeshData {
+            positions: vec![V3::default(); 3],
+            indices: vec![[0, 1, 3]],
+            materials: vec![0],
+            ..MeshData::default()
+        };
+        assert!(Mesh::from_data(data.clone()).is_err());
+
+        let data = MeshData {
+            indices: vec![[0, 1, 2]],
+            normals: vec![V3::default()],
+            ..data
+        };
+        assert!(Mesh::from_data(data).is_err());
+    }
+
+    #[test]
+    fn pdf_test() {
+        // Small triangle far away covers solid angle of its area divided by the squared distance
+        let data = MeshData {
+            positions: vec![
+                V3::new(0.0, 0.0, 100.0),
+                V3::new(1.0, 0.0, 100.0),
+                V3::new(0.0, 1.0, 100.0),
+            ],
+            indices: vec![[0, 1, 2]],
+            materials: vec![0],
+            ..MeshData::default()
+        };
+        let mesh = Mesh::from_data(data).unwrap();
+        let origin = V3::default();
+
+        // Mean of inverse probabilities of the generated directions estimates the solid angle
+        let samples = 1000;
+        let solid_angle = (0..samples)
+            .map(|_| 1.0 / mesh.value(origin, mesh.generate(origin)))
+            .sum::<f32>()
+            / samples as f32;
+        assert!((solid_angle - 0.5 / 10000.0).abs() < 1e-6);
+        assert_eq!(mesh.value(origin, V3::new(0.0, 0.0, -1.0)), 0.0);
+    }
+
+    #[test]
+    fn area_pdf_test() {
+        // Triangles of areas 0.5 and 4.5 side by side far away
+        let data = MeshData {
+            positions: vec![
+                V3::new(0.0, 0.0, 100.0),
+                V3::new(1.0, 0.0, 100.0),
+                V3::new(0.0, 1.0, 100.0),
+                V3::new(2.0, 0.0, 100.0),
+                V3::new(5.0, 0.0, 100.0),
+                V3::new(2.0, 3.0, 100.0),
+            ],
+            indices: vec![[0, 1, 2], [3, 4, 5]],
+            materials: vec![0; 2],
+            ..MeshData::default()
+        };
+        let mesh = Mesh::from_data(data).unwrap();
+        let origin = V3::default();
+
+        // Triangles are picked by their areas
+        let samples = 10000;
+        let big = (0..samples)
+            .filter(|_| {
+                let direction = mesh.generate(origin);
+                direction.x / direction.z * 100.0 > 1.5
+            })
+            .count();
+        assert!((big as f32 / samples as f32 - 0.9).abs() < 0.02);
+
+        // Probability of a direction is its squared distance over the cosine and the whole area
+        let direction = V3::new(3.0f32, 1.0, 100.0);
+        let distance_squared = direction.length().powi(2);
+        let cosine = 100.0 / direction.length();
+        let expected = distance_squared / (cosine * 5.0);
+        assert!((mesh.value(origin, direction) / expected - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn update_positions_test() {
+        let data = MeshData {
+            positions: vec![
+                V3::new(0.0, 0.0, 10.0),
+                V3::new(1.0, 0.0, 10.0),
+                V3::new(0.0, 1.0, 10.0),
+                V3::new(1.0, 1.0, 10.0),
+            ],
+            normals: vec![V3::new(0.0, 0.0, -1.0); 4],
+            indices: vec![[0, 2, 1], [1, 2, 3]],
+            materials: vec![0, 1],
+            ..MeshData::default()
+        };
+        let mut mesh = Mesh::from_data(data.clone()).unwrap();
+
+        // Square grows twice and moves farther, it stays the same as a model made with the new positions
+        let positions: Vec<V3<f32>> = data
+            .positions
+            .iter()
+            .map(|&position| position * 2.0)
+            .collect();
+        mesh.update_positions(&positions);
+        assert_eq!(mesh.positions(), &positions[..]);
+        let expected = Mesh::from_data(MeshData { positions, ..data }).unwrap();
+
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+        for direction in [V3::new(0.03, 0.03, 1.0), V3::new(0.08, 0.06, 1.0)] {
+            let ray = Ray::for_test(V3::default(), direction, &setting);
+            let hit = |mesh: &Mesh| mesh.hit(&ray).map(|hit| (hit.t, hit.material));
+            assert_eq!(hit(&mesh), hit(&expected));
+            assert!(hit(&mesh).is_some());
+            assert_eq!(
+                mesh.value(V3::default(), direction),
+                expected.value(V3::default(), direction)
+            );
+        }
+    }
+
+    #[test]
+    fn same_hits_as_triangles_test() {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(5);
+
+        // Bumpy grid with normals and texture coordinates at its verticles
+        let size = 12;
+        let mut data = MeshData::default();
+        for i in 0..=size {
+            for j in 0..=size {
+                let (x, z) = (i as f32, j as f32);
+                data.positions.push(V3::new(x, rng.gen_range(-0.5..0.5), z));
+                data.uvs.push((x / size as f32, z / size as f32));
+            }
+        }
+        for i in 0..size {
+            for j in 0..size {
+                let corner = i * (size + 1) + j;
+                let next = corner + size + 1;
+                data.indices.push([corner, corner + 1, next]);
+                data.indices.push([next, corner + 1, next + 1]);
+            }
+        }
+        data.materials = (0..data.indices.len()).collect();
+        data.compute_normals();
+
+        let mut hlist = HittableList::new();
+        for (triangle, &material) in data.indices.iter().zip(&data.materials) {
+            let [a, b, c] = triangle.map(|index| index as usize);
+            let verticles = V3::new(data.positions[a], data.positions[b], data.positions[c]);
+            let normals = V3::new(data.normals[a], data.normals[b], data.normals[c]);
+            hlist.add(
+                Triangle::new(verticles, Some(normals), material).with_uvs(V3::new(
+                    data.uvs[a],
+                    data.uvs[b],
+                    data.uvs[c],
+                )),
+            );
+        }
+        let tree = BvhNode::new(&hlist);
+        let mesh = Mesh::from_data(data).unwrap();
+
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+        for _ in 0..500 {
+            let origin = V3::new(
+                rng.gen_range(-2.0..14.0),
+                rng.gen_range(1.0..5.0),
+                rng.gen_range(-2.0..14.0),
+            );
+            let target = V3::new(rng.gen_range(0.0..12.0), 0.0, rng.gen_range(0.0..12.0));
+            let ray = Ray::for_test(origin, target - origin, &setting);
+
+            let expected = tree
+                .hit(&ray)
+                .map(|hit| (hit.t, hit.normal, hit.material, hit.u, hit.v));
+            let actual = mesh
+                .hit(&ray)
+                .map(|hit| (hit.t, hit.normal, hit.material, hit.u, hit.v));
+            assert_eq!(expected, actual);
+        }
+    }
+}
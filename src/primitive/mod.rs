@@ -2,7 +2,9 @@
 
 mod hitbox;
 mod mesh;
+mod mesh_data;
 mod moving_sphere;
+mod obj_loader;
 mod quad;
 mod rectangle;
 mod sphere;
@@ -10,6 +12,7 @@ mod triangle;
 
 pub use hitbox::HitBox;
 pub use mesh::Mesh;
+pub use mesh_data::MeshData;
 pub use moving_sphere::MovingSphere;
 pub use quad::Quad;
 pub use rectangle::XYrect;
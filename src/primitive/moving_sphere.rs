@@ -2,6 +2,7 @@ use crate::hit::*;
 use crate::hittables::Aabb;
 use crate::misc::Interval;
 use crate::misc::Pdf;
+use crate::primitive::sphere::{sphere_hit, sphere_root};
 use crate::ray::*;
 use crate::V3;
 /// Primitive representing sphere that moves during some time interval.
@@ -42,39 +43,12 @@ impl MovingSphere {
 
 impl Hittable for MovingSphere {
     fn hit(&self, ray: &Ray) -> Option<Hit> {
-        let oc = ray.origin - self.center(ray.time);
-        let a = ray.end.dot(ray.end);
-        let b = oc.dot(ray.end);
-        let c = oc.dot(oc) - self.radius * self.radius;
-        let discriminant = b * b - a * c;
-
-        if discriminant > 0.0 {
-            let time;
-            let t1 = (-b - (b * b - a * c).sqrt()) / a;
-            let t2 = (-b + (b * b - a * c).sqrt()) / a;
-
-            if ray.setting.ray_time.surrounds(t1) {
-                time = t1;
-            } else if ray.setting.ray_time.surrounds(t2) {
-                time = t2;
-            } else {
-                return None;
-            }
-
-            let point = ray.point_at_param(time);
-            let normal = (point - self.center(ray.time)) / self.radius;
-
-            let sphere_point = (point - self.center(ray.time)).norm();
-
-            let phi = (sphere_point.z).atan2(sphere_point.x);
-            let theta = (sphere_point.y).asin();
-            let u = 1.0 - (phi + std::f32::consts::PI) / (2.0 * std::f32::consts::PI);
-            let v = (theta + std::f32::consts::PI / 2.0) / std::f32::consts::PI;
+        sphere_hit(ray, self.center(ray.time), self.radius, self.material)
+    }
 
-            Some(Hit::new(ray, normal, time, point, self.material, u, v))
-        } else {
-            None
-        }
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        let interval = Interval::new(ray.setting.ray_time.min, t_max);
+        sphere_root(ray, self.center(ray.time), self.radius, interval).is_some()
     }
 
     fn bounding_box(&self) -> Aabb {
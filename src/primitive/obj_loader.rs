@@ -0,0 +1,544 @@
+//! Parsing of Wavefront .obj models and .mtl libraries of their materials.
+
+use crate::material::{Dielectric, Lambertian, LightSource, MaterialContainer, Metalic};
+use crate::texture::{ConstantTexture, ImageTexture, Texture, TextureFilter, WrapMode};
+use crate::V3;
+
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+/// Corner of a face, by indices into the arrays of the file.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Corner {
+    pub position: usize,
+    pub uv: Option<usize>,
+    pub normal: Option<usize>,
+}
+
+/// Polygon of the model.
+pub(crate) struct Face {
+    pub corners: Vec<Corner>,
+    /// Index of the material among the names of materials used by the file, None before the first `usemtl`.
+    pub material: Option<usize>,
+    /// Smoothing group of the face, normals are interpolated across edges of faces in the same group. Zero for flat faces.
+    pub smoothing_group: u32,
+}
+
+/// Faces of one object or group of the model.
+pub(crate) struct Group {
+    pub name: String,
+    pub faces: Vec<Face>,
+}
+
+/// Content of the .obj file, relative indices of corners are already resolved.
+#[derive(Default)]
+pub(crate) struct ObjFile {
+    pub positions: Vec<V3<f32>>,
+    pub uvs: Vec<(f32, f32)>,
+    pub normals: Vec<V3<f32>>,
+    pub groups: Vec<Group>,
+    /// Names of materials used by faces.
+    pub material_names: Vec<String>,
+    /// Paths of .mtl files, relative to the .obj file.
+    pub material_libraries: Vec<String>,
+}
+
+/// Material read from the .mtl file.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct MtlMaterial {
+    pub name: String,
+    /// Diffuse color, `Kd`.
+    pub diffuse: V3<f32>,
+    /// Specular color, `Ks`.
+    pub specular: V3<f32>,
+    /// Emitted light, `Ke`.
+    pub emission: V3<f32>,
+    /// Phong exponent of the specular highlight, `Ns`.
+    pub shininess: f32,
+    /// Index of refraction, `Ni`.
+    pub refractive_index: f32,
+    /// Opacity, `d`, or one minus `Tr`.
+    pub dissolve: f32,
+    /// Illumination model, `illum`.
+    pub illumination: u32,
+    /// Path of the diffuse texture, `map_Kd`, relative to the .mtl file.
+    pub diffuse_map: Option<String>,
+    /// Path of the emission texture, `map_Ke`, relative to the .mtl file.
+    pub emission_map: Option<String>,
+}
+
+impl MtlMaterial {
+    fn new(name: &str) -> Self {
+        MtlMaterial {
+            name: name.to_owned(),
+            diffuse: V3::new(0.8, 0.8, 0.8),
+            specular: V3::default(),
+            emission: V3::default(),
+            shininess: 0.0,
+            refractive_index: 1.0,
+            dissolve: 1.0,
+            illumination: 2,
+            diffuse_map: None,
+            emission_map: None,
+        }
+    }
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Returns the text of the line after the keyword, without comment.
+fn rest<'a>(line: &'a str, keyword: &str) -> &'a str {
+    line[keyword.len()..].trim()
+}
+
+/// Parses next `count` numbers, missing ones are replaced by `default`. Returns None if any of them isn't a finite number.
+fn numbers<'a>(
+    tokens: &mut impl Iterator<Item = &'a str>,
+    count: usize,
+    default: f32,
+) -> Option<Vec<f32>> {
+    (0..count)
+        .map(|_| {
+            tokens.next().map_or(Some(default), |token| {
+                token.parse().ok().filter(|value: &f32| value.is_finite())
+            })
+        })
+        .collect()
+}
+
+/// Returns index into the array of `count` items, from one based index, or negative index counted from the end.
+fn resolve(index: &str, count: usize) -> Option<usize> {
+    let index: i64 = index.parse().ok()?;
+    let resolved = if index < 0 {
+        count as i64 + index
+    } else {
+        index - 1
+    };
+    (0..count as i64)
+        .contains(&resolved)
+        .then_some(resolved as usize)
+}
+
+/// Returns index of the item with given name, adds it if there is none.
+fn index_of(names: &mut HashMap<String, usize>, name: &str, add: impl FnOnce() -> usize) -> usize {
+    match names.get(name) {
+        Some(&index) => index,
+        None => {
+            let index = add();
+            names.insert(name.to_owned(), index);
+            index
+        }
+    }
+}
+
+/// Parses content of the .obj file. Faces are grouped by the `g` statements, or by objects if there are none, faces with the same group name are merged.
+///
+/// `text` - Content of the file.
+pub(crate) fn parse_obj(text: &str) -> Result<ObjFile, Error> {
+    let mut file = ObjFile::default();
+    let mut groups = HashMap::new();
+    let mut materials = HashMap::new();
+    let mut object_name = String::new();
+    let mut group = None;
+    let mut material = None;
+    let mut smoothing_group = 0;
+
+    for (line_number, line) in text.lines().enumerate() {
+        let error = |message: &str| {
+            invalid_data(&format!(
+                "Line {} of the .obj file: {}",
+                line_number + 1,
+                message
+            ))
+        };
+
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+
+        match keyword {
+            "v" => {
+                let v = numbers(&mut tokens, 3, 0.0).ok_or_else(|| error("Invalid position"))?;
+                file.positions.push(V3::new(v[0], v[1], v[2]));
+            }
+            "vt" => {
+                let uv = numbers(&mut tokens, 2, 0.0)
+                    .ok_or_else(|| error("Invalid texture coordinates"))?;
+                file.uvs.push((uv[0], uv[1]));
+            }
+            "vn" => {
+                let n = numbers(&mut tokens, 3, 0.0).ok_or_else(|| error("Invalid normal"))?;
+                file.normals.push(V3::new(n[0], n[1], n[2]));
+            }
+            "f" => {
+                let corners = tokens
+                    .map(|corner| {
+                        let mut indices = corner.split('/');
+                        let position = resolve(indices.next()?, file.positions.len())?;
+                        let mut optional = |count| match indices.next() {
+                            None | Some("") => Some(None),
+                            Some(index) => resolve(index, count).map(Some),
+                        };
+                        Some(Corner {
+                            position,
+                            uv: optional(file.uvs.len())?,
+                            normal: optional(file.normals.len())?,
+                        })
+                    })
+                    .collect::<Option<Vec<Corner>>>()
+                    .ok_or_else(|| error("Invalid index of a corner"))?;
+
+                if corners.len() < 3 {
+                    return Err(error("Face has less than 3 corners"));
+                }
+
+                // Faces before any group statement belong to the group named after the object
+                let index = match group {
+                    Some(index) => index,
+                    None => index_of(&mut groups, &object_name, || {
+                        file.groups.push(Group {
+                            name: object_name.clone(),
+                            faces: Vec::new(),
+                        });
+                        file.groups.len() - 1
+                    }),
+                };
+                group = Some(index);
+                file.groups[index].faces.push(Face {
+                    corners,
+                    material,
+                    smoothing_group,
+                });
+            }
+            "o" => {
+                object_name = rest(line, keyword).to_owned();
+                group = None;
+            }
+            "g" => {
+                let name = match rest(line, keyword) {
+                    "" => object_name.clone(),
+                    name => name.to_owned(),
+                };
+                group = Some(index_of(&mut groups, &name, || {
+                    file.groups.push(Group {
+                        name: name.clone(),
+                        faces: Vec::new(),
+                    });
+                    file.groups.len() - 1
+                }));
+            }
+            "usemtl" => {
+                let name = rest(line, keyword);
+                material = Some(index_of(&mut materials, name, || {
+                    file.material_names.push(name.to_owned());
+                    file.material_names.len() - 1
+                }));
+            }
+            "mtllib" => file.material_libraries.push(rest(line, keyword).to_owned()),
+            // Smoothing group without a number, like `s on`, is the first one
+            "s" => {
+                smoothing_group = match rest(line, keyword) {
+                    "" | "off" => 0,
+                    group => group.parse().unwrap_or(1),
+                }
+            }
+            // Lines, points and curves are ignored
+            _ => {}
+        }
+    }
+
+    // Groups, which were only named, have no faces to make a mesh from
+    file.groups.retain(|group| !group.faces.is_empty());
+    Ok(file)
+}
+
+/// Parses content of the .mtl file.
+///
+/// `text` - Content of the file.
+pub(crate) fn parse_mtl(text: &str) -> Result<Vec<MtlMaterial>, Error> {
+    let mut materials: Vec<MtlMaterial> = Vec::new();
+
+    for (line_number, line) in text.lines().enumerate() {
+        let error = |message: &str| {
+            invalid_data(&format!(
+                "Line {} of the .mtl file: {}",
+                line_number + 1,
+                message
+            ))
+        };
+
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(keyword) => keyword,
+            None => continue,
+        };
+
+        if keyword == "newmtl" {
+            materials.push(MtlMaterial::new(rest(line, keyword)));
+            continue;
+        }
+        let material = materials
+            .last_mut()
+            .ok_or_else(|| error("Statement before the first newmtl"))?;
+
+        let mut color = || {
+            numbers(&mut tokens, 3, f32::NAN)
+                .map(|c| {
+                    // Single value sets all channels
+                    let g = if c[1].is_nan() { c[0] } else { c[1] };
+                    let b = if c[2].is_nan() { c[0] } else { c[2] };
+                    V3::new(c[0], g, b)
+                })
+                .filter(|color| !color.x.is_nan())
+                .ok_or_else(|| error("Invalid color"))
+        };
+
+        match keyword {
+            "Kd" => material.diffuse = color()?,
+            "Ks" => material.specular = color()?,
+            "Ke" => material.emission = color()?,
+            "Ns" | "Ni" | "d" | "Tr" | "illum" => {
+                let value: f32 = tokens
+                    .next()
+                    .and_then(|token| token.parse().ok())
+                    .filter(|value: &f32| value.is_finite())
+                    .ok_or_else(|| error("Invalid number"))?;
+                match keyword {
+                    "Ns" => material.shininess = value,
+                    "Ni" => material.refractive_index = value,
+                    "d" => material.dissolve = value,
+                    "Tr" => material.dissolve = 1.0 - value,
+                    _ => material.illumination = value as u32,
+                }
+            }
+            // Options of the texture are skipped, the path is the last token
+            "map_Kd" => material.diffuse_map = line.split_whitespace().last().map(str::to_owned),
+            "map_Ke" => material.emission_map = line.split_whitespace().last().map(str::to_owned),
+            _ => {}
+        }
+    }
+
+    Ok(materials)
+}
+
+/// Adds material read from the .mtl file to the container, returns its index.
+///
+/// Emitting materials become light sources, transparent or refracting ones dielectrics, reflecting ones metals, and all others are diffuse.
+///
+/// `material` - Material read from the .mtl file.
+///
+/// `directory` - Folder of the .mtl file, paths of textures are relative to it.
+///
+/// `materials` - Container to which the material is added.
+pub(crate) fn add_material(
+    material: &MtlMaterial,
+    directory: &Path,
+    materials: &mut MaterialContainer,
+) -> Result<usize, Error> {
+    let texture =
+        |map: &Option<String>, color: V3<f32>| -> Result<Box<dyn Texture + Sync + Send>, Error> {
+            Ok(match map {
+                Some(map) => Box::new(
+                    ImageTexture::new(&directory.join(map).to_string_lossy())?
+                        .with_filter(TextureFilter::Trilinear)
+                        .with_wrap(WrapMode::Repeat),
+                ),
+                None => Box::new(ConstantTexture { color }),
+            })
+        };
+
+    let index = if material.emission != V3::default() || material.emission_map.is_some() {
+        let emission = match material.emission_map {
+            Some(_) if material.emission == V3::default() => V3::new(1.0, 1.0, 1.0),
+            _ => material.emission,
+        };
+        materials.add(LightSource::new(texture(&material.emission_map, emission)?))
+    } else if material.dissolve < 1.0 || matches!(material.illumination, 4 | 6 | 7 | 9) {
+        let refractive_index = if material.refractive_index > 1.0 {
+            material.refractive_index
+        } else {
+            1.5
+        };
+        materials.add(Dielectric::new(refractive_index))
+    } else if matches!(material.illumination, 3 | 5 | 8) {
+        let albedo = if material.specular == V3::default() {
+            material.diffuse
+        } else {
+            material.specular
+        };
+        // Sharper highlight of the Phong model means less blurred reflections
+        let fuzz = (2.0 / (material.shininess.max(0.0) + 2.0)).sqrt();
+        materials.add(Metalic::new(albedo, fuzz))
+    } else {
+        materials.add(Lambertian::new(texture(
+            &material.diffuse_map,
+            material.diffuse,
+        )?))
+    };
+
+    Ok(index)
+}
+
+/// Returns triangles of the polygon as indices of its corners, by clipping ears, so concave polygons are triangulated too.
+///
+/// `points` - Corners of the polygon in order, which triangles keep.
+pub(crate) fn triangulate(points: &[V3<f32>]) -> Vec<[usize; 3]> {
+    let count = points.len();
+    if count <= 3 {
+        return vec![[0, 1, 2]];
+    }
+
+    // Normal by the Newell's method, which works for concave and slightly bent polygons
+    let mut normal = V3::<f32>::default();
+    for (index, &a) in points.iter().enumerate() {
+        let b = points[(index + 1) % count];
+        normal.x += (a.y - b.y) * (a.z + b.z);
+        normal.y += (a.z - b.z) * (a.x + b.x);
+        normal.z += (a.x - b.x) * (a.y + b.y);
+    }
+
+    // Polygon is projected onto the axis plane in which it is the biggest, mirrored to be counterclockwise
+    let axis = (0..3)
+        .max_by(|&a, &b| normal[a].abs().total_cmp(&normal[b].abs()))
+        .unwrap_or(2);
+    let (u, v) = [(1, 2), (2, 0), (0, 1)][axis as usize];
+    let sign = if normal[axis] < 0.0 { -1.0 } else { 1.0 };
+    let projected: Vec<(f32, f32)> = points.iter().map(|p| (p[u], p[v] * sign)).collect();
+
+    let cross = |o: (f32, f32), a: (f32, f32), b: (f32, f32)| {
+        (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+    };
+    let inside =
+        |p, a, b, c| cross(a, b, p) >= 0.0 && cross(b, c, p) >= 0.0 && cross(c, a, p) >= 0.0;
+
+    let mut remaining: Vec<usize> = (0..count).collect();
+    let mut triangles = Vec::with_capacity(count - 2);
+    while remaining.len() > 3 {
+        let left = remaining.len();
+        let corners = |i: usize| {
+            (
+                remaining[(i + left - 1) % left],
+                remaining[i],
+                remaining[(i + 1) % left],
+            )
+        };
+
+        // Ear is a convex corner, whose triangle has no other corner inside
+        let ear = (0..left).find(|&i| {
+            let (prev, current, next) = corners(i);
+            let (a, b, c) = (projected[prev], projected[current], projected[next]);
+            cross(a, b, c) > 0.0
+                && remaining.iter().all(|&other| {
+                    other == prev
+                        || other == current
+                        || other == next
+                        || !inside(projected[other], a, b, c)
+                })
+        });
+
+        // Degenerated polygons without ears are cut anyway
+        let ear = ear.unwrap_or(0);
+        let (prev, current, next) = corners(ear);
+        triangles.push([prev, current, next]);
+        remaining.remove(ear);
+    }
+    triangles.push([remaining[0], remaining[1], remaining[2]]);
+
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() {
+        let obj = parse_obj(
+            "mtllib scene.mtl\n\
+             o Box\n\
+             v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\n\
+             vt 0 0\nvt 1 0\nvt 1 1\n\
+             vn 0 0 1\n\
+             usemtl red # comment\n\
+             f 1/1/1 2/2/1 3/3/1 4//1\n\
+             g lid\n\
+             usemtl blue\n\
+             f -4 -3 -2\n\
+             g\n\
+             s 2\n\
+             f 1 2 4\n",
+        )
+        .unwrap();
+
+        assert_eq!(obj.material_libraries, vec!["scene.mtl"]);
+        assert_eq!(obj.material_names, vec!["red", "blue"]);
+        assert_eq!(obj.groups.len(), 2);
+        assert_eq!(obj.groups[0].name, "Box");
+        assert_eq!(obj.groups[0].faces.len(), 2);
+        assert_eq!(obj.groups[1].name, "lid");
+
+        let quad = &obj.groups[0].faces[0];
+        assert_eq!(quad.material, Some(0));
+        assert_eq!(quad.smoothing_group, 0);
+        assert_eq!(obj.groups[0].faces[1].smoothing_group, 2);
+        assert_eq!(
+            quad.corners[3],
+            Corner {
+                position: 3,
+                uv: None,
+                normal: Some(0)
+            }
+        );
+        assert_eq!(obj.groups[1].faces[0].corners[0].position, 0);
+        assert!(parse_obj("v 0 0 0\nf 1 2 3\n").is_err());
+
+        // Coordinates which aren't finite are rejected at their line
+        for line in ["v nan 0 0", "vt 0 inf", "vn 0 -infinity 0", "v 1e39 0 0"] {
+            let error = parse_obj(&format!("v 0 0 0\n{}\n", line)).err().unwrap();
+            assert!(error.to_string().starts_with("Line 2"));
+        }
+
+        let mtl = parse_mtl(
+            "newmtl glass\nKd 1 1 1\nNi 1.33\nd 0.1\n\
+             newmtl lamp\nKe 4\nmap_Kd -s 2 2 1 textures/lamp.png\n",
+        )
+        .unwrap();
+        assert_eq!(mtl[0].refractive_index, 1.33);
+        assert_eq!(mtl[1].emission, V3::new(4.0, 4.0, 4.0));
+        assert_eq!(mtl[1].diffuse_map.as_deref(), Some("textures/lamp.png"));
+    }
+
+    #[test]
+    fn triangulate_test() {
+        // L shaped hexagon, a fan from its last corner would cover the notch
+        let points = [
+            V3::new(1.0, 1.0, 0.0),
+            V3::new(1.0, 2.0, 0.0),
+            V3::new(0.0, 2.0, 0.0),
+            V3::new(0.0, 0.0, 0.0),
+            V3::new(2.0, 0.0, 0.0),
+            V3::new(2.0, 1.0, 0.0),
+        ];
+
+        let triangles = triangulate(&points);
+        assert_eq!(triangles.len(), 4);
+
+        // Triangles keep the winding and cover the area of the polygon, which is 3
+        let area: f32 = triangles
+            .iter()
+            .map(|&[a, b, c]| {
+                let normal = (points[b] - points[a]).cross(points[c] - points[a]);
+                assert!(normal.z > 0.0);
+                normal.z / 2.0
+            })
+            .sum();
+        assert!((area - 3.0).abs() < 1e-5);
+    }
+}
@@ -1,4 +1,5 @@
 use crate::{
+    hit_before,
     hittables::Aabb,
     misc::{Interval, Pdf},
     Hit, Hittable, Ray, V3,
@@ -40,13 +41,13 @@ impl Quad {
             w: n / n.dot(n),
         }
     }
-}
 
-impl Hittable for Quad {
-    #[doc = r" Returns Hit structure, if Ray intersects with this object surface in passed time interval."]
-    #[doc = r""]
-    #[doc = r" `r` - Ray that should hit the object."]
-    fn hit(&self, ray: &Ray) -> Option<Hit> {
+    /// Returns time of the hit and plane coordinates of the hit point, if the Ray hits the quad within the interval.
+    ///
+    /// `ray` - Ray that should hit the quad.
+    ///
+    /// `interval` - Times of the Ray at which hits count.
+    fn intersect(&self, ray: &Ray, interval: Interval) -> Option<(f32, f32, f32)> {
         let denominator = self.normal.dot(ray.end);
 
         // Parallel to the plane
@@ -55,7 +56,7 @@ impl Hittable for Quad {
         }
 
         let t = (self.D - self.normal.dot(ray.origin)) / denominator;
-        if !ray.setting.ray_time.contains(t) {
+        if !interval.contains(t) {
             return None;
         }
 
@@ -71,15 +72,45 @@ impl Hittable for Quad {
             return None;
         }
 
-        Some(Hit::new(
-            ray,
-            self.normal,
-            t,
-            intersection,
-            self.material,
-            alpha,
-            beta,
-        ))
+        Some((t, alpha, beta))
+    }
+}
+
+impl Hittable for Quad {
+    #[doc = r" Returns Hit structure, if Ray intersects with this object surface in passed time interval."]
+    #[doc = r""]
+    #[doc = r" `r` - Ray that should hit the object."]
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        let (t, alpha, beta) = self.intersect(ray, ray.setting.ray_time)?;
+        let intersection = ray.point_at_param(t);
+
+        if let Some(opacity) = ray.opacity(self.material) {
+            if !opacity.is_opaque(alpha, beta, intersection) {
+                return None;
+            }
+        }
+
+        Some(
+            Hit::new(
+                ray,
+                self.normal,
+                t,
+                intersection,
+                self.material,
+                alpha,
+                beta,
+            )
+            .with_tangents(self.u, self.v),
+        )
+    }
+
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        // Cutout mask needs the point of the hit
+        if ray.opacity(self.material).is_some() {
+            return hit_before(self, ray, t_max).is_some();
+        }
+        self.intersect(ray, Interval::new(ray.setting.ray_time.min, t_max))
+            .is_some()
     }
 
     #[doc = r" Returns bounding box of the object"]
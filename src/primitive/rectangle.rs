@@ -3,6 +3,7 @@ use crate::hittables::Aabb;
 use crate::misc::Interval;
 use crate::misc::Pdf;
 use crate::ray::*;
+use crate::transform::MediumStack;
 use crate::V3;
 
 const EPSILON: f32 = 0.001;
@@ -44,15 +45,33 @@ impl Hittable for XYrect {
 
         let outward_normal = V3::new(0.0, 0.0, 1.0);
 
-        Some(Hit::new(
+        Some(
+            Hit::new(
+                ray,
+                outward_normal,
+                t,
+                ray.point_at_param(t),
+                self.material,
+                (x - self.x0) / (self.x1 - self.x0),
+                (y - self.y0) / (self.y1 - self.y0),
+            )
+            .with_tangents(
+                V3::new(self.x1 - self.x0, 0.0, 0.0),
+                V3::new(0.0, self.y1 - self.y0, 0.0),
+            ),
+        )
+    }
+
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        let interval = Interval::new(ray.setting.ray_time.min, t_max);
+        rect_time(
             ray,
-            outward_normal,
-            t,
-            ray.point_at_param(t),
-            self.material,
-            (x - self.x0) / (self.x1 - self.x0),
-            (y - self.y0) / (self.y1 - self.y0),
-        ))
+            interval,
+            2,
+            self.k,
+            [(0, self.x0, self.x1), (1, self.y0, self.y1)],
+        )
+        .is_some()
     }
 
     fn bounding_box(&self) -> Aabb {
@@ -116,15 +135,33 @@ impl Hittable for XZrect {
 
         let outward_normal = V3::new(0.0, 1.0, 0.0);
 
-        Some(Hit::new(
+        Some(
+            Hit::new(
+                ray,
+                outward_normal,
+                t,
+                ray.point_at_param(t),
+                self.material,
+                (x - self.x0) / (self.x1 - self.x0),
+                (z - self.z0) / (self.z1 - self.z0),
+            )
+            .with_tangents(
+                V3::new(self.x1 - self.x0, 0.0, 0.0),
+                V3::new(0.0, 0.0, self.z1 - self.z0),
+            ),
+        )
+    }
+
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        let interval = Interval::new(ray.setting.ray_time.min, t_max);
+        rect_time(
             ray,
-            outward_normal,
-            t,
-            ray.point_at_param(t),
-            self.material,
-            (x - self.x0) / (self.x1 - self.x0),
-            (z - self.z0) / (self.z1 - self.z0),
-        ))
+            interval,
+            1,
+            self.k,
+            [(0, self.x0, self.x1), (2, self.z0, self.z1)],
+        )
+        .is_some()
     }
 
     fn bounding_box(&self) -> Aabb {
@@ -188,15 +225,33 @@ impl Hittable for YZrect {
 
         let outward_normal = V3::new(1.0, 0.0, 0.0);
 
-        Some(Hit::new(
+        Some(
+            Hit::new(
+                ray,
+                outward_normal,
+                t,
+                ray.point_at_param(t),
+                self.material,
+                (y - self.y0) / (self.y1 - self.y0),
+                (z - self.z0) / (self.z1 - self.z0),
+            )
+            .with_tangents(
+                V3::new(0.0, self.y1 - self.y0, 0.0),
+                V3::new(0.0, 0.0, self.z1 - self.z0),
+            ),
+        )
+    }
+
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        let interval = Interval::new(ray.setting.ray_time.min, t_max);
+        rect_time(
             ray,
-            outward_normal,
-            t,
-            ray.point_at_param(t),
-            self.material,
-            (y - self.y0) / (self.y1 - self.y0),
-            (z - self.z0) / (self.z1 - self.z0),
-        ))
+            interval,
+            0,
+            self.k,
+            [(1, self.y0, self.y1), (2, self.z0, self.z1)],
+        )
+        .is_some()
     }
 
     fn bounding_box(&self) -> Aabb {
@@ -222,12 +277,43 @@ impl YZrect {
     }
 }
 
+/// Returns time at which the Ray crosses the rectangle within the interval.
+///
+/// `axis` - Axis perpendicular to the rectangle.
+///
+/// `k` - Position of the rectangle on the axis.
+///
+/// `bounds` - Other axes with the bounds of the rectangle on them.
+fn rect_time(
+    ray: &Ray,
+    interval: Interval,
+    axis: u32,
+    k: f32,
+    bounds: [(u32, f32, f32); 2],
+) -> Option<f32> {
+    let t = (k - ray.origin[axis]) / ray.end[axis];
+    if t.is_nan() || !interval.contains(t) {
+        return None;
+    }
+
+    bounds
+        .iter()
+        .all(|&(axis, min, max)| {
+            let coordinate = ray.origin[axis] + t * ray.end[axis];
+            min <= coordinate && coordinate <= max
+        })
+        .then_some(t)
+}
+
 impl Pdf for XZrect {
     fn value(&self, origin: V3<f32>, direction: V3<f32>) -> f32 {
         let ray = Ray {
             origin,
             end: direction,
             time: 1.0,
+            cone: RayCone::default(),
+            channel: None,
+            media: MediumStack::default(),
             setting: &RaySetting {
                 depth: 32,
                 background_color: V3::default(),
@@ -236,6 +322,7 @@ impl Pdf for XZrect {
                     max: 2048.0,
                 },
             },
+            materials: None,
         };
 
         match self.hit(&ray) {
@@ -267,6 +354,9 @@ impl Pdf for XYrect {
             origin,
             end: direction,
             time: 1.0,
+            cone: RayCone::default(),
+            channel: None,
+            media: MediumStack::default(),
             setting: &RaySetting {
                 depth: 32,
                 background_color: V3::default(),
@@ -275,6 +365,7 @@ impl Pdf for XYrect {
                     max: 2048.0,
                 },
             },
+            materials: None,
         };
 
         match self.hit(&ray) {
@@ -306,6 +397,9 @@ impl Pdf for YZrect {
             origin,
             end: direction,
             time: 1.0,
+            cone: RayCone::default(),
+            channel: None,
+            media: MediumStack::default(),
             setting: &RaySetting {
                 depth: 32,
                 background_color: V3::default(),
@@ -314,6 +408,7 @@ impl Pdf for YZrect {
                     max: 2048.0,
                 },
             },
+            materials: None,
         };
 
         match self.hit(&ray) {
@@ -1,9 +1,10 @@
 use crate::hit::*;
 use crate::hittables::Aabb;
-use crate::misc::Interval;
 use crate::misc::Onb;
 use crate::misc::Pdf;
+use crate::misc::{gamma, Interval};
 use crate::ray::*;
+use crate::transform::MediumStack;
 use crate::V3;
 /// Sphere primitive.
 #[derive(Clone)]
@@ -18,40 +19,12 @@ pub struct Sphere {
 
 impl Hittable for Sphere {
     fn hit(&self, ray: &Ray) -> Option<Hit> {
-        let oc = ray.origin - self.center;
-        let a = ray.end.dot(ray.end);
-        let b = oc.dot(ray.end);
-        let c = oc.dot(oc) - self.radius * self.radius;
-        let discriminant = b * b - a * c;
-
-        if discriminant > 0.0 {
-            let t;
-            let t1 = (-b - (b * b - a * c).sqrt()) / a;
-            let t2 = (-b + (b * b - a * c).sqrt()) / a;
-
-            if ray.setting.ray_time.contains(t1) {
-                t = t1;
-            } else if ray.setting.ray_time.contains(t2) {
-                t = t2;
-            } else {
-                return None;
-            }
-
-            let point = ray.point_at_param(t);
-            let normal = (point - self.center) / self.radius;
-            let pi = std::f32::consts::PI;
-
-            let sphere_point = (point - self.center).norm();
-
-            let phi = (sphere_point.z).atan2(sphere_point.x);
-            let theta = (sphere_point.y).asin();
-            let u = 1.0 - (phi + pi) / (2.0 * pi);
-            let v = (theta + pi / 2.0) / pi;
+        sphere_hit(ray, self.center, self.radius, self.material)
+    }
 
-            Some(Hit::new(ray, normal, t, point, self.material, u, v))
-        } else {
-            None
-        }
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        let interval = Interval::new(ray.setting.ray_time.min, t_max);
+        sphere_root(ray, self.center, self.radius, interval).is_some()
     }
 
     fn bounding_box(&self) -> Aabb {
@@ -93,14 +66,18 @@ impl Pdf for Sphere {
             origin,
             end: direction,
             time: 1.0,
+            cone: RayCone::default(),
+            channel: None,
+            media: MediumStack::default(),
             setting: &RaySetting {
                 depth: 32,
                 background_color: V3::default(),
                 ray_time: Interval {
                     min: 0.001,
-                    max: 2048.0,
+                    max: f32::INFINITY,
                 },
             },
+            materials: None,
         };
 
         match self.hit(&ray) {
@@ -124,6 +101,99 @@ impl Pdf for Sphere {
     }
 }
 
+/// Returns hit of the sphere, with the point moved back onto the surface and its error bounded.
+///
+/// `ray` - Ray that should hit the sphere.
+///
+/// `center` - Center of the sphere at the time of the ray.
+///
+/// `radius` - Radius of the sphere, negative radius flips the normal inside.
+///
+/// `material` - Material of the sphere.
+pub(crate) fn sphere_hit(ray: &Ray, center: V3<f32>, radius: f32, material: usize) -> Option<Hit> {
+    let t = sphere_root(ray, center, radius, ray.setting.ray_time)?;
+
+    // Point is moved back onto the surface, which bounds its error
+    let offset = ray.point_at_param(t) - center;
+    let offset = offset * (radius.abs() / offset.length());
+    let point = center + offset;
+    let error = offset.abs() * gamma(5) + point.abs() * gamma(1);
+    let normal = offset / radius;
+    let pi = std::f32::consts::PI;
+
+    let sphere_point = (point - center).norm();
+
+    let phi = (sphere_point.z).atan2(sphere_point.x);
+    let theta = (sphere_point.y).asin();
+    let u = 1.0 - (phi + pi) / (2.0 * pi);
+    let v = (theta + pi / 2.0) / pi;
+
+    let (dpdu, dpdv) = sphere_tangents(point - center);
+
+    Some(
+        Hit::new(ray, normal, t, point, material, u, v)
+            .with_tangents(dpdu, dpdv)
+            .with_error(error),
+    )
+}
+
+/// Returns time of the nearest hit of the sphere within the interval.
+///
+/// `ray` - Ray that should hit the sphere.
+///
+/// `center` - Center of the sphere at the time of the ray.
+///
+/// `radius` - Radius of the sphere.
+///
+/// `interval` - Times of the Ray at which hits count.
+pub(crate) fn sphere_root(
+    ray: &Ray,
+    center: V3<f32>,
+    radius: f32,
+    interval: Interval,
+) -> Option<f32> {
+    let oc = ray.origin - center;
+    let a = ray.end.dot(ray.end);
+    let b = oc.dot(ray.end);
+    let c = oc.dot(oc) - radius * radius;
+    let discriminant = b * b - a * c;
+
+    if discriminant <= 0.0 {
+        return None;
+    }
+
+    // Roots are computed without subtracting close numbers, which would lose precision of the near root
+    let q = -b - discriminant.sqrt().copysign(b);
+    let (t1, t2) = ((q / a).min(c / q), (q / a).max(c / q));
+
+    // Roots closer to the origin than their rounding error may be the surface the ray starts from, unless the interval asks for roots behind the origin too
+    let t_error = gamma(7) * (oc.length() + radius.abs()) / a.sqrt();
+    [t1, t2]
+        .iter()
+        .copied()
+        .find(|&t| (interval.min < 0.0 || t > t_error) && interval.contains(t))
+}
+
+/// Returns partial derivatives of the point on the sphere with respect to `u` and `v` texture coordinates.
+///
+/// `local_point` - Point on the sphere relative to its center.
+pub(crate) fn sphere_tangents(local_point: V3<f32>) -> (V3<f32>, V3<f32>) {
+    let pi = std::f32::consts::PI;
+    let V3 { x, y, z } = local_point;
+    let radius_xz = (x * x + z * z).sqrt();
+
+    let dpdu = V3::new(z, 0.0, -x) * (2.0 * pi);
+
+    // Tangent along the meridian degenerates at the poles
+    let dpdv = if radius_xz > 0.0 {
+        V3::new(-y * x / radius_xz, radius_xz, -y * z / radius_xz) * pi
+    } else {
+        V3::default()
+    };
+
+    (dpdu, dpdv)
+}
+
 fn random_to_sphere(radius: f32, distance_squared: f32) -> V3<f32> {
     use rand::Rng;
     let mut rng = rand::thread_rng();
@@ -1,7 +1,7 @@
 use crate::hit::*;
 use crate::hittables::Aabb;
-use crate::misc::Interval;
 use crate::misc::Pdf;
+use crate::misc::{gamma, Interval};
 use crate::ray::*;
 use crate::V3;
 /// Triangle primitive.
@@ -11,6 +11,10 @@ pub struct Triangle {
     verticles: [V3<f32>; 3],
     /// Normals of corresponding verticles
     normals: Option<[V3<f32>; 3]>,
+    /// Texture coordinates of corresponding verticles
+    uvs: Option<[(f32, f32); 3]>,
+    /// Partial derivatives of the surface with respect to texture coordinates
+    tangents: (V3<f32>, V3<f32>),
     /// Material of verticle
     material: usize,
     bounding_box: Aabb,
@@ -18,56 +22,221 @@ pub struct Triangle {
 
 impl Hittable for Triangle {
     fn hit(&self, ray: &Ray) -> Option<Hit> {
-        let v0v1 = self.verticles[1] - self.verticles[0];
-        let v0v2 = self.verticles[2] - self.verticles[0];
+        let coordinates = intersect(&self.verticles, ray, ray.setting.ray_time)?;
 
-        let pvec = ray.end.cross(v0v2);
-        let det = v0v1.dot(pvec);
-        let inv_det = 1.0 / det;
-        let epsilon = 0.0000001;
+        TriangleShading {
+            verticles: self.verticles,
+            normals: self.normals,
+            uvs: self.uvs,
+            tangents: self.tangents,
+            material: self.material,
+        }
+        .shade(ray, coordinates)
+    }
 
-        if det.abs() < epsilon {
-            return None;
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        // Cutout mask needs the texture coordinates of the hit
+        if ray.opacity(self.material).is_some() {
+            return hit_before(self, ray, t_max).is_some();
         }
+        intersect(
+            &self.verticles,
+            ray,
+            Interval::new(ray.setting.ray_time.min, t_max),
+        )
+        .is_some()
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounding_box.clone()
+    }
+}
 
-        let tvec = ray.origin - self.verticles[0];
-        let u = tvec.dot(pvec) * inv_det;
+/// Returns time of the hit and barycentric coordinates of the second, the third and the first verticle, if the Ray hits the triangle within the interval.
+///
+/// `verticles` - Verticles of the triangle, CCW.
+///
+/// `ray` - Ray that should hit the triangle.
+///
+/// `interval` - Times of the Ray at which hits count.
+pub(crate) fn intersect(
+    verticles: &[V3<f32>; 3],
+    ray: &Ray,
+    interval: Interval,
+) -> Option<(f32, f32, f32, f32)> {
+    // Watertight test by Woop, Benthin and Wald, rays going through shared edges and verticles always hit one of the triangles
+    // Coordinates are permuted, so the ray goes along the z axis in its biggest direction
+    let kz = (0..3)
+        .max_by(|&a, &b| {
+            ray.end[a]
+                .abs()
+                .partial_cmp(&ray.end[b].abs())
+                .expect("Tried to compare a NaN")
+        })
+        .unwrap_or(2);
+    let kx = (kz + 1) % 3;
+    let ky = (kx + 1) % 3;
+    let permute = |p: V3<f32>| V3::new(p[kx], p[ky], p[kz]);
 
-        if !(0.0..=1.0).contains(&u) {
-            return None;
-        }
+    let direction = permute(ray.end);
+    let mut p0 = permute(verticles[0] - ray.origin);
+    let mut p1 = permute(verticles[1] - ray.origin);
+    let mut p2 = permute(verticles[2] - ray.origin);
 
-        let qvec = tvec.cross(v0v1);
-        let v = ray.end.dot(qvec) * inv_det;
+    // Shear moves the ray onto the z axis
+    let shear_x = -direction.x / direction.z;
+    let shear_y = -direction.y / direction.z;
+    let shear_z = 1.0 / direction.z;
+    for p in [&mut p0, &mut p1, &mut p2] {
+        p.x += shear_x * p.z;
+        p.y += shear_y * p.z;
+    }
 
-        if v < 0.0 || u + v > 1.0 {
-            return None;
-        }
+    // Edge functions tell on which side of each edge the ray goes
+    let mut e0 = p1.x * p2.y - p1.y * p2.x;
+    let mut e1 = p2.x * p0.y - p2.y * p0.x;
+    let mut e2 = p0.x * p1.y - p0.y * p1.x;
 
-        let t = v0v2.dot(qvec) * inv_det;
+    // Ray going exactly through an edge is decided in double precision
+    if e0 == 0.0 || e1 == 0.0 || e2 == 0.0 {
+        let edge =
+            |a: V3<f32>, b: V3<f32>| (a.x as f64 * b.y as f64 - a.y as f64 * b.x as f64) as f32;
+        e0 = edge(p1, p2);
+        e1 = edge(p2, p0);
+        e2 = edge(p0, p1);
+    }
+
+    if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
+        return None;
+    }
+    let det = e0 + e1 + e2;
+    if det == 0.0 {
+        return None;
+    }
 
-        if t < ray.setting.ray_time.min || t > ray.setting.ray_time.max {
-            return None;
+    p0.z *= shear_z;
+    p1.z *= shear_z;
+    p2.z *= shear_z;
+    let t_scaled = e0 * p0.z + e1 * p1.z + e2 * p2.z;
+
+    let inv_det = 1.0 / det;
+    let t = t_scaled * inv_det;
+    if !interval.contains(t) {
+        return None;
+    }
+
+    // Hit right at the origin may be caused by rounding errors, so t has to be bigger than its error bound, unless the interval asks for hits behind the origin too
+    let max_z = p0.z.abs().max(p1.z.abs()).max(p2.z.abs());
+    let max_x = p0.x.abs().max(p1.x.abs()).max(p2.x.abs());
+    let max_y = p0.y.abs().max(p1.y.abs()).max(p2.y.abs());
+    let max_e = e0.abs().max(e1.abs()).max(e2.abs());
+    let delta_z = gamma(3) * max_z;
+    let delta_x = gamma(5) * (max_x + max_z);
+    let delta_y = gamma(5) * (max_y + max_z);
+    let delta_e = 2.0 * (gamma(2) * max_x * max_y + delta_y * max_x + delta_x * max_y);
+    let delta_t =
+        3.0 * (gamma(3) * max_e * max_z + delta_e * max_z + delta_z * max_e) * inv_det.abs();
+    if interval.min >= 0.0 && t <= delta_t {
+        return None;
+    }
+
+    // Barycentric coordinates of the second and the third verticle
+    let u = e1 * inv_det;
+    let v = e2 * inv_det;
+    let w = e0 * inv_det;
+
+    Some((t, u, v, w))
+}
+
+/// Returns partial derivatives of the surface of the triangle with respect to texture coordinates, or its edges if it has no texture coordinates.
+///
+/// `verticles` - Verticles of the triangle, CCW.
+///
+/// `uvs` - Texture coordinates of corresponding verticles.
+pub(crate) fn tangents(
+    verticles: &[V3<f32>; 3],
+    uvs: Option<[(f32, f32); 3]>,
+) -> (V3<f32>, V3<f32>) {
+    let edge1 = verticles[1] - verticles[0];
+    let edge2 = verticles[2] - verticles[0];
+
+    if let Some(uvs) = uvs {
+        let (du1, dv1) = (uvs[1].0 - uvs[0].0, uvs[1].1 - uvs[0].1);
+        let (du2, dv2) = (uvs[2].0 - uvs[0].0, uvs[2].1 - uvs[0].1);
+        let determinant = du1 * dv2 - dv1 * du2;
+
+        // Degenerated UVs leave tangents along the edges
+        if determinant.abs() > 1e-12 {
+            let inv_determinant = 1.0 / determinant;
+            return (
+                (edge1 * dv2 - edge2 * dv1) * inv_determinant,
+                (edge2 * du1 - edge1 * du2) * inv_determinant,
+            );
         }
+    }
+    (edge1, edge2)
+}
+
+/// Attributes of a triangle needed to shade its hit, taken from a [`Triangle`] or from buffers of a mesh.
+pub(crate) struct TriangleShading {
+    pub(crate) verticles: [V3<f32>; 3],
+    pub(crate) normals: Option<[V3<f32>; 3]>,
+    pub(crate) uvs: Option<[(f32, f32); 3]>,
+    pub(crate) tangents: (V3<f32>, V3<f32>),
+    pub(crate) material: usize,
+}
+
+impl TriangleShading {
+    /// Returns hit at given coordinates, unless it is in a transparent part of the triangle, as given by its material.
+    ///
+    /// `ray` - Ray that hit the triangle.
+    ///
+    /// `coordinates` - Time of the hit and barycentric coordinates, like returned by [`intersect`].
+    pub(crate) fn shade(&self, ray: &Ray, coordinates: (f32, f32, f32, f32)) -> Option<Hit> {
+        let (t, u, v, w) = coordinates;
 
         let normal_to_triangle = match self.normals {
-            Some(normals) => normals[0] * (1.0 - u - v) + normals[1] * u + normals[2] * v,
-            None => (v0v2).cross(v0v1).norm(),
+            Some(normals) => normals[0] * w + normals[1] * u + normals[2] * v,
+            None => (self.verticles[2] - self.verticles[0])
+                .cross(self.verticles[1] - self.verticles[0])
+                .norm(),
         };
 
-        Some(Hit::new(
-            ray,
-            normal_to_triangle,
-            t,
-            ray.point_at_param(t),
-            self.material,
-            u,
-            v,
-        ))
-    }
+        // Barycentric coordinates are used as texture coordinates if the triangle has no UVs
+        let (texture_u, texture_v) = match self.uvs {
+            Some(uvs) => (
+                uvs[0].0 * w + uvs[1].0 * u + uvs[2].0 * v,
+                uvs[0].1 * w + uvs[1].1 * u + uvs[2].1 * v,
+            ),
+            None => (u, v),
+        };
 
-    fn bounding_box(&self) -> Aabb {
-        self.bounding_box.clone()
+        // Point interpolated from the verticles has much smaller error than the one computed along the ray
+        let point = self.verticles[0] * w + self.verticles[1] * u + self.verticles[2] * v;
+        let error = ((self.verticles[0] * w).abs()
+            + (self.verticles[1] * u).abs()
+            + (self.verticles[2] * v).abs())
+            * gamma(7);
+
+        if let Some(opacity) = ray.opacity(self.material) {
+            if !opacity.is_opaque(texture_u, texture_v, point) {
+                return None;
+            }
+        }
+
+        Some(
+            Hit::new(
+                ray,
+                normal_to_triangle,
+                t,
+                point,
+                self.material,
+                texture_u,
+                texture_v,
+            )
+            .with_tangents(self.tangents.0, self.tangents.1)
+            .with_error(error),
+        )
     }
 }
 
@@ -83,11 +252,44 @@ impl Triangle {
         Triangle {
             verticles: [verticles.x, verticles.y, verticles.z],
             normals: normals.map(|normals| [normals.x, normals.y, normals.z]),
+            uvs: None,
+            tangents: tangents(&[verticles.x, verticles.y, verticles.z], None),
             material,
             bounding_box: Self::init_bounding_box(&verticles),
         }
     }
 
+    /// Returns Triangle with texture coordinates assigned to its verticles.
+    ///
+    /// `uvs` - Texture coordinates of corresponding verticles.
+    pub fn with_uvs(mut self, uvs: V3<(f32, f32)>) -> Self {
+        let uvs = [uvs.x, uvs.y, uvs.z];
+
+        self.tangents = tangents(&self.verticles, Some(uvs));
+        self.uvs = Some(uvs);
+        self
+    }
+
+    /// Returns copy of the Triangle moved to other verticles, with the same normals, texture coordinates and material.
+    ///
+    /// `verticles` - New verticles, CCW.
+    pub fn with_verticles(&self, verticles: V3<V3<f32>>) -> Self {
+        let normals = self
+            .normals
+            .map(|normals| V3::new(normals[0], normals[1], normals[2]));
+        let triangle = Triangle::new(verticles, normals, self.material);
+
+        match self.uvs {
+            Some(uvs) => triangle.with_uvs(V3::new(uvs[0], uvs[1], uvs[2])),
+            None => triangle,
+        }
+    }
+
+    /// Returns verticles of the Triangle, CCW.
+    pub fn verticles(&self) -> [V3<f32>; 3] {
+        self.verticles
+    }
+
     fn init_bounding_box(verticles: &V3<V3<f32>>) -> Aabb {
         use crate::misc::IntoMultizip;
 
@@ -130,3 +332,46 @@ impl Pdf for Triangle {
         todo!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn watertight_test() {
+        // Quad split along its diagonal, far from the origin
+        let corners = [
+            V3::new(1000.0, 0.0, 1000.0),
+            V3::new(1001.0, 0.0, 1000.0),
+            V3::new(1001.0, 0.0, 1001.0),
+            V3::new(1000.0, 0.0, 1001.0),
+        ];
+        let first = Triangle::new(V3::new(corners[0], corners[1], corners[2]), None, 0);
+        let second = Triangle::new(V3::new(corners[0], corners[2], corners[3]), None, 0);
+
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.0, f32::INFINITY),
+        };
+        let origin = V3::new(990.0, 7.0, 995.0);
+
+        for i in 0..=100 {
+            let target = corners[0] + (corners[2] - corners[0]) * (i as f32 / 100.0);
+            let ray = Ray::for_test(origin, target - origin, &setting);
+
+            let hit = first.hit(&ray).or_else(|| second.hit(&ray));
+            assert!(hit.is_some());
+
+            // Ray leaving the surface doesn't hit it again
+            let hit = hit.unwrap();
+            let reflected = V3::new(ray.end.x, -ray.end.y, ray.end.z);
+            let leaving = Ray {
+                origin: hit.spawn_origin(reflected),
+                end: reflected,
+                ..ray
+            };
+            assert!(first.hit(&leaving).is_none() && second.hit(&leaving).is_none());
+        }
+    }
+}
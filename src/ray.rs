@@ -1,8 +1,16 @@
 use crate::hit::*;
-use crate::misc::{Interval, MixturePdf, Pdf};
+use crate::material::{MaterialContainer, MaterialTrait, ScatterRecord};
+use crate::misc::{Interval, Pdf};
+use crate::texture::Opacity;
+use crate::transform::{MediumInterface, MediumStack};
 use crate::Scene;
 use crate::V3;
 
+use rand::Rng;
+
+/// Relative part of the distance to a light, by which shadow rays are shortened, so they don't hit the light itself.
+const SHADOW_EPSILON: f32 = 0.0001;
+
 /// Ray in form of segment of the straight line.
 #[derive(Copy, Clone)]
 pub struct Ray<'setting> {
@@ -12,8 +20,34 @@ pub struct Ray<'setting> {
     pub end: V3<f32>,
     /// Timestamp during which Ray lives.
     pub time: f32,
+    /// Cone around the Ray, approximating the area seen through the pixel.
+    pub cone: RayCone,
+    /// Color channel carried by the Ray, after it went through a medium with colored extinction, or None if the Ray carries all channels.
+    pub channel: Option<u32>,
+    /// Media the Ray is inside of.
+    pub media: MediumStack,
     /// Global setting shared by all Rays during rendering.
     pub setting: &'setting RaySetting,
+    /// Materials of the Scene, whose cutout masks let the Ray through transparent parts of surfaces, or None if all surfaces are opaque.
+    pub materials: Option<&'setting MaterialContainer>,
+}
+
+/// Cone around the Ray, its width at the hit point tells how big area of the texture should be filtered.
+#[derive(Copy, Clone, Default)]
+pub struct RayCone {
+    /// Width of the cone at the origin of the Ray.
+    pub width: f32,
+    /// How much the width grows per unit of travelled distance.
+    pub spread: f32,
+}
+
+impl RayCone {
+    /// Returns width of the cone after travelling some distance.
+    ///
+    /// `distance` - Distance from the origin of the Ray.
+    pub fn width_at(&self, distance: f32) -> f32 {
+        self.width + self.spread * distance
+    }
 }
 
 /// Global setting of all rays.
@@ -27,7 +61,42 @@ pub struct RaySetting {
     pub ray_time: Interval,
 }
 
-impl Ray<'_> {
+/// Returns weight of a sample by the power heuristic of multiple importance sampling, with the exponent 2.
+///
+/// `pdf` - Pdf of the strategy, which generated the sample.
+///
+/// `other_pdf` - Pdf of the other strategy, with which the same sample could be generated.
+fn power_heuristic(pdf: f32, other_pdf: f32) -> f32 {
+    let (pdf, other_pdf) = (pdf * pdf, other_pdf * other_pdf);
+    if pdf + other_pdf > 0.0 {
+        pdf / (pdf + other_pdf)
+    } else {
+        0.0
+    }
+}
+
+impl<'setting> Ray<'setting> {
+    /// Returns Ray at the time 0, outside of any media and opaque to cutout masks, for tests.
+    ///
+    /// `origin` - Point from which the Ray goes.
+    ///
+    /// `end` - Direction of the Ray.
+    ///
+    /// `setting` - Global setting of rays.
+    #[cfg(test)]
+    pub(crate) fn for_test(origin: V3<f32>, end: V3<f32>, setting: &'setting RaySetting) -> Self {
+        Ray {
+            origin,
+            end,
+            time: 0.0,
+            cone: RayCone::default(),
+            channel: None,
+            media: MediumStack::default(),
+            setting,
+            materials: None,
+        }
+    }
+
     /// Returns point lying on the Ray at the specific time.
     ///
     /// `t` - Time at which point should be taken.
@@ -35,64 +104,147 @@ impl Ray<'_> {
         self.origin + self.end * t
     }
 
+    /// Returns Ray with cone, used to filter textures, replaced.
+    ///
+    /// `cone` - New cone of the Ray.
+    pub fn with_cone(mut self, cone: RayCone) -> Self {
+        self.cone = cone;
+        self
+    }
+
+    /// Returns Ray passing through transparent parts of surfaces, as given by cutout masks of their materials.
+    ///
+    /// `materials` - Materials of the Scene.
+    pub fn with_materials(mut self, materials: &'setting MaterialContainer) -> Self {
+        self.materials = Some(materials);
+        self
+    }
+
+    /// Returns cutout mask of the material, or None if surfaces with the material are opaque for the Ray.
+    ///
+    /// `material` - Index of the material of the hit surface.
+    pub(crate) fn opacity(&self, material: usize) -> Option<&'setting Opacity> {
+        self.materials?.opacity(material)
+    }
+
     /// Get the color of casted Ray.
     ///
     /// `hittable` - Struct implementing hittable trait from which color should be taken; usually this is a Scene.
-    pub fn get_color(&self, scene: &Scene) -> V3<f32> {
-        self.color(scene, 0)
+    pub fn get_color<W: Hittable>(&self, scene: &Scene<W>) -> V3<f32> {
+        self.color(scene, 0, None)
+    }
+
+    /// Returns color of the Ray carrying only one color channel, weighted by the probability of picking that channel.
+    ///
+    /// `channel` - Picked color channel.
+    ///
+    /// `scattering_pdf` - Pdf of the direction of the Ray at the previous hit, see [`Ray::color`].
+    fn channel_color<W: Hittable>(
+        &self,
+        scene: &Scene<W>,
+        depth: u16,
+        channel: u32,
+        scattering_pdf: Option<f32>,
+    ) -> V3<f32> {
+        channel_mask(channel).hadamard(
+            Ray {
+                channel: Some(channel),
+                ..*self
+            }
+            .color(scene, depth, scattering_pdf),
+        )
     }
 
     /// Recursively bounce ray between objects in scene, at each hit multiply current color of the ray with color of the object, or hit point on the object.
-    fn color(&self, scene: &Scene, depth: u16) -> V3<f32> {
+    ///
+    /// `scattering_pdf` - Pdf with which the material at the previous hit sampled direction of the Ray, used to weight light hit by it against light sampled directly there, or None if light couldn't be sampled there.
+    fn color<W: Hittable>(
+        &self,
+        scene: &Scene<W>,
+        depth: u16,
+        scattering_pdf: Option<f32>,
+    ) -> V3<f32> {
+        let medium = self.media.current();
+        // Free paths in a medium with colored extinction differ between channels, so the Ray has to pick one of them.
+        if self.channel.is_none() && medium.is_scattering() && medium.is_chromatic() {
+            let channel = rand::thread_rng().gen_range(0..3);
+            return self.channel_color(scene, depth, channel, scattering_pdf);
+        }
+
         // Does the intersection occur at all?
-        match scene.world.hit(self) {
+        match self.trace(scene) {
+            // Medium with colored extinction picked a channel, the Ray is traced again carrying only that channel.
+            Some(Hit {
+                channel: Some(channel),
+                ..
+            }) if self.channel.is_none() => {
+                self.channel_color(scene, depth, channel, scattering_pdf)
+            }
+            // Surface inside of a higher priority medium doesn't exist for the Ray, it only changes the media the Ray is in.
+            // Shadow rays are stopped by it, so light behind it is found only by the Ray and taken fully.
+            Some(
+                hit @ Hit {
+                    interface: Some(interface),
+                    front_face,
+                    ..
+                },
+            ) if self.media.is_false_intersection(interface, front_face) => self
+                .crossed(&hit, interface, front_face)
+                .color(scene, depth, None),
             // If ray hit some object, then we bounce that Ray from the object with updated color.
             Some(hit) => {
                 let material = scene.materials.get(hit.material);
                 match material.scatter(self, &hit) {
+                    // Invisible surface only changes the media the Ray is in, it isn't a bounce.
+                    Some(ScatterRecord {
+                        specular_ray: Some(ray),
+                        invisible: true,
+                        ..
+                    }) => ray.color(scene, depth, None),
                     // Scatter ray from a hit point
 
                     // Ray has been scattered
                     Some(scatter_record) => {
                         // Checks if ray had bounced too many times.
                         if depth < self.setting.depth {
-                            let generated_dir;
-                            let pdf_val: f32;
-
-                            // If there are lights on the scene, then sample rays to those lights and with accordance to material, else sample just from the material.
-                            match &scene.lights {
-                                Some(lights) => {
-                                    let mixture_pdf = MixturePdf::new(lights, &*scatter_record.pdf);
-                                    generated_dir = mixture_pdf.generate(hit.point);
-                                    pdf_val = mixture_pdf.value(hit.point, generated_dir);
-                                }
-                                None => {
-                                    generated_dir = scatter_record.pdf.generate(hit.point);
-                                    pdf_val = scatter_record.pdf.value(hit.point, generated_dir);
-                                }
-                            }
-
-                            let scattered_ray = Ray {
-                                origin: hit.point,
-                                end: generated_dir,
-                                time: self.time,
-                                setting: self.setting,
-                            };
-
                             match scatter_record.specular_ray {
                                 Some(ray) => {
                                     // Returns specular ray.
-                                    scatter_record
-                                        .attenuation
-                                        .hadamard(ray.color(scene, depth + 1))
+                                    scatter_record.attenuation.hadamard(ray.color(
+                                        scene,
+                                        depth + 1,
+                                        None,
+                                    ))
                                 }
                                 None => {
+                                    // Light is sampled directly and by the material, both samples are weighted by multiple importance sampling.
+                                    let direct = self
+                                        .sample_light(scene, &hit, material, &scatter_record)
+                                        .map(|shadow_ray| shadow_ray.traced_light(scene))
+                                        .unwrap_or_default();
+
+                                    let generated_dir = scatter_record.pdf.generate(hit.point);
+                                    let pdf_val =
+                                        scatter_record.pdf.value(hit.point, generated_dir);
+
+                                    let scattered_ray = self.scattered(&hit, generated_dir);
+
                                     // Returns scattered ray.
-                                    material.color_emitted(self, &hit)
-                                        + ((scatter_record.attenuation
+                                    let scattered = if pdf_val > 0.0 {
+                                        (scatter_record.attenuation
                                             * material.scattering_pdf(self, &hit, &scattered_ray))
-                                        .hadamard(scattered_ray.color(scene, depth + 1))
-                                            / pdf_val)
+                                        .hadamard(scattered_ray.color(
+                                            scene,
+                                            depth + 1,
+                                            Some(pdf_val),
+                                        )) / pdf_val
+                                    } else {
+                                        V3::default()
+                                    };
+
+                                    self.emitted(scene, material, &hit, scattering_pdf)
+                                        + direct
+                                        + scattered
                                 }
                             }
                         } else {
@@ -101,11 +253,175 @@ impl Ray<'_> {
                         }
                     }
                     // If scatter hasn't produced Ray, at example in case of absorbing the Ray, then the resulting color of the Ray is emitted light by the object.
-                    None => material.color_emitted(self, &hit),
+                    None => self.emitted(scene, material, &hit, scattering_pdf),
                 }
             }
             // No intersections occured, thus Ray came from the background.
             None => self.setting.background_color,
         }
     }
+
+    /// Returns the nearest hit of a surface, or of a particle of the medium the Ray travels through, if the particle is nearer.
+    pub(crate) fn trace<W: Hittable>(&self, scene: &Scene<W>) -> Option<Hit> {
+        let medium = self.media.current();
+
+        // Ray may collide with a particle of the medium it travels through before it reaches the nearest surface.
+        let free_path = medium.free_path(self.channel) / self.end.length();
+        match scene.world.hit(self) {
+            Some(hit) if hit.t <= free_path => Some(hit),
+            _ if free_path < self.setting.ray_time.max => Some(Hit::new(
+                self,
+                V3::new(1.0, 0.0, 0.0),
+                free_path,
+                self.point_at_param(free_path),
+                medium.phase_function,
+                0.0,
+                0.0,
+            )),
+            hit => hit,
+        }
+    }
+
+    /// Returns the Ray continuing through a surface, which doesn't exist for it, in changed media.
+    ///
+    /// `hit` - Hit of the surface.
+    ///
+    /// `interface` - Medium inside of the surface.
+    ///
+    /// `front_face` - True if the Ray enters the surface.
+    pub(crate) fn crossed(&self, hit: &Hit, interface: MediumInterface, front_face: bool) -> Self {
+        Ray {
+            origin: hit.spawn_origin(self.end),
+            media: self.media.crossed(interface, front_face),
+            ..*self
+        }
+        .with_cone(RayCone {
+            width: hit.footprint,
+            spread: self.cone.spread,
+        })
+    }
+
+    /// Returns the Ray scattered from the hit.
+    ///
+    /// `hit` - Hit from which the Ray is scattered.
+    ///
+    /// `direction` - Direction of the scattered Ray.
+    pub(crate) fn scattered(&self, hit: &Hit, direction: V3<f32>) -> Self {
+        Ray {
+            origin: hit.spawn_origin(direction),
+            end: direction,
+            time: self.time,
+            cone: RayCone {
+                width: hit.footprint,
+                spread: self.cone.spread,
+            },
+            channel: self.channel,
+            media: self.media,
+            setting: self.setting,
+            materials: self.materials,
+        }
+    }
+
+    /// Returns light emitted by the hit object, weighted against light sampled directly at the previous hit.
+    ///
+    /// `material` - Material of the hit object.
+    ///
+    /// `hit` - Hit of the Ray.
+    ///
+    /// `scattering_pdf` - Pdf of the direction of the Ray at the previous hit, see [`Ray::color`].
+    pub(crate) fn emitted<W>(
+        &self,
+        scene: &Scene<W>,
+        material: &dyn MaterialTrait,
+        hit: &Hit,
+        scattering_pdf: Option<f32>,
+    ) -> V3<f32> {
+        let emitted = material.color_emitted(self, hit);
+
+        match (&scene.lights, scattering_pdf) {
+            (Some(lights), Some(scattering_pdf)) if emitted != V3::default() => {
+                let light_pdf = lights.value(self.origin, self.end);
+                emitted * power_heuristic(scattering_pdf, light_pdf)
+            }
+            _ => emitted,
+        }
+    }
+
+    /// Returns shadow ray toward a random point on the lights, with light it brings if no object is in the way, weighted against light found by the material.
+    ///
+    /// `hit` - Hit at which light is gathered.
+    ///
+    /// `material` - Material of the hit object.
+    ///
+    /// `scatter_record` - Scattering of the Ray at the hit.
+    pub(crate) fn sample_light<W>(
+        &self,
+        scene: &Scene<W>,
+        hit: &Hit,
+        material: &dyn MaterialTrait,
+        scatter_record: &ScatterRecord,
+    ) -> Option<ShadowRay<'setting>> {
+        let lights = scene.lights.as_ref()?;
+        let direction = lights.generate(hit.point);
+        let light_pdf = lights.value(hit.point, direction);
+        if light_pdf <= 0.0 {
+            return None;
+        }
+
+        let light_ray = Ray {
+            origin: hit.spawn_origin(direction),
+            end: direction,
+            cone: RayCone::default(),
+            ..*self
+        };
+        let light_hit = lights.hit(&light_ray)?;
+
+        // Particles of the medium the Ray is in dim the light on its way
+        let light = scene
+            .materials
+            .get(light_hit.material)
+            .color_emitted(&light_ray, &light_hit)
+            .hadamard(
+                self.media
+                    .current()
+                    .transmittance(light_hit.t * direction.length()),
+            );
+        let scattering_pdf = scatter_record.pdf.value(hit.point, direction);
+
+        Some(ShadowRay {
+            ray: light_ray,
+            t_max: light_hit.t * (1.0 - SHADOW_EPSILON),
+            light: (scatter_record.attenuation * material.scattering_pdf(self, hit, &light_ray))
+                .hadamard(light)
+                * power_heuristic(light_pdf, scattering_pdf)
+                / light_pdf,
+        })
+    }
+}
+
+/// Ray toward a light, which brings the light dimmed by the objects in the way.
+pub(crate) struct ShadowRay<'setting> {
+    /// Ray going toward the light.
+    pub ray: Ray<'setting>,
+    /// Time of the Ray, at which it reaches the light.
+    pub t_max: f32,
+    /// Light brought by the Ray if nothing is in the way, already weighted.
+    pub light: V3<f32>,
+}
+
+impl ShadowRay<'_> {
+    /// Returns light brought by the Ray through the objects of the Scene, surfaces stop it and media dim it.
+    pub fn traced_light<W: Hittable>(&self, scene: &Scene<W>) -> V3<f32> {
+        self.light
+            .hadamard(scene.world.transmittance(&self.ray, self.t_max))
+    }
+}
+
+/// Returns weights of the color channels of a Ray carrying only one of them, picked with equal probability.
+///
+/// `channel` - Picked color channel.
+pub(crate) fn channel_mask(channel: u32) -> V3<f32> {
+    (0..3)
+        .map(|i| if i == channel { 3.0 } else { 0.0 })
+        .collect()
 }
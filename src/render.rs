@@ -1,7 +1,7 @@
-use crate::hittables::{BvhNode, HittableList};
+use crate::hittables::{FlatBvh, HittableList};
 use crate::material::MaterialContainer;
 use crate::misc::Ppm;
-use crate::{Camera, RaySetting, V3};
+use crate::{Camera, Hittable, RaySetting, V3};
 
 use itertools::*;
 use rand::Rng;
@@ -24,11 +24,11 @@ pub struct ImageConfig {
     pub name: &'static str,
 }
 /// Represents scene to render.
-pub struct Scene {
+pub struct Scene<W = FlatBvh> {
     /// Camera
     pub camera: Camera,
-    /// Scene to render
-    pub world: BvhNode,
+    /// Scene to render, a Bvh over all objects, or a [`Tlas`](crate::hittables::Tlas) over instances of objects, which can move between frames.
+    pub world: W,
     /// Lights
     pub lights: Option<HittableList>,
     /// Materials
@@ -45,16 +45,52 @@ pub trait Render {
     /// `image_config` - Starting configuration of rendered image.
     ///
     /// `iterations` - How many times image should be rendered, each consequtive time number of rays increases by the factor of 2.
-    fn loop_render(&self, image_config: ImageConfig, iterations: u16);
+    fn loop_render(&self, image_config: ImageConfig, iterations: u16) {
+        let mut image_config = ImageConfig { ..image_config };
+
+        for _i in 0..iterations {
+            let now = Instant::now();
+
+            let image = self.render(image_config);
+
+            image
+                .write_file(&format!(
+                    "{}_{}.ppm",
+                    image_config.name, image_config.samples_per_pixel
+                ))
+                .expect("YOU FAILED");
+            // image
+            //     .median_filter(1)
+            //     .write_file(&format!(
+            //         "{}_{}_median_1.ppm",
+            //         image_config.name, image_config.samples_per_pixel
+            //     ))
+            //     .expect("YOU FAILED");
+
+            println!(
+                "{} milliseconds for {} rays.",
+                now.elapsed().as_millis(),
+                image_config.samples_per_pixel
+            );
+
+            image_config = ImageConfig {
+                samples_per_pixel: image_config.samples_per_pixel * 2,
+                ..image_config
+            };
+        }
+    }
 }
 
-impl Render for Scene {
+impl<W: Hittable> Render for Scene<W> {
     fn render(&self, image_config: ImageConfig) -> Ppm {
         let mut output_file = Ppm::new(image_config.ny, image_config.nx);
 
         // Average color of subpixels
         let scale = 1.0 / (image_config.samples_per_pixel as f32);
 
+        // Every ray through the pixel carries the same cone
+        let pixel_cone = self.camera.pixel_cone(image_config.ny);
+
         // Create vector of pixels; first two u32 values are pixel's x and y coordinate, and V3 is color of the pixel.
         let pixels: Vec<(u32, u32, V3<u8>)> = iproduct!(0..image_config.ny, 0..image_config.nx)
             .par_bridge()
@@ -72,23 +108,12 @@ impl Render for Scene {
                         + self
                             .camera
                             .get_ray(u, v, &image_config.ray_setting)
+                            .with_cone(pixel_cone)
+                            .with_materials(&self.materials)
                             .get_color(self);
                 }
 
-                let color_without_nan =
-                    color
-                        .into_iter()
-                        .map(|color: f32| if color.is_nan() { 0.0f32 } else { color });
-
-                // Scale colors components by averaging color of subpixels
-                let scaled_color = color_without_nan.map(|component| component * scale);
-
-                // gamma correction by sqrt and clamping
-                let u8color = scaled_color
-                    .map(|component| (component.sqrt().clamp(0.0, 0.999) * 254.99) as u8)
-                    .collect();
-
-                (pixel.1, pixel.0, u8color)
+                (pixel.1, pixel.0, pixel_color(color, scale))
             })
             .collect();
 
@@ -99,39 +124,23 @@ impl Render for Scene {
 
         output_file
     }
+}
 
-    fn loop_render(&self, image_config: ImageConfig, iterations: u16) {
-        let mut image_config = ImageConfig { ..image_config };
-
-        for _i in 0..iterations {
-            let now = Instant::now();
-
-            let image = self.render(image_config);
-
-            image
-                .write_file(&format!(
-                    "{}_{}.ppm",
-                    image_config.name, image_config.samples_per_pixel
-                ))
-                .expect("YOU FAILED");
-            // image
-            //     .median_filter(1)
-            //     .write_file(&format!(
-            //         "{}_{}_median_1.ppm",
-            //         image_config.name, image_config.samples_per_pixel
-            //     ))
-            //     .expect("YOU FAILED");
-
-            println!(
-                "{} milliseconds for {} rays.",
-                now.elapsed().as_millis(),
-                image_config.samples_per_pixel
-            );
-
-            image_config = ImageConfig {
-                samples_per_pixel: image_config.samples_per_pixel * 2,
-                ..image_config
-            };
-        }
-    }
+/// Returns color of the pixel stored in the image.
+///
+/// `color` - Sum of the colors of all samples of the pixel.
+///
+/// `scale` - Inverse of the number of samples.
+pub(crate) fn pixel_color(color: V3<f32>, scale: f32) -> V3<u8> {
+    let color_without_nan = color
+        .into_iter()
+        .map(|color: f32| if color.is_nan() { 0.0f32 } else { color });
+
+    // Scale colors components by averaging color of subpixels
+    let scaled_color = color_without_nan.map(|component| component * scale);
+
+    // gamma correction by sqrt and clamping
+    scaled_color
+        .map(|component| (component.sqrt().clamp(0.0, 0.999) * 254.99) as u8)
+        .collect()
 }
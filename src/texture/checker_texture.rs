@@ -1,5 +1,5 @@
 use crate::texture::Texture;
-use crate::V3;
+use crate::{Hit, V3};
 /// Checker texture.
 #[derive(Clone)]
 pub struct CheckerTexture {
@@ -7,15 +7,41 @@ pub struct CheckerTexture {
     pub odd: Box<dyn Texture>,
     /// Texture on the even positions of the texture.
     pub even: Box<dyn Texture>,
+    /// How many times the pattern changes per unit of world coordinates.
+    pub frequency: f32,
+}
+
+impl CheckerTexture {
+    /// Returns true if the point is on the odd position of the pattern.
+    fn is_odd(&self, p: V3<f32>) -> bool {
+        let p = p * self.frequency;
+        p.x.sin() * p.y.sin() * p.z.sin() < 0.0
+    }
 }
 
 impl Texture for CheckerTexture {
     fn value(&self, u: f32, v: f32, p: V3<f32>) -> V3<f32> {
-        let sines = (10.0 * p.x).sin() * (10.0 * p.y).sin() * (10.0 * p.z).sin();
-        if sines < 0.0 {
+        if self.is_odd(p) {
             self.odd.value(u, v, p)
         } else {
             self.even.value(u, v, p)
         }
     }
+
+    fn sample(&self, hit: &Hit) -> V3<f32> {
+        let p = hit.point;
+        if self.is_odd(p) {
+            self.odd.sample(hit)
+        } else {
+            self.even.sample(hit)
+        }
+    }
+
+    fn alpha(&self, u: f32, v: f32, p: V3<f32>) -> f32 {
+        if self.is_odd(p) {
+            self.odd.alpha(u, v, p)
+        } else {
+            self.even.alpha(u, v, p)
+        }
+    }
 }
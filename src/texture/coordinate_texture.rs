@@ -0,0 +1,168 @@
+use crate::texture::Texture;
+use crate::{Hit, V3};
+
+/// Coordinates passed as the position to the wrapped texture.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CoordinateSource {
+    /// Position of the hit in the world.
+    World,
+    /// Position of the hit on the object before it was translated or rotated, so the pattern moves with the object.
+    Object,
+    /// Texture coordinates `u`, `v` as the position `(u, v, 0)`.
+    Uv,
+}
+
+impl CoordinateSource {
+    /// Returns chosen coordinates of the hit.
+    pub(crate) fn point(self, hit: &Hit) -> V3<f32> {
+        match self {
+            CoordinateSource::World => hit.point,
+            CoordinateSource::Object => hit.object_point,
+            CoordinateSource::Uv => V3::new(hit.u, hit.v, 0.0),
+        }
+    }
+
+    /// Returns chosen coordinates without hit event, where object coordinates aren't known and world position is used instead.
+    pub(crate) fn point_at(self, u: f32, v: f32, p: V3<f32>) -> V3<f32> {
+        match self {
+            CoordinateSource::World | CoordinateSource::Object => p,
+            CoordinateSource::Uv => V3::new(u, v, 0.0),
+        }
+    }
+}
+
+/// Texture evaluating another texture in different coordinates, for example to use 3D noise in texture space.
+#[derive(Clone)]
+pub struct CoordinateTexture {
+    /// Texture that is evaluated.
+    pub texture: Box<dyn Texture>,
+    /// Coordinates passed as the position to the texture.
+    pub source: CoordinateSource,
+    /// Scale of the position.
+    pub scale: f32,
+}
+
+impl CoordinateTexture {
+    /// Returns new texture evaluated in chosen coordinates.
+    ///
+    /// `texture` - Texture that is evaluated.
+    ///
+    /// `source` - Coordinates passed as the position to the texture.
+    ///
+    /// `scale` - Scale of the position, bigger values make the pattern smaller.
+    pub fn new<T: Texture + 'static>(texture: T, source: CoordinateSource, scale: f32) -> Self {
+        CoordinateTexture {
+            texture: Box::new(texture),
+            source,
+            scale,
+        }
+    }
+}
+
+impl Texture for CoordinateTexture {
+    fn value(&self, u: f32, v: f32, p: V3<f32>) -> V3<f32> {
+        self.texture
+            .value(u, v, self.source.point_at(u, v, p) * self.scale)
+    }
+
+    fn sample(&self, hit: &Hit) -> V3<f32> {
+        let point = self.source.point(hit) * self.scale;
+        self.texture.sample(&Hit { point, ..*hit })
+    }
+
+    fn alpha(&self, u: f32, v: f32, p: V3<f32>) -> f32 {
+        self.texture
+            .alpha(u, v, self.source.point_at(u, v, p) * self.scale)
+    }
+}
+
+/// Texture projected on the object along the three axes and blended by the normal, which maps images onto objects without texture coordinates.
+#[derive(Clone)]
+pub struct TriplanarTexture {
+    /// Texture that is projected, it is sampled with `u`, `v` taken from the position.
+    pub texture: Box<dyn Texture>,
+    /// Coordinates that are projected, world or object.
+    pub source: CoordinateSource,
+    /// Scale of the position, bigger values make the texture repeat more often.
+    pub scale: f32,
+    /// How sharp the transitions between projections are, higher values blend over smaller area.
+    pub sharpness: f32,
+}
+
+impl TriplanarTexture {
+    /// Returns new triplanar texture.
+    ///
+    /// `texture` - Texture that is projected.
+    ///
+    /// `source` - Coordinates that are projected.
+    ///
+    /// `scale` - Scale of the position.
+    pub fn new<T: Texture + 'static>(texture: T, source: CoordinateSource, scale: f32) -> Self {
+        TriplanarTexture {
+            texture: Box::new(texture),
+            source,
+            scale,
+            sharpness: 4.0,
+        }
+    }
+
+    /// Returns weights of projections along x, y and z axes.
+    fn weights(&self, normal: V3<f32>) -> V3<f32> {
+        let weights: V3<f32> = normal
+            .into_iter()
+            .map(|n| n.abs().powf(self.sharpness))
+            .collect();
+        weights / (weights.x + weights.y + weights.z)
+    }
+
+    /// Returns weighted sum of lookups of the three projections.
+    ///
+    /// `lookup` - Evaluates texture at `u`, `v` and the axes along which `u` and `v` grow.
+    fn blend<T>(
+        &self,
+        point: V3<f32>,
+        weights: V3<f32>,
+        lookup: impl Fn(f32, f32, V3<f32>, V3<f32>) -> T,
+    ) -> T
+    where
+        T: std::ops::Mul<f32, Output = T> + std::ops::Add<Output = T>,
+    {
+        let p = point * self.scale;
+        let (x, y, z) = (
+            V3::new(1.0, 0.0, 0.0),
+            V3::new(0.0, 1.0, 0.0),
+            V3::new(0.0, 0.0, 1.0),
+        );
+
+        lookup(p.z, p.y, z, y) * weights.x
+            + lookup(p.x, p.z, x, z) * weights.y
+            + lookup(p.x, p.y, x, y) * weights.z
+    }
+}
+
+impl Texture for TriplanarTexture {
+    /// Without the normal all projections have the same weight.
+    fn value(&self, _u: f32, _v: f32, p: V3<f32>) -> V3<f32> {
+        let weights = V3::<f32>::new(1.0, 1.0, 1.0) / 3.0;
+        self.blend(p, weights, |u, v, _, _| self.texture.value(u, v, p))
+    }
+
+    fn sample(&self, hit: &Hit) -> V3<f32> {
+        let weights = self.weights(hit.normal);
+        self.blend(self.source.point(hit), weights, |u, v, dpdu, dpdv| {
+            // Texture coordinates grow along the projection axes, which the filtering of image textures needs
+            self.texture.sample(&Hit {
+                u,
+                v,
+                dpdu: dpdu / self.scale,
+                dpdv: dpdv / self.scale,
+                ..*hit
+            })
+        })
+    }
+
+    fn alpha(&self, _u: f32, _v: f32, p: V3<f32>) -> f32 {
+        let weights = V3::<f32>::new(1.0, 1.0, 1.0) / 3.0;
+        self.blend(p, weights, |u, v, _, _| self.texture.alpha(u, v, p))
+    }
+}
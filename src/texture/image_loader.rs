@@ -0,0 +1,492 @@
+//! Decoding of image files used by textures.
+
+use crate::V3;
+use std::io::{Error, ErrorKind};
+
+/// Encoding of colors stored in the image.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSpace {
+    /// Colors are gamma encoded with sRGB transfer function, as most of 8 and 16 bit images.
+    Srgb,
+    /// Colors are proportional to the light intensity, as floating point images or data like normal maps.
+    Linear,
+}
+
+/// Image decoded into floating point colors in <0, 1> range, or unbounded for HDR images.
+pub(crate) struct DecodedImage {
+    pub width: u32,
+    pub height: u32,
+    /// Colors of pixels, row by row from the top of the image.
+    pub colors: Vec<V3<f32>>,
+    /// Opacity of pixels, 1 if the image has no alpha channel.
+    pub alphas: Vec<f32>,
+    /// Encoding of colors stored in the file.
+    pub color_space: ColorSpace,
+}
+
+/// Decodes PNG, PPM/PGM, PFM or Radiance HDR image, format is recognized by the content of the file.
+///
+/// `bytes` - Content of the image file.
+pub(crate) fn decode(bytes: &[u8]) -> Result<DecodedImage, Error> {
+    if bytes.starts_with(b"\x89PNG") {
+        decode_png(bytes)
+    } else if bytes.starts_with(b"PF") || bytes.starts_with(b"Pf") {
+        decode_pfm(bytes)
+    } else if bytes.starts_with(b"P") {
+        decode_pnm(bytes)
+    } else if bytes.starts_with(b"#?") {
+        decode_hdr(bytes)
+    } else {
+        Err(invalid_data("Unknown image format"))
+    }
+}
+
+/// Returns linear value of sRGB encoded color component.
+pub(crate) fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn invalid_data(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/// Returns number of samples of the image, if its size is valid.
+///
+/// `width`, `height` - Size of the image in pixels, read from its header.
+///
+/// `channels` - Number of samples per pixel.
+fn sample_count(width: u32, height: u32, channels: usize) -> Result<usize, Error> {
+    if width == 0 || height == 0 {
+        return Err(invalid_data("Image has no pixels"));
+    }
+    (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(channels))
+        .ok_or_else(|| invalid_data("Image is too big"))
+}
+
+/// Splits decoded samples into colors and alphas.
+///
+/// `channels` - Number of samples per pixel, 1 for grayscale, 2 for grayscale with alpha, 3 for RGB and 4 for RGBA.
+fn split_channels(samples: &[f32], channels: usize) -> (Vec<V3<f32>>, Vec<f32>) {
+    samples
+        .chunks_exact(channels)
+        .map(|pixel| match channels {
+            1 => (V3::new(pixel[0], pixel[0], pixel[0]), 1.0),
+            2 => (V3::new(pixel[0], pixel[0], pixel[0]), pixel[1]),
+            3 => (V3::new(pixel[0], pixel[1], pixel[2]), 1.0),
+            _ => (V3::new(pixel[0], pixel[1], pixel[2]), pixel[3]),
+        })
+        .unzip()
+}
+
+fn decode_png(bytes: &[u8]) -> Result<DecodedImage, Error> {
+    let mut decoder = png::Decoder::new(bytes);
+    // Palette, transparency chunk and grayscale with less than 8 bits are expanded to whole bytes
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info()?;
+
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let frame = reader.next_frame(&mut buffer)?;
+    buffer.truncate(frame.buffer_size());
+
+    let (color_type, bit_depth) = reader.output_color_type();
+    sample_count(frame.width, frame.height, color_type.samples())?;
+
+    let samples: Vec<f32> = match bit_depth {
+        png::BitDepth::Sixteen => buffer
+            .chunks_exact(2)
+            .map(|sample| f32::from(u16::from_be_bytes([sample[0], sample[1]])) / 65535.0)
+            .collect(),
+        _ => buffer
+            .iter()
+            .map(|sample| f32::from(*sample) / 255.0)
+            .collect(),
+    };
+
+    let (colors, alphas) = split_channels(&samples, color_type.samples());
+
+    // Image is assumed to be sRGB, unless its gamma says otherwise
+    let info = reader.info();
+    let color_space = match (info.srgb, info.source_gamma) {
+        (None, Some(gamma)) if (gamma.into_value() - 1.0).abs() < 0.01 => ColorSpace::Linear,
+        _ => ColorSpace::Srgb,
+    };
+
+    Ok(DecodedImage {
+        width: frame.width,
+        height: frame.height,
+        colors,
+        alphas,
+        color_space,
+    })
+}
+
+/// Reads whitespace separated tokens from the header of PPM and PFM files.
+struct HeaderReader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> HeaderReader<'a> {
+    fn token(&mut self) -> Result<&'a str, Error> {
+        // Skip whitespace and comments
+        while let Some(&byte) = self.bytes.get(self.position) {
+            if byte == b'#' {
+                while self.bytes.get(self.position).is_some_and(|&b| b != b'\n') {
+                    self.position += 1;
+                }
+            } else if byte.is_ascii_whitespace() {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+
+        let start = self.position;
+        while self
+            .bytes
+            .get(self.position)
+            .is_some_and(|b| !b.is_ascii_whitespace())
+        {
+            self.position += 1;
+        }
+
+        if start == self.position {
+            return Err(invalid_data("Unexpected end of image header"));
+        }
+
+        std::str::from_utf8(&self.bytes[start..self.position])
+            .map_err(|_| invalid_data("Image header is not valid text"))
+    }
+
+    fn number<T: std::str::FromStr>(&mut self) -> Result<T, Error> {
+        self.token()?
+            .parse()
+            .map_err(|_| invalid_data("Invalid number in image header"))
+    }
+
+    /// Returns data after the header, which is separated from it by single whitespace.
+    fn data(self) -> &'a [u8] {
+        &self.bytes[(self.position + 1).min(self.bytes.len())..]
+    }
+}
+
+fn decode_pnm(bytes: &[u8]) -> Result<DecodedImage, Error> {
+    let mut header = HeaderReader { bytes, position: 0 };
+
+    let (channels, binary) = match header.token()? {
+        "P2" => (1, false),
+        "P3" => (3, false),
+        "P5" => (1, true),
+        "P6" => (3, true),
+        _ => return Err(invalid_data("Unsupported PNM format")),
+    };
+
+    let width: u32 = header.number()?;
+    let height: u32 = header.number()?;
+    let max_value: u32 = header.number()?;
+
+    if max_value == 0 || max_value > 65535 {
+        return Err(invalid_data("Invalid maximum value of PNM image"));
+    }
+
+    let sample_count = sample_count(width, height, channels)?;
+    let scale = 1.0 / max_value as f32;
+
+    let samples: Vec<f32> = if binary {
+        let data = header.data();
+        // Samples bigger than one byte are stored as big endian
+        let bytes_per_sample = if max_value > 255 { 2 } else { 1 };
+
+        if data.len() / bytes_per_sample < sample_count {
+            return Err(invalid_data("PNM image data is too short"));
+        }
+
+        data.chunks_exact(bytes_per_sample)
+            .take(sample_count)
+            .map(|sample| match sample {
+                [high, low] => f32::from(u16::from_be_bytes([*high, *low])) * scale,
+                _ => f32::from(sample[0]) * scale,
+            })
+            .collect()
+    } else {
+        (0..sample_count)
+            .map(|_| header.number::<u32>().map(|sample| sample as f32 * scale))
+            .collect::<Result<_, _>>()?
+    };
+
+    let (colors, alphas) = split_channels(&samples, channels);
+
+    Ok(DecodedImage {
+        width,
+        height,
+        colors,
+        alphas,
+        color_space: ColorSpace::Srgb,
+    })
+}
+
+fn decode_pfm(bytes: &[u8]) -> Result<DecodedImage, Error> {
+    let mut header = HeaderReader { bytes, position: 0 };
+
+    let channels = match header.token()? {
+        "PF" => 3,
+        "Pf" => 1,
+        _ => return Err(invalid_data("Unsupported PFM format")),
+    };
+
+    let width: u32 = header.number()?;
+    let height: u32 = header.number()?;
+    // Negative scale means little endian floats
+    let scale: f32 = header.number()?;
+
+    let data = header.data();
+    let sample_count = sample_count(width, height, channels)?;
+
+    if data.len() / 4 < sample_count {
+        return Err(invalid_data("PFM image data is too short"));
+    }
+
+    let samples: Vec<f32> = data
+        .chunks_exact(4)
+        .take(sample_count)
+        .map(|sample| {
+            let sample = [sample[0], sample[1], sample[2], sample[3]];
+            if scale < 0.0 {
+                f32::from_le_bytes(sample)
+            } else {
+                f32::from_be_bytes(sample)
+            }
+        })
+        .collect();
+
+    // Rows of PFM image go from the bottom to the top
+    let row_length = width as usize * channels;
+    let samples: Vec<f32> = samples
+        .chunks_exact(row_length)
+        .rev()
+        .flatten()
+        .copied()
+        .collect();
+
+    let (colors, alphas) = split_channels(&samples, channels);
+
+    Ok(DecodedImage {
+        width,
+        height,
+        colors,
+        alphas,
+        color_space: ColorSpace::Linear,
+    })
+}
+
+fn decode_hdr(bytes: &[u8]) -> Result<DecodedImage, Error> {
+    let mut position = 0;
+    let mut next_line = || -> Result<&str, Error> {
+        let start = position;
+        let length = bytes[start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| invalid_data("Unexpected end of HDR header"))?;
+        position = start + length + 1;
+
+        std::str::from_utf8(&bytes[start..start + length])
+            .map_err(|_| invalid_data("HDR header is not valid text"))
+    };
+
+    // Header ends with empty line
+    loop {
+        let line = next_line()?.trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(format) = line.strip_prefix("FORMAT=") {
+            if format != "32-bit_rle_rgbe" {
+                return Err(invalid_data("Unsupported HDR pixel format"));
+            }
+        }
+    }
+
+    let resolution: Vec<&str> = next_line()?.split_whitespace().collect();
+    let (height, width) = match resolution.as_slice() {
+        ["-Y", height, "+X", width] => (
+            height
+                .parse::<u32>()
+                .map_err(|_| invalid_data("Invalid HDR height"))?,
+            width
+                .parse::<u32>()
+                .map_err(|_| invalid_data("Invalid HDR width"))?,
+        ),
+        _ => return Err(invalid_data("Unsupported HDR image orientation")),
+    };
+
+    let mut data = &bytes[position..];
+    // Size from the header is only trusted as far as the data could hold the pixels flat
+    let sample_count = sample_count(width, height, 3)?;
+    let mut samples = Vec::with_capacity(sample_count.min(data.len() / 4 * 3));
+    // Scanlines of run length encoding are shorter than 0x8000 pixels, flat ones need 4 bytes per pixel
+    if width as usize >= 0x8000 && data.len() / 4 < width as usize {
+        return Err(invalid_data("HDR image data is too short"));
+    }
+    let mut scanline = vec![[0u8; 4]; width as usize];
+
+    for _row in 0..height {
+        data = read_hdr_scanline(data, &mut scanline)?;
+
+        samples.extend(scanline.iter().flat_map(|&[r, g, b, exponent]| {
+            // Shared exponent is biased by 128, and mantissas are 8 bit fractions
+            let scale = if exponent == 0 {
+                0.0
+            } else {
+                2.0f32.powi(i32::from(exponent) - 136)
+            };
+            [r, g, b].map(|mantissa| f32::from(mantissa) * scale)
+        }));
+    }
+
+    let (colors, alphas) = split_channels(&samples, 3);
+
+    Ok(DecodedImage {
+        width,
+        height,
+        colors,
+        alphas,
+        color_space: ColorSpace::Linear,
+    })
+}
+
+/// Reads single row of RGBE pixels, either flat or run length encoded, returns data left after the row.
+fn read_hdr_scanline<'a>(data: &'a [u8], scanline: &mut [[u8; 4]]) -> Result<&'a [u8], Error> {
+    let too_short = || invalid_data("HDR image data is too short");
+    let width = scanline.len();
+
+    let is_run_length_encoded = (8..0x8000).contains(&width)
+        && data.len() >= 4
+        && data[0] == 2
+        && data[1] == 2
+        && (usize::from(data[2]) << 8 | usize::from(data[3])) == width;
+
+    if !is_run_length_encoded {
+        let flat = data.get(..width * 4).ok_or_else(too_short)?;
+        for (pixel, bytes) in scanline.iter_mut().zip(flat.chunks_exact(4)) {
+            pixel.copy_from_slice(bytes);
+        }
+        return Ok(&data[width * 4..]);
+    }
+
+    // Each channel is encoded separately as runs of the same value or literal values
+    let mut data = &data[4..];
+    for channel in 0..4 {
+        let mut x = 0;
+        while x < width {
+            let (&count, rest) = data.split_first().ok_or_else(too_short)?;
+
+            if count > 128 {
+                let count = usize::from(count - 128);
+                let (&value, rest) = rest.split_first().ok_or_else(too_short)?;
+                if x + count > width {
+                    return Err(invalid_data("HDR run exceeds scanline"));
+                }
+                scanline[x..x + count]
+                    .iter_mut()
+                    .for_each(|pixel| pixel[channel] = value);
+                x += count;
+                data = rest;
+            } else {
+                let count = usize::from(count);
+                if count == 0 || x + count > width {
+                    return Err(invalid_data("Invalid HDR literal run"));
+                }
+                let values = rest.get(..count).ok_or_else(too_short)?;
+                scanline[x..x + count]
+                    .iter_mut()
+                    .zip(values)
+                    .for_each(|(pixel, &value)| pixel[channel] = value);
+                x += count;
+                data = &rest[count..];
+            }
+        }
+    }
+
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ascii_ppm_test() {
+        let image = decode(b"P3\n# comment\n2 1\n255\n255 0 0  0 0 255\n").unwrap();
+
+        assert_eq!((image.width, image.height), (2, 1));
+        assert_eq!(
+            image.colors,
+            vec![V3::new(1.0, 0.0, 0.0), V3::new(0.0, 0.0, 1.0)]
+        );
+        assert_eq!(image.alphas, vec![1.0, 1.0]);
+        assert_eq!(image.color_space, ColorSpace::Srgb);
+    }
+
+    #[test]
+    fn pfm_test() {
+        let mut bytes = b"Pf\n1 2\n-1.0\n".to_vec();
+        bytes.extend_from_slice(&0.25f32.to_le_bytes());
+        bytes.extend_from_slice(&4.0f32.to_le_bytes());
+
+        let image = decode(&bytes).unwrap();
+
+        // Bottom row is stored first
+        assert_eq!(
+            image.colors,
+            vec![V3::new(4.0, 4.0, 4.0), V3::new(0.25, 0.25, 0.25)]
+        );
+        assert_eq!(image.color_space, ColorSpace::Linear);
+    }
+
+    #[test]
+    fn hdr_test() {
+        let mut bytes = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 8\n".to_vec();
+        // First row flat
+        for _ in 0..8 {
+            bytes.extend_from_slice(&[128, 64, 0, 129]);
+        }
+        // Second row run length encoded, one run for each channel
+        bytes.extend_from_slice(&[2, 2, 0, 8]);
+        bytes.extend_from_slice(&[136, 128, 136, 64, 136, 0, 136, 130]);
+
+        let image = decode(&bytes).unwrap();
+
+        assert_eq!((image.width, image.height), (8, 2));
+        assert_eq!(image.colors[0], V3::new(1.0, 0.5, 0.0));
+        assert_eq!(image.colors[15], V3::new(2.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn invalid_size_test() {
+        // Sizes whose number of samples doesn't fit into memory
+        assert!(decode(b"P6\n4294967295 4294967295\n255\n").is_err());
+        assert!(decode(b"PF\n4294967295 4294967295\n-1.0\n").is_err());
+        assert!(decode(b"#?RADIANCE\n\n-Y 4294967295 +X 4294967295\n").is_err());
+        // Big size with little data isn't allocated up front
+        assert!(decode(b"#?RADIANCE\n\n-Y 60000 +X 60000\n\x02\x02\x00\x08").is_err());
+
+        // Images without pixels
+        assert!(decode(b"P3\n0 0\n255\n").is_err());
+        assert!(decode(b"P5\n4 0\n255\n").is_err());
+        assert!(decode(b"Pf\n0 1\n-1.0\n").is_err());
+        assert!(decode(b"#?RADIANCE\n\n-Y 0 +X 8\n").is_err());
+    }
+
+    #[test]
+    fn srgb_to_linear_test() {
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+    }
+}
@@ -1,64 +1,543 @@
-use crate::texture::Texture;
-use crate::V3;
-/// Texture that represents some sort of rasterized image.
+use crate::misc::Onb;
+use crate::texture::image_loader::{self, srgb_to_linear, DecodedImage};
+use crate::texture::{ColorSpace, Texture};
+use crate::{Hit, V3};
+use std::io::Error;
+
+/// How texels are combined when the texture is sampled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TextureFilter {
+    /// Color of the closest texel.
+    Nearest,
+    /// Linear interpolation between four closest texels.
+    Bilinear,
+    /// Bilinear interpolation on two mipmap levels chosen by the size of the ray footprint, blended linearly.
+    Trilinear,
+    /// Elliptically weighted average of texels covered by the ray footprint, which stays sharp along surfaces seen at grazing angles.
+    Ewa,
+}
+
+/// What happens with texture coordinates outside of <0, 1> range.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WrapMode {
+    /// Texture is tiled.
+    Repeat,
+    /// Texture is tiled, with every other tile mirrored.
+    Mirror,
+    /// Texels on the edges of the texture are stretched.
+    Clamp,
+}
+
+/// Transformation of texture coordinates applied before sampling, coordinates are scaled, then rotated and then offset.
+#[derive(Clone, Copy, Debug)]
+pub struct UvTransform {
+    /// Scale of `u` and `v` coordinates, values bigger than 1 tile the texture more densely.
+    pub scale: (f32, f32),
+    /// Offset added to `u` and `v` coordinates.
+    pub offset: (f32, f32),
+    /// Rotation of texture coordinates in radians.
+    pub rotation: f32,
+}
+
+impl UvTransform {
+    /// Returns transformed texture coordinates.
+    fn apply(&self, u: f32, v: f32) -> (f32, f32) {
+        let (du, dv) = self.apply_vector(u, v);
+        (du + self.offset.0, dv + self.offset.1)
+    }
+
+    /// Returns transformed difference of texture coordinates, which is not affected by offset.
+    fn apply_vector(&self, u: f32, v: f32) -> (f32, f32) {
+        let (u, v) = (u * self.scale.0, v * self.scale.1);
+        let (sin, cos) = self.rotation.sin_cos();
+        (u * cos - v * sin, u * sin + v * cos)
+    }
+}
+
+impl Default for UvTransform {
+    fn default() -> Self {
+        UvTransform {
+            scale: (1.0, 1.0),
+            offset: (0.0, 0.0),
+            rotation: 0.0,
+        }
+    }
+}
+
+/// Single level of the mipmap pyramid.
 #[derive(Clone)]
-pub struct ImageTexture {
-    texture: Vec<u8>,
-    bytes_per_pixel: u32,
+struct MipLevel {
     width: u32,
     height: u32,
+    colors: Vec<V3<f32>>,
+    alphas: Vec<f32>,
+}
+
+impl MipLevel {
+    /// Returns color and alpha of the texel, coordinates outside of the level are wrapped.
+    fn texel(&self, x: i64, y: i64, wrap: WrapMode) -> (V3<f32>, f32) {
+        let x = wrap_coordinate(x, self.width as i64, wrap);
+        let y = wrap_coordinate(y, self.height as i64, wrap);
+        let index = (y * self.width as i64 + x) as usize;
+
+        (self.colors[index], self.alphas[index])
+    }
+
+    /// Returns level two times smaller in each dimension, each texel is the average of four texels of this level.
+    fn downsample(&self) -> MipLevel {
+        let width = (self.width / 2).max(1);
+        let height = (self.height / 2).max(1);
+
+        let mut colors = Vec::with_capacity((width * height) as usize);
+        let mut alphas = Vec::with_capacity((width * height) as usize);
+
+        for y in 0..height as i64 {
+            for x in 0..width as i64 {
+                let (color, alpha) = [(0, 0), (1, 0), (0, 1), (1, 1)].iter().fold(
+                    (V3::default(), 0.0),
+                    |(color, alpha), (dx, dy)| {
+                        let texel = self.texel(x * 2 + dx, y * 2 + dy, WrapMode::Clamp);
+                        (color + texel.0 * 0.25, alpha + texel.1 * 0.25)
+                    },
+                );
+                colors.push(color);
+                alphas.push(alpha);
+            }
+        }
+
+        MipLevel {
+            width,
+            height,
+            colors,
+            alphas,
+        }
+    }
+}
+
+/// Returns texel coordinate moved into <0, size) range.
+fn wrap_coordinate(coordinate: i64, size: i64, wrap: WrapMode) -> i64 {
+    match wrap {
+        WrapMode::Repeat => coordinate.rem_euclid(size),
+        WrapMode::Mirror => {
+            let mirrored = coordinate.rem_euclid(2 * size);
+            if mirrored >= size {
+                2 * size - 1 - mirrored
+            } else {
+                mirrored
+            }
+        }
+        WrapMode::Clamp => coordinate.clamp(0, size - 1),
+    }
+}
+
+/// Smallest cosine between the ray and the surface used to stretch the footprint, limits the blur at grazing angles.
+const MIN_COSINE: f32 = 0.02;
+
+/// Biggest ratio between axes of the EWA footprint, longer footprints are made wider to bound the number of texels read.
+const MAX_ANISOTROPY: f32 = 8.0;
+
+/// Texture that represents some sort of rasterized image.
+#[derive(Clone)]
+pub struct ImageTexture {
+    /// Mipmap pyramid, first level is the original image.
+    levels: Vec<MipLevel>,
+    filter: TextureFilter,
+    wrap: WrapMode,
+    uv_transform: UvTransform,
 }
 
 impl ImageTexture {
-    /// Creates new image texture.
+    /// Creates new image texture from PNG, PPM, PFM or Radiance HDR file.
+    ///
+    /// Colors of 8 and 16 bit images are converted from sRGB to linear, unless the PNG file says it is already linear.
     ///
     /// `file` - Path to image file from which Texture should be made.
-    pub fn new(file: &str) -> ImageTexture {
-        //reading texture
-        use std::fs::File;
-        let decoder = png::Decoder::new(File::open(file).unwrap());
-        let mut reader = decoder.read_info().unwrap();
-        // Allocate the output buffer.
-        let mut texture = vec![0; reader.output_buffer_size()];
-        // Read the next frame. Currently this function should only called once.
-        // The default options
-        reader.next_frame(&mut texture).unwrap();
+    pub fn new(file: &str) -> Result<ImageTexture, Error> {
+        let image = image_loader::decode(&std::fs::read(file)?)?;
+        let color_space = image.color_space;
+
+        Ok(ImageTexture::from_decoded(image, color_space))
+    }
+
+    /// Creates new image texture with colors interpreted in the given color space, for example linear for normal maps.
+    ///
+    /// `file` - Path to image file from which Texture should be made.
+    ///
+    /// `color_space` - Encoding of colors stored in the file.
+    pub fn new_with_color_space(
+        file: &str,
+        color_space: ColorSpace,
+    ) -> Result<ImageTexture, Error> {
+        let image = image_loader::decode(&std::fs::read(file)?)?;
+
+        Ok(ImageTexture::from_decoded(image, color_space))
+    }
+
+    /// Creates new image texture from content of PNG, PPM, PFM or Radiance HDR file, like an image embedded in a model.
+    ///
+    /// `bytes` - Content of the image file.
+    ///
+    /// `color_space` - Encoding of colors stored in the file.
+    pub fn from_bytes(bytes: &[u8], color_space: ColorSpace) -> Result<ImageTexture, Error> {
+        Ok(ImageTexture::from_decoded(
+            image_loader::decode(bytes)?,
+            color_space,
+        ))
+    }
+
+    fn from_decoded(image: DecodedImage, color_space: ColorSpace) -> ImageTexture {
+        let colors = match color_space {
+            ColorSpace::Srgb => image
+                .colors
+                .into_iter()
+                .map(|color| color.into_iter().map(srgb_to_linear).collect())
+                .collect(),
+            ColorSpace::Linear => image.colors,
+        };
 
         ImageTexture {
-            texture,
-            bytes_per_pixel: 4,
-            width: reader.info().width,
-            height: reader.info().height,
+            levels: vec![MipLevel {
+                width: image.width,
+                height: image.height,
+                colors,
+                alphas: image.alphas,
+            }],
+            filter: TextureFilter::Nearest,
+            wrap: WrapMode::Clamp,
+            uv_transform: UvTransform::default(),
+        }
+    }
+
+    /// Returns texture sampled with given filter. Mipmaps are generated if the filter needs them.
+    ///
+    /// `filter` - How texels are combined when the texture is sampled.
+    pub fn with_filter(mut self, filter: TextureFilter) -> Self {
+        if matches!(filter, TextureFilter::Trilinear | TextureFilter::Ewa) {
+            self.build_mipmaps();
+        }
+        self.filter = filter;
+        self
+    }
+
+    /// Returns texture with given behaviour outside of <0, 1> texture coordinates.
+    ///
+    /// `wrap` - What happens with coordinates outside of the texture.
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Returns texture with transformed texture coordinates.
+    ///
+    /// `uv_transform` - Scale, offset and rotation of texture coordinates.
+    pub fn with_uv_transform(mut self, uv_transform: UvTransform) -> Self {
+        self.uv_transform = uv_transform;
+        self
+    }
+
+    fn build_mipmaps(&mut self) {
+        self.levels.truncate(1);
+
+        while let Some(level) = self.levels.last() {
+            if level.width == 1 && level.height == 1 {
+                break;
+            }
+            let next_level = level.downsample();
+            self.levels.push(next_level);
         }
     }
 
-    fn clamp<T: PartialOrd>(num: T, x: T, y: T) -> T {
-        if num < x {
-            return x;
+    /// Returns filtered color and alpha of the texture.
+    ///
+    /// `u`, `v` - Position on the texture.
+    ///
+    /// `footprint` - Two axes of the ellipse covered by the ray on the texture, if known.
+    fn lookup(&self, u: f32, v: f32, footprint: Option<[(f32, f32); 2]>) -> (V3<f32>, f32) {
+        let (u, v) = self.uv_transform.apply(u, v);
+        let footprint =
+            footprint.map(|axes| axes.map(|(du, dv)| self.uv_transform.apply_vector(du, dv)));
+
+        match (self.filter, footprint) {
+            (TextureFilter::Nearest, _) => self.nearest(u, v),
+            (TextureFilter::Bilinear, _) | (_, None) => self.bilinear(0, u, v),
+            (TextureFilter::Trilinear, Some(axes)) => {
+                let base = &self.levels[0];
+                let width = axes
+                    .iter()
+                    .map(|(du, dv)| (du * base.width as f32).hypot(dv * base.height as f32) * 2.0)
+                    .fold(0.0, f32::max);
+
+                self.trilinear(width.log2(), u, v)
+            }
+            (TextureFilter::Ewa, Some(axes)) => self.ewa(axes, u, v),
         }
-        if num > y {
-            return y;
+    }
+
+    fn nearest(&self, u: f32, v: f32) -> (V3<f32>, f32) {
+        let level = &self.levels[0];
+        let x = (u * level.width as f32).floor() as i64;
+        let y = ((1.0 - v) * level.height as f32).floor() as i64;
+
+        level.texel(x, y, self.wrap)
+    }
+
+    fn bilinear(&self, level: usize, u: f32, v: f32) -> (V3<f32>, f32) {
+        let level = &self.levels[level];
+
+        // Texel centers lie on whole coordinates
+        let x = u * level.width as f32 - 0.5;
+        let y = (1.0 - v) * level.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (dx, dy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        [
+            (0, 0, (1.0 - dx) * (1.0 - dy)),
+            (1, 0, dx * (1.0 - dy)),
+            (0, 1, (1.0 - dx) * dy),
+            (1, 1, dx * dy),
+        ]
+        .iter()
+        .fold((V3::default(), 0.0), |(color, alpha), (ox, oy, weight)| {
+            let texel = level.texel(x0 + ox, y0 + oy, self.wrap);
+            (color + texel.0 * *weight, alpha + texel.1 * weight)
+        })
+    }
+
+    fn trilinear(&self, lod: f32, u: f32, v: f32) -> (V3<f32>, f32) {
+        let lod = lod.max(0.0).min((self.levels.len() - 1) as f32);
+        let lower_level = lod.floor() as usize;
+        let blend = lod - lod.floor();
+
+        if blend == 0.0 || lower_level + 1 >= self.levels.len() {
+            self.bilinear(lower_level, u, v)
+        } else {
+            let lower = self.bilinear(lower_level, u, v);
+            let upper = self.bilinear(lower_level + 1, u, v);
+            (
+                lower.0 * (1.0 - blend) + upper.0 * blend,
+                lower.1 * (1.0 - blend) + upper.1 * blend,
+            )
         }
-        num
     }
+
+    fn ewa(&self, axes: [(f32, f32); 2], u: f32, v: f32) -> (V3<f32>, f32) {
+        let base = &self.levels[0];
+        let to_texels = |(du, dv): (f32, f32)| (du * base.width as f32, dv * base.height as f32);
+        let length = |(x, y): (f32, f32)| x.hypot(y);
+
+        let (mut major, mut minor) = (to_texels(axes[0]), to_texels(axes[1]));
+        if length(major) < length(minor) {
+            std::mem::swap(&mut major, &mut minor);
+        }
+
+        // Too eccentric footprint is made wider, blurring the texture instead of reading lots of texels
+        let (major_length, minor_length) = (length(major), length(minor));
+        if minor_length * MAX_ANISOTROPY < major_length && minor_length > 0.0 {
+            let scale = major_length / (minor_length * MAX_ANISOTROPY);
+            minor = (minor.0 * scale, minor.1 * scale);
+        }
+
+        let minor_length = length(minor);
+        if minor_length == 0.0 {
+            return self.bilinear(0, u, v);
+        }
+
+        let lod = minor_length
+            .log2()
+            .max(0.0)
+            .min((self.levels.len() - 1) as f32);
+        let lower_level = lod.floor() as usize;
+        let blend = lod - lod.floor();
+
+        let lower = self.ewa_level(lower_level, major, minor, u, v);
+        if blend == 0.0 || lower_level + 1 >= self.levels.len() {
+            lower
+        } else {
+            let upper = self.ewa_level(lower_level + 1, major, minor, u, v);
+            (
+                lower.0 * (1.0 - blend) + upper.0 * blend,
+                lower.1 * (1.0 - blend) + upper.1 * blend,
+            )
+        }
+    }
+
+    /// Elliptically weighted average with gaussian filter on single mipmap level.
+    ///
+    /// `major`, `minor` - Axes of the footprint ellipse in texels of the first level.
+    fn ewa_level(
+        &self,
+        level: usize,
+        major: (f32, f32),
+        minor: (f32, f32),
+        u: f32,
+        v: f32,
+    ) -> (V3<f32>, f32) {
+        let level_scale = 0.5f32.powi(level as i32);
+        let texture_level = &self.levels[level];
+
+        // Coordinates on this level, texel centers lie on whole coordinates
+        let s = u * texture_level.width as f32 - 0.5;
+        let t = (1.0 - v) * texture_level.height as f32 - 0.5;
+
+        // Image rows go top to bottom, so `v` axis is flipped
+        let (major_s, major_t) = (major.0 * level_scale, -major.1 * level_scale);
+        let (minor_s, minor_t) = (minor.0 * level_scale, -minor.1 * level_scale);
+
+        // Implicit equation of the ellipse, texels inside it satisfy a*s^2 + b*s*t + c*t^2 < 1
+        let mut a = major_t * major_t + minor_t * minor_t + 1.0;
+        let mut b = -2.0 * (major_s * major_t + minor_s * minor_t);
+        let mut c = major_s * major_s + minor_s * minor_s + 1.0;
+        let inv_f = 1.0 / (a * c - b * b * 0.25);
+        a *= inv_f;
+        b *= inv_f;
+        c *= inv_f;
+
+        // Bounding box of the ellipse
+        let determinant = -b * b + 4.0 * a * c;
+        let inv_determinant = 1.0 / determinant;
+        let s_radius = 2.0 * inv_determinant * (determinant * c).sqrt();
+        let t_radius = 2.0 * inv_determinant * (determinant * a).sqrt();
+        let (s0, s1) = ((s - s_radius).ceil() as i64, (s + s_radius).floor() as i64);
+        let (t0, t1) = ((t - t_radius).ceil() as i64, (t + t_radius).floor() as i64);
+
+        let alpha = 2.0f32;
+        let edge_weight = (-alpha).exp();
+
+        let mut color = V3::<f32>::default();
+        let mut opacity = 0.0;
+        let mut weight_sum = 0.0;
+
+        for it in t0..=t1 {
+            let tt = it as f32 - t;
+            for is in s0..=s1 {
+                let ss = is as f32 - s;
+                let radius_squared = a * ss * ss + b * ss * tt + c * tt * tt;
+
+                if radius_squared < 1.0 {
+                    let weight = (-alpha * radius_squared).exp() - edge_weight;
+                    let texel = texture_level.texel(is, it, self.wrap);
+
+                    color = color + texel.0 * weight;
+                    opacity += texel.1 * weight;
+                    weight_sum += weight;
+                }
+            }
+        }
+
+        if weight_sum > 0.0 {
+            (color / weight_sum, opacity / weight_sum)
+        } else {
+            self.bilinear(level, u, v)
+        }
+    }
+}
+
+/// Returns two axes of the ellipse covered by the ray cone on the surface, mapped into texture coordinates.
+fn uv_footprint(hit: &Hit) -> Option<[(f32, f32); 2]> {
+    if hit.footprint <= 0.0 || hit.dpdu.near_zero() || hit.dpdv.near_zero() {
+        return None;
+    }
+
+    let normal = hit.normal.norm();
+    let direction = hit.direction.norm();
+    let cosine = direction.dot(normal).abs().max(MIN_COSINE);
+    let radius = hit.footprint * 0.5;
+
+    // Footprint on the surface is stretched along the direction of the ray projected on the surface
+    let projected = direction - normal * direction.dot(normal);
+    let major = if projected.near_zero() {
+        Onb::build_from_w(&normal).u()
+    } else {
+        projected.norm()
+    };
+    let minor = normal.cross(major);
+
+    // Vector on the surface is expressed in tangents by solving the least squares system of dpdu and dpdv
+    let (a, b, c) = (
+        hit.dpdu.dot(hit.dpdu),
+        hit.dpdu.dot(hit.dpdv),
+        hit.dpdv.dot(hit.dpdv),
+    );
+    let determinant = a * c - b * b;
+    if determinant.abs() < 1e-12 {
+        return None;
+    }
+
+    Some([major * (radius / cosine), minor * radius].map(|axis| {
+        let (du, dv) = (axis.dot(hit.dpdu), axis.dot(hit.dpdv));
+        (
+            (c * du - b * dv) / determinant,
+            (a * dv - b * du) / determinant,
+        )
+    }))
 }
 
 impl Texture for ImageTexture {
     fn value(&self, ui: f32, vi: f32, _pp: V3<f32>) -> V3<f32> {
-        let u = ((ImageTexture::clamp(ui, 0.0, 1.0)) * (self.width as f32)) as u32;
-        let v = ((1.0 - ImageTexture::clamp(vi, 0.0, 1.0)) * (self.height as f32)) as u32;
+        self.lookup(ui, vi, None).0
+    }
 
-        let u = ImageTexture::clamp(u, 0, self.width - 1);
-        let v = ImageTexture::clamp(v, 0, self.height - 1);
+    fn sample(&self, hit: &Hit) -> V3<f32> {
+        self.lookup(hit.u, hit.v, uv_footprint(hit)).0
+    }
 
-        let color_scale = 1.0 / 255.0;
+    fn alpha(&self, ui: f32, vi: f32, _pp: V3<f32>) -> f32 {
+        self.lookup(ui, vi, None).1
+    }
+}
 
-        let pixel = (v * self.width * self.bytes_per_pixel + u * self.bytes_per_pixel) as usize;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        V3::new(
-            (f32::from(self.texture[pixel])) * color_scale,
-            (f32::from(self.texture[pixel + 1])) * color_scale,
-            (f32::from(self.texture[pixel + 2])) * color_scale,
-        )
+    #[test]
+    fn wrap_coordinate_test() {
+        let repeated: Vec<i64> = (-3..7)
+            .map(|x| wrap_coordinate(x, 3, WrapMode::Repeat))
+            .collect();
+        assert_eq!(repeated, vec![0, 1, 2, 0, 1, 2, 0, 1, 2, 0]);
+
+        let mirrored: Vec<i64> = (-3..7)
+            .map(|x| wrap_coordinate(x, 3, WrapMode::Mirror))
+            .collect();
+        assert_eq!(mirrored, vec![2, 1, 0, 0, 1, 2, 2, 1, 0, 0]);
+
+        let clamped: Vec<i64> = (-3..7)
+            .map(|x| wrap_coordinate(x, 3, WrapMode::Clamp))
+            .collect();
+        assert_eq!(clamped, vec![0, 0, 0, 0, 1, 2, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn mipmap_test() {
+        let level = MipLevel {
+            width: 2,
+            height: 2,
+            colors: vec![
+                V3::new(1.0, 0.0, 0.0),
+                V3::new(0.0, 1.0, 0.0),
+                V3::new(0.0, 0.0, 1.0),
+                V3::new(1.0, 1.0, 1.0),
+            ],
+            alphas: vec![1.0, 0.0, 1.0, 0.0],
+        };
+
+        let texture = ImageTexture {
+            levels: vec![level],
+            filter: TextureFilter::Nearest,
+            wrap: WrapMode::Repeat,
+            uv_transform: UvTransform::default(),
+        }
+        .with_filter(TextureFilter::Trilinear);
+
+        assert_eq!(texture.levels.len(), 2);
+        assert_eq!(texture.levels[1].colors, vec![V3::new(0.5, 0.5, 0.5)]);
+        assert_eq!(texture.levels[1].alphas, vec![0.5]);
+
+        // Sampling between texel centers averages all four texels
+        let (color, alpha) = texture.bilinear(0, 0.5, 0.5);
+        assert_eq!(color, V3::new(0.5, 0.5, 0.5));
+        assert_eq!(alpha, 0.5);
     }
 }
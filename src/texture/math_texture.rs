@@ -0,0 +1,399 @@
+use crate::misc::Interval;
+use crate::texture::Texture;
+use crate::{Hit, V3};
+
+/// Returns mean of color channels, which is used where a texture acts as a single number.
+fn intensity(color: V3<f32>) -> f32 {
+    color.into_iter().sum::<f32>() / 3.0
+}
+
+/// Texture blending two textures, the blend factor is also a texture.
+#[derive(Clone)]
+pub struct MixTexture {
+    /// Texture used where factor is 0.
+    pub a: Box<dyn Texture>,
+    /// Texture used where factor is 1.
+    pub b: Box<dyn Texture>,
+    /// Mean of channels of this texture, clamped to <0, 1>, is the weight of `b`.
+    pub factor: Box<dyn Texture>,
+}
+
+impl MixTexture {
+    /// Returns new texture blending two textures.
+    ///
+    /// `a`, `b` - Textures that are blended.
+    ///
+    /// `factor` - Weight of `b` texture.
+    pub fn new<A: Texture + 'static, B: Texture + 'static, F: Texture + 'static>(
+        a: A,
+        b: B,
+        factor: F,
+    ) -> Self {
+        MixTexture {
+            a: Box::new(a),
+            b: Box::new(b),
+            factor: Box::new(factor),
+        }
+    }
+
+    fn mix<T>(&self, factor: V3<f32>, lookup: impl Fn(&dyn Texture) -> T) -> T
+    where
+        T: std::ops::Mul<f32, Output = T> + std::ops::Add<Output = T>,
+    {
+        let factor = intensity(factor).clamp(0.0, 1.0);
+        lookup(self.a.as_ref()) * (1.0 - factor) + lookup(self.b.as_ref()) * factor
+    }
+}
+
+impl Texture for MixTexture {
+    fn value(&self, u: f32, v: f32, p: V3<f32>) -> V3<f32> {
+        self.mix(self.factor.value(u, v, p), |texture| texture.value(u, v, p))
+    }
+
+    fn sample(&self, hit: &Hit) -> V3<f32> {
+        self.mix(self.factor.sample(hit), |texture| texture.sample(hit))
+    }
+
+    fn alpha(&self, u: f32, v: f32, p: V3<f32>) -> f32 {
+        self.mix(self.factor.value(u, v, p), |texture| texture.alpha(u, v, p))
+    }
+}
+
+/// Operation combining channels of two textures.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MathOperation {
+    /// Sum of colors.
+    Add,
+    /// Difference of colors.
+    Subtract,
+    /// Product of colors.
+    Multiply,
+    /// Quotient of colors, division by zero gives zero.
+    Divide,
+    /// Smaller of the two values.
+    Min,
+    /// Bigger of the two values.
+    Max,
+}
+
+impl MathOperation {
+    fn apply(self, a: V3<f32>, b: V3<f32>) -> V3<f32> {
+        match self {
+            MathOperation::Add => a + b,
+            MathOperation::Subtract => a - b,
+            MathOperation::Multiply => a.hadamard(b),
+            MathOperation::Divide => a
+                .into_iter()
+                .zip(b)
+                .map(|(a, b)| if b == 0.0 { 0.0 } else { a / b })
+                .collect(),
+            MathOperation::Min => a.min(b),
+            MathOperation::Max => a.max(b),
+        }
+    }
+}
+
+/// Texture combining channels of two textures with a math operation.
+#[derive(Clone)]
+pub struct MathTexture {
+    /// First operand, its opacity is the opacity of the result.
+    pub a: Box<dyn Texture>,
+    /// Second operand.
+    pub b: Box<dyn Texture>,
+    /// Operation applied to the operands.
+    pub operation: MathOperation,
+}
+
+impl MathTexture {
+    /// Returns new texture combining two textures.
+    ///
+    /// `a`, `b` - Operands of the operation.
+    ///
+    /// `operation` - Operation applied to each channel.
+    pub fn new<A: Texture + 'static, B: Texture + 'static>(
+        a: A,
+        b: B,
+        operation: MathOperation,
+    ) -> Self {
+        MathTexture {
+            a: Box::new(a),
+            b: Box::new(b),
+            operation,
+        }
+    }
+}
+
+impl Texture for MathTexture {
+    fn value(&self, u: f32, v: f32, p: V3<f32>) -> V3<f32> {
+        self.operation
+            .apply(self.a.value(u, v, p), self.b.value(u, v, p))
+    }
+
+    fn sample(&self, hit: &Hit) -> V3<f32> {
+        self.operation.apply(self.a.sample(hit), self.b.sample(hit))
+    }
+
+    fn alpha(&self, u: f32, v: f32, p: V3<f32>) -> f32 {
+        self.a.alpha(u, v, p)
+    }
+}
+
+/// Texture mapping mean of channels of the input texture to colors interpolated between stops.
+#[derive(Clone)]
+pub struct ColorRamp {
+    /// Texture which value is mapped.
+    pub input: Box<dyn Texture>,
+    /// Positions and colors of the stops, sorted by position.
+    stops: Vec<(f32, V3<f32>)>,
+}
+
+impl ColorRamp {
+    /// Returns new color ramp.
+    ///
+    /// `input` - Texture which value is mapped.
+    ///
+    /// `stops` - Positions and colors of the ramp, values before the first and after the last stop get the color of that stop.
+    pub fn new<T: Texture + 'static>(input: T, mut stops: Vec<(f32, V3<f32>)>) -> Self {
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        ColorRamp {
+            input: Box::new(input),
+            stops,
+        }
+    }
+
+    fn map(&self, value: f32) -> V3<f32> {
+        let next = self.stops.partition_point(|stop| stop.0 < value);
+
+        match (self.stops.get(next.wrapping_sub(1)), self.stops.get(next)) {
+            (Some(&(start, from)), Some(&(end, to))) => {
+                let t = (value - start) / (end - start);
+                from * (1.0 - t) + to * t
+            }
+            (Some(&(_, color)), None) | (None, Some(&(_, color))) => color,
+            (None, None) => V3::default(),
+        }
+    }
+}
+
+impl Texture for ColorRamp {
+    fn value(&self, u: f32, v: f32, p: V3<f32>) -> V3<f32> {
+        self.map(intensity(self.input.value(u, v, p)))
+    }
+
+    fn sample(&self, hit: &Hit) -> V3<f32> {
+        self.map(intensity(self.input.sample(hit)))
+    }
+
+    fn alpha(&self, u: f32, v: f32, p: V3<f32>) -> f32 {
+        self.input.alpha(u, v, p)
+    }
+}
+
+/// Texture with inverted colors of another texture, each channel is subtracted from 1.
+#[derive(Clone)]
+pub struct InvertTexture {
+    /// Texture which colors are inverted.
+    pub texture: Box<dyn Texture>,
+}
+
+impl InvertTexture {
+    /// Returns new inverted texture.
+    ///
+    /// `texture` - Texture which colors are inverted.
+    pub fn new<T: Texture + 'static>(texture: T) -> Self {
+        InvertTexture {
+            texture: Box::new(texture),
+        }
+    }
+}
+
+impl Texture for InvertTexture {
+    fn value(&self, u: f32, v: f32, p: V3<f32>) -> V3<f32> {
+        V3::new(1.0, 1.0, 1.0) - self.texture.value(u, v, p)
+    }
+
+    fn sample(&self, hit: &Hit) -> V3<f32> {
+        V3::new(1.0, 1.0, 1.0) - self.texture.sample(hit)
+    }
+
+    fn alpha(&self, u: f32, v: f32, p: V3<f32>) -> f32 {
+        self.texture.alpha(u, v, p)
+    }
+}
+
+/// Texture with hue, saturation and value of another texture adjusted.
+#[derive(Clone)]
+pub struct HsvTexture {
+    /// Texture which colors are adjusted.
+    pub texture: Box<dyn Texture>,
+    /// Shift of hue, 1 is full turn around the color wheel.
+    pub hue: f32,
+    /// Multiplier of saturation.
+    pub saturation: f32,
+    /// Multiplier of value.
+    pub value: f32,
+}
+
+impl HsvTexture {
+    /// Returns new texture with adjusted colors.
+    ///
+    /// `texture` - Texture which colors are adjusted.
+    ///
+    /// `hue` - Shift of hue, 1 is full turn around the color wheel.
+    ///
+    /// `saturation`, `value` - Multipliers of saturation and value.
+    pub fn new<T: Texture + 'static>(texture: T, hue: f32, saturation: f32, value: f32) -> Self {
+        HsvTexture {
+            texture: Box::new(texture),
+            hue,
+            saturation,
+            value,
+        }
+    }
+
+    fn adjust(&self, color: V3<f32>) -> V3<f32> {
+        let (hue, saturation, value) = rgb_to_hsv(color);
+        hsv_to_rgb(
+            (hue + self.hue).rem_euclid(1.0),
+            (saturation * self.saturation).clamp(0.0, 1.0),
+            value * self.value,
+        )
+    }
+}
+
+impl Texture for HsvTexture {
+    fn value(&self, u: f32, v: f32, p: V3<f32>) -> V3<f32> {
+        self.adjust(self.texture.value(u, v, p))
+    }
+
+    fn sample(&self, hit: &Hit) -> V3<f32> {
+        self.adjust(self.texture.sample(hit))
+    }
+
+    fn alpha(&self, u: f32, v: f32, p: V3<f32>) -> f32 {
+        self.texture.alpha(u, v, p)
+    }
+}
+
+/// Returns hue in <0, 1) range, saturation and value of RGB color.
+fn rgb_to_hsv(color: V3<f32>) -> (f32, f32, f32) {
+    let max = color.max_component();
+    let delta = max - color.min_component();
+
+    if delta <= 0.0 {
+        return (0.0, 0.0, max);
+    }
+
+    let hue = if max == color.x {
+        ((color.y - color.z) / delta).rem_euclid(6.0)
+    } else if max == color.y {
+        (color.z - color.x) / delta + 2.0
+    } else {
+        (color.x - color.y) / delta + 4.0
+    };
+
+    (hue / 6.0, delta / max, max)
+}
+
+/// Returns RGB color from hue in <0, 1) range, saturation and value.
+fn hsv_to_rgb(hue: f32, saturation: f32, value: f32) -> V3<f32> {
+    let channel = |n: f32| {
+        let k = (n + hue * 6.0) % 6.0;
+        value - value * saturation * k.min(4.0 - k).clamp(0.0, 1.0)
+    };
+
+    V3::new(channel(5.0), channel(3.0), channel(1.0))
+}
+
+/// Texture linearly mapping each channel of another texture from one range to another.
+#[derive(Clone)]
+pub struct RemapTexture {
+    /// Texture which channels are remapped.
+    pub texture: Box<dyn Texture>,
+    /// Range of input values.
+    pub from: Interval,
+    /// Range of output values.
+    pub to: Interval,
+    /// Whether the output is clamped to `to` range.
+    pub clamp: bool,
+}
+
+impl RemapTexture {
+    /// Returns new remapping texture.
+    ///
+    /// `texture` - Texture which channels are remapped.
+    ///
+    /// `from` - Range of input values.
+    ///
+    /// `to` - Range of output values.
+    pub fn new<T: Texture + 'static>(texture: T, from: Interval, to: Interval) -> Self {
+        RemapTexture {
+            texture: Box::new(texture),
+            from,
+            to,
+            clamp: true,
+        }
+    }
+
+    fn remap(&self, color: V3<f32>) -> V3<f32> {
+        color
+            .into_iter()
+            .map(|channel| {
+                let t = (channel - self.from.min) / (self.from.max - self.from.min);
+                let t = if self.clamp { t.clamp(0.0, 1.0) } else { t };
+                self.to.min + t * (self.to.max - self.to.min)
+            })
+            .collect()
+    }
+}
+
+impl Texture for RemapTexture {
+    fn value(&self, u: f32, v: f32, p: V3<f32>) -> V3<f32> {
+        self.remap(self.texture.value(u, v, p))
+    }
+
+    fn sample(&self, hit: &Hit) -> V3<f32> {
+        self.remap(self.texture.sample(hit))
+    }
+
+    fn alpha(&self, u: f32, v: f32, p: V3<f32>) -> f32 {
+        self.texture.alpha(u, v, p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture::ConstantTexture;
+
+    #[test]
+    fn hsv_round_trip_test() {
+        for color in [
+            V3::new(0.8, 0.2, 0.1),
+            V3::new(0.1, 0.7, 0.3),
+            V3::new(0.2, 0.3, 0.9),
+            V3::new(0.5, 0.5, 0.5),
+        ] {
+            let (hue, saturation, value) = rgb_to_hsv(color);
+            let result = hsv_to_rgb(hue, saturation, value);
+            assert!((result - color).length() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn color_ramp_test() {
+        let ramp = |value: f32| {
+            ColorRamp::new(
+                ConstantTexture {
+                    color: V3::new(value, value, value),
+                },
+                vec![(1.0, V3::new(0.0, 0.0, 1.0)), (0.0, V3::new(1.0, 0.0, 0.0))],
+            )
+            .value(0.0, 0.0, V3::default())
+        };
+
+        assert_eq!(ramp(-1.0), V3::new(1.0, 0.0, 0.0));
+        assert_eq!(ramp(0.25), V3::new(0.75, 0.0, 0.25));
+        assert_eq!(ramp(2.0), V3::new(0.0, 0.0, 1.0));
+    }
+}
@@ -2,17 +2,31 @@
 
 mod checker_texture;
 mod constant_texture;
+mod coordinate_texture;
+mod image_loader;
 mod image_texture;
+mod math_texture;
+mod noise_texture;
+mod opacity;
+mod pattern_texture;
 mod perlin_noise_texture;
 mod plasma_texture;
 
 pub use checker_texture::CheckerTexture;
 pub use constant_texture::ConstantTexture;
-pub use image_texture::ImageTexture;
+pub use coordinate_texture::{CoordinateSource, CoordinateTexture, TriplanarTexture};
+pub use image_loader::ColorSpace;
+pub use image_texture::{ImageTexture, TextureFilter, UvTransform, WrapMode};
+pub use math_texture::{
+    ColorRamp, HsvTexture, InvertTexture, MathOperation, MathTexture, MixTexture, RemapTexture,
+};
+pub use noise_texture::{NoisePattern, NoiseTexture};
+pub use opacity::{AlphaMode, Opacity};
+pub use pattern_texture::{Pattern, PatternTexture};
 pub use perlin_noise_texture::PerlinNoiseTexture;
 pub use plasma_texture::PlasmaTexture;
 
-use crate::V3;
+use crate::{Hit, V3};
 use objekt_clonable::*;
 /// Objects implementing this trait behaves like texture.
 #[clonable]
@@ -23,6 +37,22 @@ pub trait Texture: Send + Sync + Clone + TextureClone {
     ///
     /// `p` - Position in the world of the point on the texture.
     fn value(&self, u: f32, v: f32, p: V3<f32>) -> V3<f32>;
+
+    /// Returns color of the Texture at the hit point, filtered over the area covered by the ray cone if the Texture supports filtering.
+    ///
+    /// `hit` - Hit event at which the Texture is sampled.
+    fn sample(&self, hit: &Hit) -> V3<f32> {
+        self.value(hit.u, hit.v, hit.point)
+    }
+
+    /// Returns opacity of the Texture at the UV position, from 0 for fully transparent to 1 for opaque.
+    ///
+    /// `u`, `v` - Position on the texture.
+    ///
+    /// `p` - Position in the world of the point on the texture.
+    fn alpha(&self, _u: f32, _v: f32, _p: V3<f32>) -> f32 {
+        1.0
+    }
 }
 /// Needed to clone trait object, which are implementations of Texture trait, into threads for parralelization.
 pub trait TextureClone {
@@ -0,0 +1,91 @@
+use crate::misc::FractalNoise;
+use crate::texture::Texture;
+use crate::V3;
+
+/// Shape made from the fractal noise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NoisePattern {
+    /// Noise itself, mapped to <0, 1> range.
+    Plain,
+    /// Veins running across the x axis, distorted by the noise.
+    Marble {
+        /// How many veins there are per unit of scaled coordinates.
+        frequency: f32,
+        /// How much the noise bends the veins.
+        distortion: f32,
+    },
+    /// Growth rings around the y axis, distorted by the noise.
+    Wood {
+        /// How many rings there are per unit of scaled coordinates.
+        rings: f32,
+        /// How much the noise bends the rings.
+        distortion: f32,
+    },
+    /// Clouds with clear gaps between them.
+    Clouds {
+        /// Noise value under which there are no clouds, from 0 for overcast to 1 for clear sky.
+        coverage: f32,
+        /// Width of the transition between clear sky and clouds.
+        softness: f32,
+    },
+}
+
+/// Grayscale texture made from fractal noise, for colors it can be used as input of a [`ColorRamp`](crate::texture::ColorRamp).
+#[derive(Clone)]
+pub struct NoiseTexture {
+    /// Source of the noise.
+    pub noise: FractalNoise,
+    /// Shape made from the noise.
+    pub pattern: NoisePattern,
+    /// Scale of the position, bigger values make the pattern smaller.
+    pub scale: f32,
+}
+
+impl NoiseTexture {
+    /// Returns new noise texture.
+    ///
+    /// `noise` - Source of the noise.
+    ///
+    /// `pattern` - Shape made from the noise.
+    ///
+    /// `scale` - Scale of the position.
+    pub fn new(noise: FractalNoise, pattern: NoisePattern, scale: f32) -> Self {
+        NoiseTexture {
+            noise,
+            pattern,
+            scale,
+        }
+    }
+
+    /// Returns value of the pattern in <0, 1> range.
+    fn intensity(&self, p: V3<f32>) -> f32 {
+        let p = p * self.scale;
+        let noise = self.noise.value(p);
+
+        match self.pattern {
+            NoisePattern::Plain => 0.5 * (noise + 1.0),
+            NoisePattern::Marble {
+                frequency,
+                distortion,
+            } => 0.5 * (1.0 + ((p.x + noise * distortion) * frequency).sin()),
+            NoisePattern::Wood { rings, distortion } => {
+                let radius = (p.x * p.x + p.z * p.z).sqrt() + noise * distortion;
+                let ring = radius * rings;
+                ring - ring.floor()
+            }
+            NoisePattern::Clouds { coverage, softness } => {
+                let t =
+                    ((0.5 * (noise + 1.0) - coverage) / softness.max(f32::EPSILON)).clamp(0.0, 1.0);
+                t * t * (3.0 - 2.0 * t)
+            }
+        }
+        .clamp(0.0, 1.0)
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f32, _v: f32, p: V3<f32>) -> V3<f32> {
+        let intensity = self.intensity(p);
+        V3::new(intensity, intensity, intensity)
+    }
+}
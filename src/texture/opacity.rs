@@ -0,0 +1,176 @@
+use crate::texture::Texture;
+use crate::V3;
+use rand::Rng;
+use std::sync::Arc;
+
+/// Way in which alpha of the texture decides if the surface is hit.
+#[derive(Clone, Copy)]
+pub enum AlphaMode {
+    /// Surface is hit if alpha is equal or greater than the threshold.
+    Threshold(f32),
+    /// Surface is hit with probability equal to alpha, which gives correct partial transparency after averaging samples.
+    Stochastic,
+}
+
+/// Cutout mask for the surface, the alpha channel of the texture tells which parts of the surface are transparent for rays.
+#[derive(Clone)]
+pub struct Opacity {
+    /// Texture from which alpha is taken, shared between all primitives using it.
+    pub texture: Arc<dyn Texture + Sync + Send>,
+    /// How alpha is turned into hit or miss.
+    pub mode: AlphaMode,
+}
+
+impl Opacity {
+    /// Returns new opacity mask.
+    ///
+    /// `texture` - Texture from which alpha is taken.
+    ///
+    /// `mode` - How alpha is turned into hit or miss.
+    pub fn new<T: Texture + 'static>(texture: T, mode: AlphaMode) -> Self {
+        Opacity {
+            texture: Arc::new(texture),
+            mode,
+        }
+    }
+
+    /// Checks if the ray should hit the surface at given position, or pass through it.
+    ///
+    /// `u`, `v` - Position on the texture.
+    ///
+    /// `p` - Position in the world of the hit point.
+    pub fn is_opaque(&self, u: f32, v: f32, p: V3<f32>) -> bool {
+        let alpha = self.texture.alpha(u, v, p);
+
+        match self.mode {
+            AlphaMode::Threshold(threshold) => alpha >= threshold,
+            AlphaMode::Stochastic => alpha >= 1.0 || rand::thread_rng().gen::<f32>() < alpha,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{Lambertian, MaterialContainer};
+    use crate::misc::Interval;
+    use crate::primitive::{Mesh, MeshData, Quad, Triangle};
+    use crate::texture::ConstantTexture;
+    use crate::{Hittable, Ray, RaySetting};
+
+    /// Texture with given alpha where `u` is below one half, opaque elsewhere.
+    #[derive(Clone)]
+    struct HalfTransparent {
+        alpha: f32,
+    }
+
+    impl Texture for HalfTransparent {
+        fn value(&self, _u: f32, _v: f32, _p: V3<f32>) -> V3<f32> {
+            V3::new(1.0, 1.0, 1.0)
+        }
+
+        fn alpha(&self, u: f32, _v: f32, _p: V3<f32>) -> f32 {
+            if u < 0.5 {
+                self.alpha
+            } else {
+                1.0
+            }
+        }
+    }
+
+    /// Returns material container with one material cut out by the mask, and the index of that material.
+    fn cut_out(opacity: Opacity) -> (MaterialContainer, usize) {
+        let mut materials = MaterialContainer::default();
+        let white = ConstantTexture {
+            color: V3::new(1.0, 1.0, 1.0),
+        };
+        let material = materials.add_with_opacity(Lambertian::new(Box::new(white)), opacity);
+        (materials, material)
+    }
+
+    /// Returns triangle, quad and mesh in the plane z = 0, all with the same material. Both texture and barycentric coordinates of each of them follow x.
+    fn primitives(material: usize) -> Vec<Box<dyn Hittable>> {
+        let (a, b, c) = (
+            V3::new(0.0, 0.0, 0.0),
+            V3::new(1.0, 0.0, 0.0),
+            V3::new(0.0, 1.0, 0.0),
+        );
+        let mesh = MeshData {
+            positions: vec![a, b, c],
+            indices: vec![[0, 1, 2]],
+            materials: vec![material],
+            ..MeshData::default()
+        };
+
+        vec![
+            Box::new(Triangle::new(V3::new(a, b, c), None, material)),
+            Box::new(Quad::new(a, b - a, c - a, material)),
+            Box::new(Mesh::from_data(mesh).unwrap()),
+        ]
+    }
+
+    #[test]
+    fn threshold_test() {
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+        let down = V3::new(0.0, 0.0, -1.0);
+        let (materials, material) = cut_out(Opacity::new(
+            HalfTransparent { alpha: 0.25 },
+            AlphaMode::Threshold(0.5),
+        ));
+
+        // Primitives sharing the material share its mask
+        for primitive in primitives(material) {
+            let plain = Ray::for_test(V3::new(0.25, 0.25, 1.0), down, &setting);
+            let transparent = plain.with_materials(&materials);
+            assert!(primitive.hit(&transparent).is_none());
+            assert!(!primitive.occluded(&transparent, 2.0));
+
+            let opaque =
+                Ray::for_test(V3::new(0.6, 0.2, 1.0), down, &setting).with_materials(&materials);
+            assert!(primitive.hit(&opaque).is_some());
+            assert!(primitive.occluded(&opaque, 2.0));
+
+            // Rays without materials see all surfaces opaque
+            assert!(primitive.hit(&plain).is_some());
+        }
+    }
+
+    #[test]
+    fn stochastic_test() {
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+        let down = V3::new(0.0, 0.0, -1.0);
+        let (materials, material) = cut_out(Opacity::new(
+            HalfTransparent { alpha: 0.25 },
+            AlphaMode::Stochastic,
+        ));
+
+        // Fraction of hits follows alpha, its standard deviation over the samples is below 0.005
+        let samples = 10000;
+        for primitive in primitives(material) {
+            let ray =
+                Ray::for_test(V3::new(0.25, 0.25, 1.0), down, &setting).with_materials(&materials);
+            let hits = (0..samples)
+                .filter(|_| primitive.hit(&ray).is_some())
+                .count();
+            let occlusions = (0..samples)
+                .filter(|_| primitive.occluded(&ray, 2.0))
+                .count();
+            for count in [hits, occlusions] {
+                let fraction = count as f32 / samples as f32;
+                assert!((fraction - 0.25).abs() < 0.03, "{}", fraction);
+            }
+
+            let opaque =
+                Ray::for_test(V3::new(0.6, 0.2, 1.0), down, &setting).with_materials(&materials);
+            assert!((0..samples).all(|_| primitive.hit(&opaque).is_some()));
+        }
+    }
+}
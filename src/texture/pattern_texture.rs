@@ -0,0 +1,286 @@
+use crate::texture::{CoordinateSource, Texture};
+use crate::{Hit, V3};
+
+/// Procedural pattern, which decides how two textures are distributed on the surface.
+///
+/// Patterns repeat every unit of the coordinates, `scale` of [`PatternTexture`] changes their size.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Pattern {
+    /// Alternating unit squares, or cubes in 3D coordinates.
+    Checker,
+    /// Lines of the second texture on unit cell borders.
+    Grid {
+        /// Width of the lines.
+        line_width: f32,
+    },
+    /// Parallel stripes, the first texture covers `ratio` of each stripe period.
+    Stripes {
+        /// Direction in which stripes alternate, its length is the frequency of stripes.
+        direction: V3<f32>,
+        /// Part of the period covered by the first texture.
+        ratio: f32,
+    },
+    /// Rows of bricks of the first texture, every other row shifted by half of the brick, separated by mortar of the second texture.
+    Brick {
+        /// Width of the brick, which is also its depth in 3D coordinates.
+        width: f32,
+        /// Height of the brick, rows go along the second coordinate.
+        height: f32,
+        /// Width of the mortar between bricks.
+        mortar: f32,
+    },
+    /// Distance to the closest of randomly placed points, one point in each unit cell, blends from the first texture at the point to the second one.
+    Voronoi {
+        /// How far from the cell center points can be, from 0 for regular grid to 1.
+        jitter: f32,
+    },
+    /// Cells around randomly placed points, each cell gets random blend of the two textures.
+    VoronoiCells {
+        /// How far from the cell center points can be, from 0 for regular grid to 1.
+        jitter: f32,
+    },
+}
+
+impl Pattern {
+    /// Returns weight of the second texture at the point.
+    ///
+    /// `point` - Scaled coordinates.
+    ///
+    /// `planar` - True if the coordinates are 2D texture coordinates, then the third coordinate is ignored.
+    fn factor(&self, point: V3<f32>, planar: bool) -> f32 {
+        let dimensions = if planar { 2 } else { 3 };
+        let mut coordinates = point.into_iter().take(dimensions);
+
+        match *self {
+            Pattern::Checker => {
+                let parity = coordinates.map(|c| c.floor() as i64).sum::<i64>();
+                parity.rem_euclid(2) as f32
+            }
+            Pattern::Grid { line_width } => {
+                let on_line = coordinates.any(|c| {
+                    let fraction = c - c.floor();
+                    fraction.min(1.0 - fraction) < line_width * 0.5
+                });
+                if on_line {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Pattern::Stripes { direction, ratio } => {
+                let position = point.dot(direction);
+                if position - position.floor() < ratio {
+                    0.0
+                } else {
+                    1.0
+                }
+            }
+            Pattern::Brick {
+                width,
+                height,
+                mortar,
+            } => {
+                let row = (point.y / height).floor();
+                // Every other row is shifted by half of the brick
+                let shift = if row as i64 % 2 == 0 { 0.0 } else { 0.5 };
+
+                let mut distances = vec![
+                    (point.y - row * height).min((row + 1.0) * height - point.y),
+                    edge_distance(point.x / width + shift) * width,
+                ];
+                if !planar {
+                    distances.push(edge_distance(point.z / width + shift) * width);
+                }
+
+                if distances
+                    .into_iter()
+                    .any(|distance| distance < mortar * 0.5)
+                {
+                    1.0
+                } else {
+                    0.0
+                }
+            }
+            Pattern::Voronoi { jitter } => {
+                let (distance, _) = closest_feature(point, jitter, planar);
+                distance.min(1.0)
+            }
+            Pattern::VoronoiCells { jitter } => {
+                let (_, cell) = closest_feature(point, jitter, planar);
+                hash(cell, 3)
+            }
+        }
+    }
+}
+
+/// Returns distance from the closest integer coordinate.
+fn edge_distance(coordinate: f32) -> f32 {
+    let fraction = coordinate - coordinate.floor();
+    fraction.min(1.0 - fraction)
+}
+
+/// Returns pseudorandom number in <0, 1) range for the cell.
+///
+/// `cell` - Integer coordinates of the cell.
+///
+/// `channel` - Different channels give independent numbers for the same cell.
+fn hash(cell: V3<i32>, channel: u32) -> f32 {
+    let mut h = (cell.x as u32).wrapping_mul(0x8da6_b343)
+        ^ (cell.y as u32).wrapping_mul(0xd816_3841)
+        ^ (cell.z as u32).wrapping_mul(0xcb1a_b31f)
+        ^ channel.wrapping_mul(0x1656_67b1);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x2c1b_3c6d);
+    h ^= h >> 12;
+    h = h.wrapping_mul(0x297a_2d39);
+    h ^= h >> 15;
+
+    (h >> 8) as f32 / (1 << 24) as f32
+}
+
+/// Returns distance to the closest feature point and the cell the point belongs to.
+fn closest_feature(point: V3<f32>, jitter: f32, planar: bool) -> (f32, V3<i32>) {
+    let base = point.floor();
+    let base = V3::new(base.x as i32, base.y as i32, base.z as i32);
+    let z_range = if planar { 0..=0 } else { -1..=1 };
+
+    let mut closest = (f32::INFINITY, base);
+    for dz in z_range {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let cell = V3::new(base.x + dx, base.y + dy, base.z + dz);
+                let offset = |channel| 0.5 + jitter * (hash(cell, channel) - 0.5);
+                let feature = V3::new(
+                    cell.x as f32 + offset(0),
+                    cell.y as f32 + offset(1),
+                    if planar {
+                        point.z
+                    } else {
+                        cell.z as f32 + offset(2)
+                    },
+                );
+
+                let distance = (feature - point).length();
+                if distance < closest.0 {
+                    closest = (distance, cell);
+                }
+            }
+        }
+    }
+
+    closest
+}
+
+/// Texture distributing two textures by a procedural pattern, in texture, object or world coordinates.
+#[derive(Clone)]
+pub struct PatternTexture {
+    /// Pattern that decides where each texture is.
+    pub pattern: Pattern,
+    /// Coordinates in which the pattern is evaluated.
+    pub source: CoordinateSource,
+    /// Scale of the coordinates, bigger values make the pattern smaller.
+    pub scale: f32,
+    /// First texture of the pattern.
+    pub a: Box<dyn Texture>,
+    /// Second texture of the pattern.
+    pub b: Box<dyn Texture>,
+}
+
+impl PatternTexture {
+    /// Returns new pattern texture.
+    ///
+    /// `pattern` - Pattern that decides where each texture is.
+    ///
+    /// `source` - Coordinates in which the pattern is evaluated.
+    ///
+    /// `scale` - Scale of the coordinates, bigger values make the pattern smaller.
+    ///
+    /// `a`, `b` - Textures of the pattern.
+    pub fn new<A: Texture + 'static, B: Texture + 'static>(
+        pattern: Pattern,
+        source: CoordinateSource,
+        scale: f32,
+        a: A,
+        b: B,
+    ) -> Self {
+        PatternTexture {
+            pattern,
+            source,
+            scale,
+            a: Box::new(a),
+            b: Box::new(b),
+        }
+    }
+
+    /// Returns blend of the textures, only the needed ones are looked up.
+    fn blend<T>(&self, point: V3<f32>, lookup: impl Fn(&dyn Texture) -> T) -> T
+    where
+        T: std::ops::Mul<f32, Output = T> + std::ops::Add<Output = T>,
+    {
+        let planar = self.source == CoordinateSource::Uv;
+        let factor = self.pattern.factor(point * self.scale, planar);
+
+        if factor <= 0.0 {
+            lookup(self.a.as_ref())
+        } else if factor >= 1.0 {
+            lookup(self.b.as_ref())
+        } else {
+            lookup(self.a.as_ref()) * (1.0 - factor) + lookup(self.b.as_ref()) * factor
+        }
+    }
+}
+
+impl Texture for PatternTexture {
+    fn value(&self, u: f32, v: f32, p: V3<f32>) -> V3<f32> {
+        self.blend(self.source.point_at(u, v, p), |texture| {
+            texture.value(u, v, p)
+        })
+    }
+
+    fn sample(&self, hit: &Hit) -> V3<f32> {
+        self.blend(self.source.point(hit), |texture| texture.sample(hit))
+    }
+
+    fn alpha(&self, u: f32, v: f32, p: V3<f32>) -> f32 {
+        self.blend(self.source.point_at(u, v, p), |texture| {
+            texture.alpha(u, v, p)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checker_test() {
+        let checker = Pattern::Checker;
+
+        assert_eq!(checker.factor(V3::new(0.5, 0.5, 7.5), true), 0.0);
+        assert_eq!(checker.factor(V3::new(1.5, 0.5, 7.5), true), 1.0);
+        assert_eq!(checker.factor(V3::new(-0.5, 0.5, 0.5), false), 1.0);
+        assert_eq!(checker.factor(V3::new(-0.5, -0.5, 0.5), false), 0.0);
+    }
+
+    #[test]
+    fn brick_test() {
+        let brick = Pattern::Brick {
+            width: 2.0,
+            height: 1.0,
+            mortar: 0.1,
+        };
+
+        assert_eq!(brick.factor(V3::new(1.0, 0.5, 0.0), true), 0.0);
+        assert_eq!(brick.factor(V3::new(1.0, 0.99, 0.0), true), 1.0);
+        // Second row is shifted, so the joint is in the middle of the brick below it
+        assert_eq!(brick.factor(V3::new(1.0, 1.5, 0.0), true), 1.0);
+    }
+
+    #[test]
+    fn voronoi_test() {
+        let voronoi = Pattern::Voronoi { jitter: 0.0 };
+
+        assert_eq!(voronoi.factor(V3::new(0.5, 0.5, 0.0), true), 0.0);
+        assert!((voronoi.factor(V3::new(1.0, 0.5, 0.0), true) - 0.5).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,351 @@
+use crate::hittables::Aabb;
+use crate::misc::{gamma, HittablePdf, Matrix4, Pdf};
+use crate::{Hit, Hittable, Ray, V3};
+
+use std::sync::Arc;
+
+/// Affine transformation, like translation, rotation, scale or shear, or any composition of them.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform {
+    /// Matrix transforming from the object to the world.
+    matrix: Matrix4,
+    /// Matrix transforming from the world to the object.
+    inverse: Matrix4,
+}
+
+impl Transform {
+    /// Returns transformation that keeps everything in place.
+    pub fn identity() -> Self {
+        Transform {
+            matrix: Matrix4::identity(),
+            inverse: Matrix4::identity(),
+        }
+    }
+
+    /// Returns transformation given by a matrix, or None if the matrix can't be inverted.
+    ///
+    /// `matrix` - Matrix transforming from the object to the world, the last row should be 0, 0, 0, 1.
+    pub fn from_matrix(matrix: Matrix4) -> Option<Self> {
+        Some(Transform {
+            matrix,
+            inverse: matrix.inverse()?,
+        })
+    }
+
+    /// Returns translation.
+    ///
+    /// `offset` - Vector by which the object is moved.
+    pub fn translation(offset: V3<f32>) -> Self {
+        let mut matrix = Matrix4::identity();
+        let mut inverse = Matrix4::identity();
+        for (axis, value) in offset.into_iter().enumerate() {
+            matrix.m[axis][3] = value;
+            inverse.m[axis][3] = -value;
+        }
+        Transform { matrix, inverse }
+    }
+
+    /// Returns scale along the axes, negative values mirror the object.
+    ///
+    /// `scale` - Scale along each axis, none of them can be zero.
+    pub fn scaling(scale: V3<f32>) -> Self {
+        let mut matrix = Matrix4::identity();
+        let mut inverse = Matrix4::identity();
+        for (axis, value) in scale.into_iter().enumerate() {
+            matrix.m[axis][axis] = value;
+            inverse.m[axis][axis] = 1.0 / value;
+        }
+        Transform { matrix, inverse }
+    }
+
+    /// Returns rotation by some angle around some axis going through the origin, in the same direction as [`IntoRotated::rotate`](crate::transform::IntoRotated::rotate).
+    ///
+    /// `axis` - Axis around which the object is rotated.
+    ///
+    /// `angle` - Angle in radians.
+    pub fn rotation(axis: V3<f32>, angle: f32) -> Self {
+        let V3 { x, y, z } = axis.norm();
+        let (sin, cos) = angle.sin_cos();
+        let t = 1.0 - cos;
+
+        let matrix = Matrix4 {
+            m: [
+                [
+                    t * x * x + cos,
+                    t * x * y - sin * z,
+                    t * x * z + sin * y,
+                    0.0,
+                ],
+                [
+                    t * x * y + sin * z,
+                    t * y * y + cos,
+                    t * y * z - sin * x,
+                    0.0,
+                ],
+                [
+                    t * x * z - sin * y,
+                    t * y * z + sin * x,
+                    t * z * z + cos,
+                    0.0,
+                ],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        };
+
+        // Rotation matrix is orthogonal
+        Transform {
+            matrix,
+            inverse: matrix.transpose(),
+        }
+    }
+
+    /// Returns shear, which moves each coordinate proportionally to the other coordinates.
+    ///
+    /// `xy`, `xz` - How much the x coordinate moves per unit of y and z.
+    ///
+    /// `yx`, `yz` - How much the y coordinate moves per unit of x and z.
+    ///
+    /// `zx`, `zy` - How much the z coordinate moves per unit of x and y.
+    ///
+    /// Returns None if the shear flattens the space.
+    pub fn shear(xy: f32, xz: f32, yx: f32, yz: f32, zx: f32, zy: f32) -> Option<Self> {
+        Transform::from_matrix(Matrix4 {
+            m: [
+                [1.0, xy, xz, 0.0],
+                [yx, 1.0, yz, 0.0],
+                [zx, zy, 1.0, 0.0],
+                [0.0, 0.0, 0.0, 1.0],
+            ],
+        })
+    }
+
+    /// Returns transformation which applies this transformation first and the other one after it.
+    ///
+    /// `other` - Transformation applied after this one.
+    pub fn then(self, other: Transform) -> Self {
+        Transform {
+            matrix: other.matrix * self.matrix,
+            inverse: self.inverse * other.inverse,
+        }
+    }
+
+    /// Returns transformation that undoes this one.
+    pub fn inverse(self) -> Self {
+        Transform {
+            matrix: self.inverse,
+            inverse: self.matrix,
+        }
+    }
+
+    /// Returns matrix transforming from the object to the world.
+    pub fn matrix(&self) -> Matrix4 {
+        self.matrix
+    }
+
+    /// Returns transformed point.
+    ///
+    /// `p` - Point in the object coordinates.
+    pub fn point(&self, p: V3<f32>) -> V3<f32> {
+        self.matrix.transform_point(p)
+    }
+
+    /// Returns bound of the error of the transformed point, including rounding of the transformation itself.
+    ///
+    /// `p` - Point in the object coordinates.
+    ///
+    /// `error` - Bound of the absolute error of the point along each axis.
+    pub fn point_error(&self, p: V3<f32>, error: V3<f32>) -> V3<f32> {
+        let m = &self.matrix.m;
+        let row = |i: usize| {
+            let propagated =
+                m[i][0].abs() * error.x + m[i][1].abs() * error.y + m[i][2].abs() * error.z;
+            let rounding = (m[i][0] * p.x).abs()
+                + (m[i][1] * p.y).abs()
+                + (m[i][2] * p.z).abs()
+                + m[i][3].abs();
+            (1.0 + gamma(3)) * propagated + gamma(3) * rounding
+        };
+        V3::new(row(0), row(1), row(2))
+    }
+
+    /// Returns transformed vector, like a direction or a tangent.
+    ///
+    /// `v` - Vector in the object coordinates.
+    pub fn vector(&self, v: V3<f32>) -> V3<f32> {
+        self.matrix.transform_vector(v)
+    }
+
+    /// Returns transformed normal vector, which has to be multiplied by the inverse transpose matrix to stay perpendicular to the surface, it isn't normalized.
+    ///
+    /// `n` - Normal vector in the object coordinates.
+    pub fn normal(&self, n: V3<f32>) -> V3<f32> {
+        self.inverse.transpose().transform_vector(n)
+    }
+
+    /// Returns bounding box of the transformed box.
+    ///
+    /// `bounding_box` - Box in the object coordinates.
+    pub fn bounding_box(&self, bounding_box: &Aabb) -> Aabb {
+        let points: Vec<V3<f32>> = bounding_box
+            .get_box_points()
+            .into_iter()
+            .map(|point| self.point(point))
+            .collect();
+
+        let min = points.iter().fold(points[0], |min, &point| min.min(point));
+        let max = points.iter().fold(points[0], |max, &point| max.max(point));
+
+        Aabb::new(min, max)
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+/// Represents a hittable object changed by an affine transformation, many of them can share one object as its instances.
+#[derive(Clone)]
+pub struct Transformed {
+    /// Transformation from the object to the world.
+    transform: Transform,
+    /// Bounding box of the transformed object.
+    bounding_box: Aabb,
+    /// Object that is transformed, shared between instances.
+    hittable: Arc<dyn HittablePdf>,
+}
+
+impl Transformed {
+    /// Returns new instance of a shared object.
+    ///
+    /// `hittable` - Shared object, like a big mesh or a Bvh, which is not copied.
+    ///
+    /// `transform` - Transformation from the object to the world.
+    pub fn new(hittable: Arc<dyn HittablePdf>, transform: Transform) -> Self {
+        Transformed {
+            transform,
+            bounding_box: transform.bounding_box(&hittable.bounding_box()),
+            hittable,
+        }
+    }
+}
+
+impl Hittable for Transformed {
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        // Parameter of the ray stays the same, because the transformation is linear
+        let object_ray = Ray {
+            origin: self.transform.inverse.transform_point(ray.origin),
+            end: self.transform.inverse.transform_vector(ray.end),
+            ..*ray
+        };
+
+        let hit = self.hittable.hit(&object_ray)?;
+
+        Some(Hit {
+            point: self.transform.point(hit.point),
+            error: self.transform.point_error(hit.point, hit.error),
+            normal: self.transform.normal(hit.normal).norm(),
+            dpdu: self.transform.vector(hit.dpdu),
+            dpdv: self.transform.vector(hit.dpdv),
+            direction: ray.end,
+            footprint: ray.cone.width_at(hit.t * ray.end.length()),
+            ..hit
+        })
+    }
+
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        let object_ray = Ray {
+            origin: self.transform.inverse.transform_point(ray.origin),
+            end: self.transform.inverse.transform_vector(ray.end),
+            ..*ray
+        };
+
+        self.hittable.occluded(&object_ray, t_max)
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> V3<f32> {
+        let object_ray = Ray {
+            origin: self.transform.inverse.transform_point(ray.origin),
+            end: self.transform.inverse.transform_vector(ray.end),
+            ..*ray
+        };
+
+        self.hittable.transmittance(&object_ray, t_max)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounding_box.clone()
+    }
+}
+
+impl Pdf for Transformed {
+    fn value(&self, origin: V3<f32>, direction: V3<f32>) -> f32 {
+        let inverse = &self.transform.inverse;
+        let object_direction = inverse.transform_vector(direction.norm());
+        let length = object_direction.length();
+
+        // Change of solid angle, when unit directions are mapped by the inverse transformation
+        let jacobian = inverse.determinant3().abs() / (length * length * length);
+
+        self.hittable
+            .value(inverse.transform_point(origin), object_direction)
+            * jacobian
+    }
+
+    fn generate(&self, origin: V3<f32>) -> V3<f32> {
+        let direction = self
+            .hittable
+            .generate(self.transform.inverse.transform_point(origin));
+        self.transform.vector(direction)
+    }
+}
+
+/// Transforms object into Transformed object
+pub trait IntoTransformed {
+    /// Transforms object into Transformed object
+    ///
+    /// `transform` - Transformation from the object to the world.
+    fn transform(self, transform: Transform) -> Transformed;
+}
+
+impl<T> IntoTransformed for T
+where
+    T: HittablePdf + 'static,
+{
+    fn transform(self, transform: Transform) -> Transformed {
+        Transformed::new(Arc::new(self), transform)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitive::Sphere;
+    use crate::transform::IntoRotated;
+
+    #[test]
+    fn composition_test() {
+        let axis = V3::new(1.0, 2.0, -0.5);
+        let point = V3::new(0.3, -1.0, 2.0);
+
+        // Same rotation as the quaternion one of Rotated
+        let rotated = Transform::rotation(axis, 0.7).point(point);
+        let expected = point.rot(axis, 0.35f32.sin(), 0.35f32.cos());
+        assert!((rotated - expected).length() < 1e-5);
+
+        let transform = Transform::scaling(V3::new(2.0, 1.0, 0.5))
+            .then(Transform::rotation(axis, 0.7))
+            .then(Transform::translation(V3::new(1.0, 2.0, 3.0)));
+        let back = transform.inverse().point(transform.point(point));
+        assert!((back - point).length() < 1e-5);
+
+        // Sphere rotated both ways has the same bounding box
+        let sphere = Sphere::new(V3::new(1.0, 0.0, 0.0), 0.5, 0);
+        let a = sphere.clone().rotate(axis, 0.7).bounding_box();
+        let b = sphere
+            .transform(Transform::rotation(axis, 0.7))
+            .bounding_box();
+        assert!((a.x.min - b.x.min).abs() < 1e-4 && (a.z.max - b.z.max).abs() < 1e-4);
+    }
+}
@@ -4,77 +4,164 @@ use crate::{Hit, Hittable, Ray, RaySetting, V3};
 
 use rand::Rng;
 /// Struct representing some primitive like Sphere that was changed into some sort of smoke/fog/mist.
+///
+/// It traces its boundary for every Ray and ignores other media, use [`MediumBoundary`](crate::transform::MediumBoundary) for media nested in other objects.
 #[derive(Clone)]
 pub struct ConstantMedium {
     /// The primitive that was transformed into smoke.
     boundary: Box<dyn HittablePdf>,
     /// Some material that gives color when Ray hits the object.
     phase_function: usize,
-    /// Some sort of inversed probability that Ray will scatter inside the object.
-    neg_inv_density: f32,
+    /// Probability per unit of distance that Ray collides with a particle, separately for each color channel.
+    extinction: V3<f32>,
 }
 
-const UNIVERSE: Interval = Interval::universe();
+/// Scattering and absorption coefficients of a medium, per color channel.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MediumCoefficients {
+    /// Probability per unit of distance that light scatters on a particle.
+    pub sigma_s: V3<f32>,
+    /// Probability per unit of distance that light is absorbed by a particle.
+    pub sigma_a: V3<f32>,
+}
+
+impl MediumCoefficients {
+    /// Returns new medium coefficients.
+    ///
+    /// `sigma_s` - Scattering coefficient.
+    ///
+    /// `sigma_a` - Absorption coefficient.
+    pub fn new(sigma_s: V3<f32>, sigma_a: V3<f32>) -> Self {
+        MediumCoefficients { sigma_s, sigma_a }
+    }
+
+    /// Returns probability per unit of distance that light collides with a particle, either scattering or being absorbed.
+    pub fn extinction(&self) -> V3<f32> {
+        self.sigma_s + self.sigma_a
+    }
+
+    /// Returns fraction of light scattered, not absorbed, at each collision.
+    pub fn albedo(&self) -> V3<f32> {
+        self.sigma_s
+            .into_iter()
+            .zip(self.extinction())
+            .map(|(s, t)| if t > 0.0 { s / t } else { 0.0 })
+            .collect()
+    }
+}
+
+/// Returns parameters at which the ray enters and leaves the boundary, clipped to the time interval of the ray.
+///
+/// Ray starting inside of the boundary hits it only once, where it leaves, which is told by the side of the surface it hits.
+///
+/// `boundary` - Closed object that contains the medium.
+///
+/// `ray` - Ray going through the medium.
+pub(crate) fn boundary_interval(boundary: &dyn HittablePdf, ray: &Ray) -> Option<(f32, f32)> {
+    let time = ray.setting.ray_time;
+    let first_hit = boundary.hit(&Ray {
+        setting: &RaySetting {
+            ray_time: Interval::new(time.min, f32::INFINITY),
+            ..*ray.setting
+        },
+        ..*ray
+    })?;
+    if !first_hit.front_face {
+        return Some((time.min.max(0.0), first_hit.t.min(time.max)));
+    }
+    if first_hit.t >= time.max {
+        return None;
+    }
+
+    // the second hit has to be farther than the rounding error of the first one
+    let t_error = first_hit.error.dot(first_hit.normal.abs()) / first_hit.normal.dot(ray.end).abs();
+    let second_hit = boundary.hit(&Ray {
+        setting: &RaySetting {
+            ray_time: Interval {
+                min: first_hit.t + t_error,
+                max: f32::INFINITY,
+            },
+            ..*ray.setting
+        },
+        ..*ray
+    })?;
+
+    Some((first_hit.t, second_hit.t.min(time.max)))
+}
 
 impl Hittable for ConstantMedium {
     fn hit(&self, ray: &Ray) -> Option<Hit> {
-        match self.boundary.hit(&Ray {
-            setting: &RaySetting {
-                ray_time: UNIVERSE,
-                ..*ray.setting
-            },
+        let (enter, exit) = boundary_interval(self.boundary.as_ref(), ray)?;
+        let mut rng = rand::thread_rng();
+
+        let extinction = self.extinction;
+        let density = match ray.channel {
+            Some(channel) => extinction[channel],
+            None if extinction.x == extinction.y && extinction.y == extinction.z => extinction.x,
+            // Free paths differ between channels, so the ray has to pick one channel before entering
+            None => {
+                let mut hit = Hit::new(
+                    ray,
+                    V3::new(1.0, 0.0, 0.0),
+                    enter,
+                    ray.point_at_param(enter),
+                    self.phase_function,
+                    0.0,
+                    0.0,
+                );
+                hit.channel = Some(rng.gen_range(0..3));
+                return Some(hit);
+            }
+        };
+        if density <= 0.0 {
+            return None;
+        }
+
+        // when ray hits some particle inside the object
+        let ray_length = ray.end.length();
+        let distance_inside_boundary = (exit - enter) * ray_length;
+        let random: f32 = rng.gen_range(0.0..1.0);
+        let hit_distance = -(1.0 - random).ln() / density;
+
+        if hit_distance > distance_inside_boundary {
+            return None;
+        };
+
+        Some(Hit::new(
+            ray,
+            V3::new(1.0, 0.0, 0.0),
+            enter + hit_distance / ray_length,
+            ray.point_at_param(enter + hit_distance / ray_length),
+            self.phase_function,
+            0.0,
+            0.0,
+        ))
+    }
+
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        // Particles only dim the light, the hit picking a color channel isn't in the way either
+        self.transmittance(ray, t_max) == V3::default()
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> V3<f32> {
+        let setting = RaySetting {
+            ray_time: Interval::new(ray.setting.ray_time.min, t_max),
+            ..*ray.setting
+        };
+        let segment = Ray {
+            setting: &setting,
             ..*ray
-        }) {
-            Some(mut first_hit) => {
-                // if the ray hit the object at all
-                match self.boundary.hit(&Ray {
-                    setting: &RaySetting {
-                        ray_time: Interval {
-                            min: first_hit.t + 0.0001,
-                            max: f32::INFINITY,
-                        },
-                        ..*ray.setting
-                    },
-                    ..*ray
-                }) {
-                    Some(mut second_hit) => {
-                        // when ray hits some particle inside the object
-                        if first_hit.t < ray.setting.ray_time.min {
-                            first_hit.t = ray.setting.ray_time.min
-                        };
-                        if second_hit.t > ray.setting.ray_time.max {
-                            second_hit.t = ray.setting.ray_time.max
-                        };
-                        if first_hit.t >= second_hit.t {
-                            return None;
-                        };
-                        if first_hit.t < 0.0 {
-                            first_hit.t = 0.0;
-                        }
-
-                        let ray_length = ray.end.length();
-                        let distance_inside_boundary = (second_hit.t - first_hit.t) * ray_length;
-                        let random: f32 = rand::thread_rng().gen_range(0.0..1.0);
-                        let hit_distance = self.neg_inv_density * random.ln();
-
-                        if hit_distance > distance_inside_boundary {
-                            return None;
-                        };
-
-                        Some(Hit::new(
-                            ray,
-                            V3::new(1.0, 0.0, 0.0),
-                            first_hit.t + hit_distance / ray_length,
-                            ray.point_at_param(first_hit.t + hit_distance / ray_length),
-                            self.phase_function,
-                            0.0,
-                            0.0,
-                        ))
-                    }
-                    None => None,
-                }
+        };
+
+        match boundary_interval(self.boundary.as_ref(), &segment) {
+            Some((enter, exit)) => {
+                let distance = (exit - enter) * ray.end.length();
+                self.extinction
+                    .into_iter()
+                    .map(|sigma| (-sigma * distance).exp())
+                    .collect()
             }
-            None => None,
+            None => V3::new(1.0, 1.0, 1.0),
         }
     }
 
@@ -90,6 +177,17 @@ pub trait IntoConstantMedium {
     ///
     /// `phase_function` - Material that gives color when Ray scatter inside the object.
     fn into_constant_medium(self, neg_inv_density: f32, phase_function: usize) -> ConstantMedium;
+
+    /// Transforms object into ConstantMedium object with extinction depending on color, like in skin or murky water.
+    ///
+    /// `coefficients` - Scattering and absorption coefficients of the medium, the phase function material should use its albedo.
+    ///
+    /// `phase_function` - Material that gives color when Ray scatter inside the object.
+    fn into_colored_medium(
+        self,
+        coefficients: MediumCoefficients,
+        phase_function: usize,
+    ) -> ConstantMedium;
 }
 
 impl<T> IntoConstantMedium for T
@@ -100,7 +198,19 @@ where
         ConstantMedium {
             boundary: Box::new(self),
             phase_function,
-            neg_inv_density: -1.0 / neg_inv_density,
+            extinction: V3::new(neg_inv_density, neg_inv_density, neg_inv_density),
+        }
+    }
+
+    fn into_colored_medium(
+        self,
+        coefficients: MediumCoefficients,
+        phase_function: usize,
+    ) -> ConstantMedium {
+        ConstantMedium {
+            boundary: Box::new(self),
+            phase_function,
+            extinction: coefficients.extinction(),
         }
     }
 }
@@ -114,3 +224,85 @@ impl Pdf for ConstantMedium {
         self.boundary.generate(origin)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittables::{FlatBvh, HittableList};
+    use crate::primitive::{Sphere, Triangle};
+
+    #[test]
+    fn boundary_interval_inside_test() {
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+
+        // Ray starting inside of the fog around the camera still goes through it up to the boundary
+        let sphere = Sphere::new(V3::default(), 5000.0, 0);
+        let ray = Ray::for_test(V3::default(), V3::new(1.0, 0.0, 0.0), &setting);
+        let (enter, exit) = boundary_interval(&sphere, &ray).unwrap();
+        assert_eq!(enter, 0.001);
+        assert!((exit - 5000.0).abs() < 0.01);
+
+        // The same holds for boundaries made of triangles
+        let (a, b, c, d) = (
+            V3::new(-10.0, -10.0, -10.0),
+            V3::new(10.0, -10.0, 10.0),
+            V3::new(10.0, 10.0, -10.0),
+            V3::new(-10.0, 10.0, 10.0),
+        );
+        let mut tetrahedron = HittableList::new();
+        for verticles in [
+            V3::new(a, b, c),
+            V3::new(a, d, b),
+            V3::new(a, c, d),
+            V3::new(b, d, c),
+        ] {
+            tetrahedron.add(Triangle::new(verticles, None, 0));
+        }
+        let ray = Ray::for_test(V3::default(), V3::new(1.0, 0.0, 0.0), &setting);
+        let (enter, exit) = boundary_interval(&tetrahedron, &ray).unwrap();
+        assert_eq!(enter, 0.001);
+        assert!((exit - 10.0).abs() < 0.01);
+
+        // Ray starting in front of the boundary enters it at its surface
+        let ray = Ray::for_test(V3::new(-6000.0, 0.0, 0.0), V3::new(1.0, 0.0, 0.0), &setting);
+        let (enter, exit) = boundary_interval(&sphere, &ray).unwrap();
+        assert!((enter - 1000.0).abs() < 0.01);
+        assert!((exit - 11000.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn light_behind_chromatic_medium_test() {
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+        let extinction = V3::new(0.1, 0.5, 1.0);
+        let fog = Sphere::new(V3::new(5.0, 0.0, 0.0), 1.0, 0)
+            .into_colored_medium(MediumCoefficients::new(extinction, V3::default()), 0);
+
+        let mut objects = HittableList::new();
+        objects.add(fog);
+        objects.add(Sphere::new(V3::new(10.0, 0.0, 0.0), 1.0, 1));
+        let world = FlatBvh::new(&objects);
+
+        // Shadow ray ends just before the light, each channel is dimmed by its own extinction over the diameter of the fog
+        let ray = Ray::for_test(V3::default(), V3::new(1.0, 0.0, 0.0), &setting);
+        let t_max = 9.0 * (1.0 - 0.0001);
+        assert!(!world.occluded(&ray, t_max));
+        let transmittance = world.transmittance(&ray, t_max);
+        for channel in 0..3 {
+            let expected = (-extinction[channel] * 2.0).exp();
+            assert!((transmittance[channel] - expected).abs() < 1e-4);
+        }
+
+        // Surface in front of the fog stops all the light
+        objects.add(Sphere::new(V3::new(2.0, 0.0, 0.0), 0.5, 0));
+        let world = FlatBvh::new(&objects);
+        assert_eq!(world.transmittance(&ray, t_max), V3::default());
+    }
+}
@@ -15,6 +15,14 @@ impl Hittable for FlipFace {
         hit.map(|hit| hit.flip_front_face())
     }
 
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        self.hittable.occluded(ray, t_max)
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> crate::V3<f32> {
+        self.hittable.transmittance(ray, t_max)
+    }
+
     fn bounding_box(&self) -> Aabb {
         self.hittable.bounding_box()
     }
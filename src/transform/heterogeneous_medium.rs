@@ -0,0 +1,263 @@
+use crate::hittables::Aabb;
+use crate::misc::{HittablePdf, Interval, Pdf};
+use crate::texture::Texture;
+use crate::transform::constant_medium::boundary_interval;
+use crate::{Hit, Hittable, Ray, RaySetting, V3};
+
+use objekt_clonable::*;
+use rand::Rng;
+
+#[clonable]
+/// Density of particles, which can change from point to point.
+pub trait DensityField: Send + Sync + Clone {
+    /// Returns density at the point, the probability per unit of distance that Ray scatters there.
+    ///
+    /// `point` - Position in the world.
+    fn density(&self, point: V3<f32>) -> f32;
+
+    /// Returns upper bound of the density in the whole field, which is used to sample free paths.
+    fn majorant(&self) -> f32;
+
+    /// Returns tighter upper bounds of the density along parts of the ray, as the ray parameter where the part ends and the majorant in it.
+    ///
+    /// `ray` - Ray going through the field.
+    ///
+    /// `enter`, `exit` - Parameters of the ray between which the parts are needed, the last part ends at `exit`.
+    fn majorant_segments(&self, _ray: &Ray, _enter: f32, exit: f32) -> Vec<(f32, f32)> {
+        vec![(exit, self.majorant())]
+    }
+}
+
+/// Density taken from a solid texture, like noise, mean of color channels clamped to <0, 1> is multiplied by the maximal density.
+#[derive(Clone)]
+pub struct TextureDensity {
+    /// Texture evaluated at the position in the world.
+    pub texture: Box<dyn Texture>,
+    /// Density where the texture is white.
+    pub max_density: f32,
+}
+
+impl TextureDensity {
+    /// Returns new density from a solid texture.
+    ///
+    /// `texture` - Texture evaluated at the position in the world.
+    ///
+    /// `max_density` - Density where the texture is white.
+    pub fn new<T: Texture + 'static>(texture: T, max_density: f32) -> Self {
+        TextureDensity {
+            texture: Box::new(texture),
+            max_density,
+        }
+    }
+}
+
+impl DensityField for TextureDensity {
+    fn density(&self, point: V3<f32>) -> f32 {
+        let color = self.texture.value(0.0, 0.0, point);
+        self.max_density * (color.into_iter().sum::<f32>() / 3.0).clamp(0.0, 1.0)
+    }
+
+    fn majorant(&self) -> f32 {
+        self.max_density
+    }
+}
+
+/// Struct representing some primitive like Sphere that was changed into smoke or cloud with density changing inside.
+#[derive(Clone)]
+pub struct HeterogeneousMedium {
+    /// The primitive that was transformed into smoke.
+    boundary: Box<dyn HittablePdf>,
+    /// Some material that gives color when Ray scatters inside the object.
+    phase_function: usize,
+    /// Density of particles inside the object.
+    density: Box<dyn DensityField>,
+}
+
+impl HeterogeneousMedium {
+    /// Samples tentative collisions along the ray with majorant density, fictitious particles fill the medium up to the majorant.
+    ///
+    /// Returns ray parameter of the collision for which `collision` returned true.
+    ///
+    /// `collision` - Called with the ratio of real density to majorant at each tentative collision, returns true to stop tracking.
+    fn track(
+        &self,
+        ray: &Ray,
+        enter: f32,
+        exit: f32,
+        mut collision: impl FnMut(f32) -> bool,
+    ) -> Option<f32> {
+        let ray_length = ray.end.length();
+        let mut rng = rand::thread_rng();
+        let mut start = enter;
+
+        for (end, majorant) in self.density.majorant_segments(ray, enter, exit) {
+            if majorant > 0.0 {
+                let mut t = start;
+                loop {
+                    let random: f32 = rng.gen_range(0.0..1.0);
+                    t -= (1.0 - random).ln() / (majorant * ray_length);
+                    // Free paths are memoryless, so sampling continues from the end of the part with the next majorant
+                    if t >= end {
+                        break;
+                    }
+
+                    if collision(self.density.density(ray.point_at_param(t)) / majorant) {
+                        return Some(t);
+                    }
+                }
+            }
+            start = end;
+        }
+
+        None
+    }
+}
+
+impl Hittable for HeterogeneousMedium {
+    /// Samples the scattering point with delta tracking.
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        let (enter, exit) = boundary_interval(self.boundary.as_ref(), ray)?;
+
+        let mut rng = rand::thread_rng();
+        let t = self.track(ray, enter, exit, |ratio| rng.gen_range(0.0..1.0) < ratio)?;
+
+        Some(Hit::new(
+            ray,
+            V3::new(1.0, 0.0, 0.0),
+            t,
+            ray.point_at_param(t),
+            self.phase_function,
+            0.0,
+            0.0,
+        ))
+    }
+
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        // Particles only dim the light
+        self.transmittance(ray, t_max) == V3::default()
+    }
+
+    /// Estimates the fraction of light that goes through the medium with ratio tracking.
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> V3<f32> {
+        let setting = RaySetting {
+            ray_time: Interval::new(ray.setting.ray_time.min, t_max),
+            ..*ray.setting
+        };
+        let segment = Ray {
+            setting: &setting,
+            ..*ray
+        };
+
+        let mut transmittance = 1.0;
+        if let Some((enter, exit)) = boundary_interval(self.boundary.as_ref(), &segment) {
+            self.track(&segment, enter, exit, |ratio| {
+                transmittance *= 1.0 - ratio;
+                transmittance <= 0.0
+            });
+        }
+
+        let transmittance = transmittance.max(0.0);
+        V3::new(transmittance, transmittance, transmittance)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+/// Transforms object into HeterogeneousMedium object
+pub trait IntoHeterogeneousMedium {
+    /// Transforms object into HeterogeneousMedium object
+    ///
+    /// `density` - Density of particles inside the object.
+    ///
+    /// `phase_function` - Material that gives color when Ray scatter inside the object.
+    fn into_heterogeneous_medium<D: DensityField + 'static>(
+        self,
+        density: D,
+        phase_function: usize,
+    ) -> HeterogeneousMedium;
+}
+
+impl<T> IntoHeterogeneousMedium for T
+where
+    T: HittablePdf + 'static,
+{
+    fn into_heterogeneous_medium<D: DensityField + 'static>(
+        self,
+        density: D,
+        phase_function: usize,
+    ) -> HeterogeneousMedium {
+        HeterogeneousMedium {
+            boundary: Box::new(self),
+            phase_function,
+            density: Box::new(density),
+        }
+    }
+}
+
+impl Pdf for HeterogeneousMedium {
+    fn value(&self, origin: V3<f32>, direction: V3<f32>) -> f32 {
+        self.boundary.value(origin, direction)
+    }
+
+    fn generate(&self, origin: V3<f32>) -> V3<f32> {
+        self.boundary.generate(origin)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitive::Sphere;
+    use crate::texture::ConstantTexture;
+
+    /// Returns sphere of half of the majorant density, along its diameter light goes through optical depth of 2.
+    fn half_dense_sphere() -> HeterogeneousMedium {
+        let density = TextureDensity::new(
+            ConstantTexture {
+                color: V3::new(0.5, 0.5, 0.5),
+            },
+            2.0,
+        );
+        Sphere::new(V3::new(5.0, 0.0, 0.0), 1.0, 0).into_heterogeneous_medium(density, 0)
+    }
+
+    #[test]
+    fn ratio_tracking_test() {
+        let medium = half_dense_sphere();
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+        let ray = Ray::for_test(V3::default(), V3::new(1.0, 0.0, 0.0), &setting);
+
+        // Mean of the estimates is the transmittance of the constant density
+        let samples = 10000;
+        let transmittance = (0..samples)
+            .map(|_| medium.transmittance(&ray, 10.0).x)
+            .sum::<f32>()
+            / samples as f32;
+        assert!((transmittance - (-2.0f32).exp()).abs() < 0.01);
+
+        // Segment ending before the medium isn't dimmed
+        assert_eq!(medium.transmittance(&ray, 3.0), V3::new(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn delta_tracking_test() {
+        let medium = half_dense_sphere();
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+        let ray = Ray::for_test(V3::default(), V3::new(1.0, 0.0, 0.0), &setting);
+
+        // Ray collides with a particle with the probability that light doesn't go through
+        let samples = 10000;
+        let hits = (0..samples).filter(|_| medium.hit(&ray).is_some()).count();
+        assert!((hits as f32 / samples as f32 - (1.0 - (-2.0f32).exp())).abs() < 0.02);
+    }
+}
@@ -0,0 +1,360 @@
+use crate::hittables::Aabb;
+use crate::misc::{HittablePdf, Pdf};
+use crate::transform::MediumCoefficients;
+use crate::{Hit, Hittable, Ray, V3};
+
+use rand::Rng;
+
+/// How many media the ray can be inside of at once, deeper nested media are ignored.
+const MAX_NESTED_MEDIA: usize = 4;
+
+/// Substance filling the inside of an object, like glass, water or fog.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Medium {
+    /// Refractive index of the substance, used by dielectric surfaces on its border.
+    pub refractive_index: f32,
+    /// Probability per unit of distance that Ray collides with a particle, separately for each color channel, zero for clear substances.
+    pub extinction: V3<f32>,
+    /// Material that gives color when Ray scatters inside the medium.
+    pub phase_function: usize,
+}
+
+impl Medium {
+    /// Returns clear medium, like glass or water.
+    ///
+    /// `refractive_index` - Refractive index of the substance.
+    pub fn clear(refractive_index: f32) -> Self {
+        Medium {
+            refractive_index,
+            extinction: V3::default(),
+            phase_function: 0,
+        }
+    }
+
+    /// Returns medium with particles which scatter and absorb light, like fog or murky water.
+    ///
+    /// `refractive_index` - Refractive index of the substance.
+    ///
+    /// `coefficients` - Scattering and absorption coefficients of the medium, the phase function material should use its albedo.
+    ///
+    /// `phase_function` - Material that gives color when Ray scatters inside the medium.
+    pub fn scattering(
+        refractive_index: f32,
+        coefficients: MediumCoefficients,
+        phase_function: usize,
+    ) -> Self {
+        Medium {
+            refractive_index,
+            extinction: coefficients.extinction(),
+            phase_function,
+        }
+    }
+
+    /// Returns true if Ray can collide with particles inside the medium.
+    pub fn is_scattering(&self) -> bool {
+        self.extinction.into_iter().any(|sigma| sigma > 0.0)
+    }
+
+    /// Returns true if extinction differs between color channels, then Ray has to carry only one channel through the medium.
+    pub fn is_chromatic(&self) -> bool {
+        self.extinction.x != self.extinction.y || self.extinction.y != self.extinction.z
+    }
+
+    /// Returns sampled distance Ray travels before it collides with a particle.
+    ///
+    /// `channel` - Color channel carried by the Ray, needed only for chromatic media.
+    pub fn free_path(&self, channel: Option<u32>) -> f32 {
+        let density = self.extinction[channel.unwrap_or(0)];
+        if density <= 0.0 {
+            return f32::INFINITY;
+        }
+
+        let random: f32 = rand::thread_rng().gen_range(0.0..1.0);
+        -(1.0 - random).ln() / density
+    }
+
+    /// Returns fraction of light, separately for each color channel, which goes through the medium without colliding with a particle.
+    ///
+    /// `distance` - Distance travelled through the medium.
+    pub fn transmittance(&self, distance: f32) -> V3<f32> {
+        self.extinction
+            .into_iter()
+            .map(|sigma| (-sigma * distance).exp())
+            .collect()
+    }
+}
+
+impl Default for Medium {
+    /// Returns vacuum, which is also good enough approximation of air.
+    fn default() -> Self {
+        Medium::clear(1.0)
+    }
+}
+
+/// Medium inside of an object, with priority deciding which medium wins where objects overlap, like liquid and its glass container.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MediumInterface {
+    /// Medium inside of the object.
+    pub interior: Medium,
+    /// Higher priority medium fills the overlap of objects, surfaces of lower priority objects inside of it are ignored.
+    pub priority: u32,
+}
+
+/// Media the Ray is currently inside of, in the order it entered them.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MediumStack {
+    entries: [Option<MediumInterface>; MAX_NESTED_MEDIA],
+}
+
+impl MediumStack {
+    /// Returns medium the Ray travels through, the one with the highest priority, or the most recently entered of equal priorities.
+    pub fn current(&self) -> Medium {
+        self.entries
+            .iter()
+            .flatten()
+            .max_by_key(|entry| entry.priority)
+            .map(|entry| entry.interior)
+            .unwrap_or_default()
+    }
+
+    /// Returns highest priority among the media, if the stack isn't empty.
+    fn max_priority(&self) -> Option<u32> {
+        self.entries
+            .iter()
+            .flatten()
+            .map(|entry| entry.priority)
+            .max()
+    }
+
+    /// Returns stack after the Ray entered the object.
+    ///
+    /// `interface` - Medium inside of the object.
+    pub fn entered(mut self, interface: MediumInterface) -> Self {
+        if let Some(slot) = self.entries.iter_mut().find(|entry| entry.is_none()) {
+            *slot = Some(interface);
+        }
+        self
+    }
+
+    /// Returns stack after the Ray left the object.
+    ///
+    /// `interface` - Medium inside of the object.
+    pub fn exited(mut self, interface: MediumInterface) -> Self {
+        if let Some(index) = self
+            .entries
+            .iter()
+            .rposition(|entry| *entry == Some(interface))
+        {
+            // Keeps the entries packed at the start, so that their order is the order of entering
+            self.entries[index..].rotate_left(1);
+            self.entries[MAX_NESTED_MEDIA - 1] = None;
+        }
+        self
+    }
+
+    /// Returns stack after the Ray crossed the surface of the object.
+    ///
+    /// `interface` - Medium inside of the object.
+    ///
+    /// `front_face` - True if the Ray hit the object from the exterior.
+    pub fn crossed(self, interface: MediumInterface, front_face: bool) -> Self {
+        if front_face {
+            self.entered(interface)
+        } else {
+            self.exited(interface)
+        }
+    }
+
+    /// Returns true if the surface lies inside of a higher priority medium, so the Ray goes through it unchanged.
+    ///
+    /// `interface` - Medium inside of the object.
+    ///
+    /// `front_face` - True if the Ray hit the object from the exterior.
+    pub fn is_false_intersection(&self, interface: MediumInterface, front_face: bool) -> bool {
+        let others = if front_face {
+            *self
+        } else {
+            self.exited(interface)
+        };
+
+        others
+            .max_priority()
+            .is_some_and(|priority| priority > interface.priority)
+    }
+}
+
+/// Object whose inside is filled with a medium, Rays crossing its surface keep track of the media they are in.
+#[derive(Clone)]
+pub struct MediumBoundary {
+    /// Closed object that contains the medium.
+    object: Box<dyn HittablePdf>,
+    /// Medium inside of the object.
+    interface: MediumInterface,
+}
+
+impl Hittable for MediumBoundary {
+    fn hit(&self, ray: &Ray) -> Option<Hit> {
+        self.object.hit(ray).map(|hit| Hit {
+            interface: Some(self.interface),
+            ..hit
+        })
+    }
+
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        self.object.occluded(ray, t_max)
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> V3<f32> {
+        self.object.transmittance(ray, t_max)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.object.bounding_box()
+    }
+}
+
+impl Pdf for MediumBoundary {
+    fn value(&self, origin: V3<f32>, direction: V3<f32>) -> f32 {
+        self.object.value(origin, direction)
+    }
+
+    fn generate(&self, origin: V3<f32>) -> V3<f32> {
+        self.object.generate(origin)
+    }
+}
+
+/// Transforms object into MediumBoundary object
+pub trait IntoMediumBoundary {
+    /// Transforms object into MediumBoundary object, for a medium without visible surface, like fog, use `Dielectric` with the same refractive index as the medium outside.
+    ///
+    /// `interior` - Medium inside of the object.
+    ///
+    /// `priority` - Higher priority medium fills the overlap of objects.
+    fn into_medium_boundary(self, interior: Medium, priority: u32) -> MediumBoundary;
+}
+
+impl<T> IntoMediumBoundary for T
+where
+    T: HittablePdf + 'static,
+{
+    fn into_medium_boundary(self, interior: Medium, priority: u32) -> MediumBoundary {
+        MediumBoundary {
+            object: Box::new(self),
+            interface: MediumInterface { interior, priority },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittables::{FlatBvh, HittableList};
+    use crate::material::{Dielectric, LightSource, MaterialContainer};
+    use crate::misc::Interval;
+    use crate::primitive::Sphere;
+    use crate::texture::ConstantTexture;
+    use crate::{Camera, RaySetting, Scene};
+
+    #[test]
+    fn nested_priorities_test() {
+        let glass = MediumInterface {
+            interior: Medium::clear(1.5),
+            priority: 2,
+        };
+        let water = MediumInterface {
+            interior: Medium::clear(1.33),
+            priority: 1,
+        };
+
+        let in_glass = MediumStack::default().entered(glass);
+        assert_eq!(in_glass.current().refractive_index, 1.5);
+        // Water surface overlapping the wall of the glass is ignored
+        assert!(in_glass.is_false_intersection(water, true));
+
+        let in_water = in_glass.entered(water).exited(glass);
+        assert_eq!(in_water.current().refractive_index, 1.33);
+        assert!(!in_water.is_false_intersection(water, false));
+        assert_eq!(in_water.exited(water).current(), Medium::default());
+    }
+
+    #[test]
+    fn invisible_boundaries_test() {
+        let mut materials = MaterialContainer::default();
+        let clear = materials.add(Dielectric::new(1.0));
+        let light = materials.add(LightSource::new(Box::new(ConstantTexture {
+            color: V3::new(4.0, 4.0, 4.0),
+        })));
+
+        // Light behind two nested boundaries of media without visible surfaces
+        let mut objects = HittableList::new();
+        for (radius, priority) in [(1.0, 1), (2.0, 2)] {
+            objects.add(
+                Sphere::new(V3::new(0.0, 0.0, -5.0), radius, clear)
+                    .into_medium_boundary(Medium::clear(1.0), priority),
+            );
+        }
+        objects.add(Sphere::new(V3::new(0.0, 0.0, -10.0), 1.0, light));
+        let camera = Camera::new(
+            V3::default(),
+            V3::new(0.0, 0.0, -1.0),
+            V3::new(0.0, 1.0, 0.0),
+            1.0,
+            1.0,
+            0.0,
+            1.0,
+            0.0,
+            1.0,
+        );
+        let scene = Scene {
+            camera,
+            world: FlatBvh::new(&objects),
+            lights: None,
+            materials,
+        };
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+
+        // Going through the surfaces isn't a bounce, so even a single bounce reaches the light
+        let ray = scene.camera.get_ray(0.5, 0.5, &setting);
+        assert_eq!(ray.get_color(&scene), V3::new(4.0, 4.0, 4.0));
+    }
+
+    #[test]
+    fn camera_inside_medium_test() {
+        let water = MediumInterface {
+            interior: Medium::clear(1.33),
+            priority: 1,
+        };
+        let camera = Camera::new(
+            V3::default(),
+            V3::new(0.0, 0.0, -1.0),
+            V3::new(0.0, 1.0, 0.0),
+            40.0,
+            1.0,
+            0.0,
+            1.0,
+            0.0,
+            1.0,
+        )
+        .with_media(MediumStack::default().entered(water));
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval::new(0.001, f32::INFINITY),
+        };
+
+        // Rays of a camera under water start in the water and leave it at its surface
+        let ray = camera.get_ray(0.5, 0.5, &setting);
+        assert_eq!(ray.media.current(), water.interior);
+        let hit = Sphere::new(V3::default(), 1.0, 0)
+            .into_medium_boundary(water.interior, water.priority)
+            .hit(&ray)
+            .unwrap();
+        assert!(!hit.front_face);
+        let left = ray.media.crossed(hit.interface.unwrap(), hit.front_face);
+        assert_eq!(left.current(), Medium::default());
+    }
+}
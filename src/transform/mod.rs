@@ -1,11 +1,19 @@
 //! Various transformations of objects.
 
+mod affine;
 mod constant_medium;
 mod flip_face;
+mod heterogeneous_medium;
+mod medium_boundary;
 mod rotate;
 mod translate;
+mod voxel_grid;
 
+pub use affine::*;
 pub use constant_medium::*;
 pub use flip_face::*;
+pub use heterogeneous_medium::*;
+pub use medium_boundary::*;
 pub use rotate::*;
 pub use translate::*;
+pub use voxel_grid::*;
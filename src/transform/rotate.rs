@@ -1,5 +1,5 @@
 use crate::hittables::Aabb;
-use crate::misc::{HittablePdf, Interval, Pdf};
+use crate::misc::{gamma, HittablePdf, Interval, Pdf};
 use crate::{Hit, Hittable, Ray, V3};
 
 /// Represents a hittable object that was rotated by some angle around some axis.
@@ -31,18 +31,40 @@ impl Hittable for Rotated {
         let hit = self.hittable.hit(&rotated_ray);
 
         hit.map(|hit| {
-            Hit::new(
-                &rotated_ray,
-                hit.normal.rot(self.axis, self.sin_theta, self.cos_theta),
-                hit.t,
-                hit.point.rot(self.axis, self.sin_theta, self.cos_theta),
-                hit.material,
-                hit.u,
-                hit.v,
-            )
+            // Rotation may move the error to any axis, rounding of the quaternion formula adds a few ulps of the point
+            let error = hit.error.length() * (1.0 + gamma(3)) + hit.point.length() * gamma(12);
+            Hit {
+                error: V3::new(error, error, error),
+                normal: hit.normal.rot(self.axis, self.sin_theta, self.cos_theta),
+                point: hit.point.rot(self.axis, self.sin_theta, self.cos_theta),
+                dpdu: hit.dpdu.rot(self.axis, self.sin_theta, self.cos_theta),
+                dpdv: hit.dpdv.rot(self.axis, self.sin_theta, self.cos_theta),
+                direction: ray.end,
+                ..hit
+            }
         })
     }
 
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        let rotated_ray = Ray {
+            origin: ray.origin.rot(-self.axis, self.sin_theta, self.cos_theta),
+            end: ray.end.rot(-self.axis, self.sin_theta, self.cos_theta),
+            ..*ray
+        };
+
+        self.hittable.occluded(&rotated_ray, t_max)
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> V3<f32> {
+        let rotated_ray = Ray {
+            origin: ray.origin.rot(-self.axis, self.sin_theta, self.cos_theta),
+            end: ray.end.rot(-self.axis, self.sin_theta, self.cos_theta),
+            ..*ray
+        };
+
+        self.hittable.transmittance(&rotated_ray, t_max)
+    }
+
     fn bounding_box(&self) -> Aabb {
         self.bounding_box.clone()
     }
@@ -117,11 +139,16 @@ where
 }
 
 impl Pdf for Rotated {
-    fn value(&self, _origin: V3<f32>, _direction: V3<f32>) -> f32 {
-        todo!()
+    fn value(&self, origin: V3<f32>, direction: V3<f32>) -> f32 {
+        self.hittable.value(
+            origin.rot(-self.axis, self.sin_theta, self.cos_theta),
+            direction.rot(-self.axis, self.sin_theta, self.cos_theta),
+        )
     }
 
-    fn generate(&self, _origin: V3<f32>) -> V3<f32> {
-        todo!()
+    fn generate(&self, origin: V3<f32>) -> V3<f32> {
+        self.hittable
+            .generate(origin.rot(-self.axis, self.sin_theta, self.cos_theta))
+            .rot(self.axis, self.sin_theta, self.cos_theta)
     }
 }
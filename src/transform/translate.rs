@@ -1,5 +1,5 @@
 use crate::hittables::Aabb;
-use crate::misc::{HittablePdf, Pdf};
+use crate::misc::{gamma, HittablePdf, Pdf};
 use crate::{Hit, Hittable, Ray, V3};
 /// Represents translated object.
 #[derive(Clone)]
@@ -19,19 +19,31 @@ impl Hittable for Translated {
 
         let hit = self.hittable.hit(&offset_ray);
 
-        hit.map(|hit| {
-            Hit::new(
-                &offset_ray,
-                hit.normal,
-                hit.t,
-                hit.point + self.offset,
-                hit.material,
-                hit.u,
-                hit.v,
-            )
+        hit.map(|hit| Hit {
+            point: hit.point + self.offset,
+            error: hit.error + (hit.point + self.offset).abs() * gamma(1),
+            ..hit
         })
     }
 
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        let offset_ray = Ray {
+            origin: ray.origin - self.offset,
+            ..*ray
+        };
+
+        self.hittable.occluded(&offset_ray, t_max)
+    }
+
+    fn transmittance(&self, ray: &Ray, t_max: f32) -> V3<f32> {
+        let offset_ray = Ray {
+            origin: ray.origin - self.offset,
+            ..*ray
+        };
+
+        self.hittable.transmittance(&offset_ray, t_max)
+    }
+
     fn bounding_box(&self) -> Aabb {
         Aabb {
             x: self.hittable.bounding_box().x + self.offset.x,
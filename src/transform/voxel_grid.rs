@@ -0,0 +1,412 @@
+use crate::hittables::Aabb;
+use crate::primitive::HitBox;
+use crate::transform::{DensityField, HeterogeneousMedium, IntoHeterogeneousMedium};
+use crate::{Ray, V3};
+
+use std::io::{Error, ErrorKind};
+
+/// Number of voxels along each axis covered by one cell of the majorant grid.
+const MAJORANT_CELL: usize = 8;
+
+/// Type of values stored in raw voxel files.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RawFormat {
+    /// One byte per voxel, 255 is density 1.
+    U8,
+    /// Little endian 32 bit float per voxel.
+    F32,
+}
+
+/// Dense grid of densities filling a box, interpolated trilinearly between voxel centers.
+#[derive(Clone)]
+pub struct VoxelGrid {
+    /// Number of voxels along x, y and z axes.
+    resolution: [usize; 3],
+    /// Corner of the box with the smallest coordinates.
+    min: V3<f32>,
+    /// Corner of the box with the biggest coordinates.
+    max: V3<f32>,
+    /// Densities, x changes fastest, then y, then z.
+    data: Vec<f32>,
+    /// Multiplier of all densities.
+    scale: f32,
+    /// Number of majorant cells along each axis.
+    majorant_resolution: [usize; 3],
+    /// Maximal density that interpolation can give in each majorant cell.
+    majorants: Vec<f32>,
+}
+
+impl VoxelGrid {
+    /// Returns new voxel grid.
+    ///
+    /// `resolution` - Number of voxels along x, y and z axes.
+    ///
+    /// `bounds` - Box filled by the grid.
+    ///
+    /// `data` - Densities, x changes fastest, then y, then z.
+    pub fn new(resolution: [usize; 3], bounds: Aabb, data: Vec<f32>) -> Result<VoxelGrid, Error> {
+        if resolution.contains(&0) || data.len() != resolution.iter().product::<usize>() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "Voxel data doesn't match the resolution",
+            ));
+        }
+
+        let majorant_resolution = resolution.map(|size| size.div_ceil(MAJORANT_CELL));
+        let mut grid = VoxelGrid {
+            resolution,
+            min: V3::new(bounds.x.min, bounds.y.min, bounds.z.min),
+            max: V3::new(bounds.x.max, bounds.y.max, bounds.z.max),
+            data,
+            scale: 1.0,
+            majorant_resolution,
+            majorants: vec![],
+        };
+        grid.majorants = grid.build_majorants();
+
+        Ok(grid)
+    }
+
+    /// Returns voxel grid read from a file with raw values and no header.
+    ///
+    /// `file` - Path to the file.
+    ///
+    /// `resolution` - Number of voxels along x, y and z axes.
+    ///
+    /// `bounds` - Box filled by the grid.
+    ///
+    /// `format` - Type of values stored in the file.
+    pub fn load_raw(
+        file: &str,
+        resolution: [usize; 3],
+        bounds: Aabb,
+        format: RawFormat,
+    ) -> Result<VoxelGrid, Error> {
+        let bytes = std::fs::read(file)?;
+        let data = match format {
+            RawFormat::U8 => bytes.iter().map(|&b| f32::from(b) / 255.0).collect(),
+            RawFormat::F32 => bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect(),
+        };
+
+        VoxelGrid::new(resolution, bounds, data)
+    }
+
+    /// Returns voxel grid read from a file in Mitsuba grid volume format, channels of multichannel grids are averaged.
+    ///
+    /// `file` - Path to the `.vol` file.
+    pub fn load_mitsuba(file: &str) -> Result<VoxelGrid, Error> {
+        VoxelGrid::parse_mitsuba(&std::fs::read(file)?)
+    }
+
+    fn parse_mitsuba(bytes: &[u8]) -> Result<VoxelGrid, Error> {
+        let invalid = |message| Error::new(ErrorKind::InvalidData, message);
+
+        if bytes.len() < 48 || &bytes[0..3] != b"VOL" || bytes[3] != 3 {
+            return Err(invalid("Not a Mitsuba volume of version 3"));
+        }
+
+        let int = |offset: usize| {
+            i32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+        let float = |offset: usize| {
+            f32::from_le_bytes([
+                bytes[offset],
+                bytes[offset + 1],
+                bytes[offset + 2],
+                bytes[offset + 3],
+            ])
+        };
+
+        // Only 32 bit float encoding is supported
+        if int(4) != 1 {
+            return Err(invalid("Unsupported Mitsuba volume encoding"));
+        }
+
+        let dimensions = [int(8), int(12), int(16), int(20)];
+        if dimensions.iter().any(|&size| size <= 0) {
+            return Err(invalid("Invalid Mitsuba volume size"));
+        }
+        let [x, y, z, channels] = dimensions.map(|size| size as usize);
+
+        let bounds = Aabb::new(
+            V3::new(float(24), float(28), float(32)),
+            V3::new(float(36), float(40), float(44)),
+        );
+
+        let values = &bytes[48..];
+        if values.len() < x * y * z * channels * 4 {
+            return Err(invalid("Mitsuba volume data is too short"));
+        }
+
+        let data = values
+            .chunks_exact(channels * 4)
+            .take(x * y * z)
+            .map(|voxel| {
+                voxel
+                    .chunks_exact(4)
+                    .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .sum::<f32>()
+                    / channels as f32
+            })
+            .collect();
+
+        VoxelGrid::new([x, y, z], bounds, data)
+    }
+
+    /// Returns voxel grid with all densities multiplied.
+    ///
+    /// `scale` - Multiplier of densities.
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    /// Returns medium filling the box of the grid.
+    ///
+    /// `phase_function` - Material that gives color when Ray scatter inside the medium.
+    pub fn into_medium(self, phase_function: usize) -> HeterogeneousMedium {
+        HitBox::new(self.min, self.max, phase_function)
+            .into_heterogeneous_medium(self, phase_function)
+    }
+
+    fn voxel(&self, x: usize, y: usize, z: usize) -> f32 {
+        self.data[(z * self.resolution[1] + y) * self.resolution[0] + x]
+    }
+
+    /// Returns size of one voxel.
+    fn voxel_size(&self) -> V3<f32> {
+        (self.max - self.min).div(V3::new(
+            self.resolution[0] as f32,
+            self.resolution[1] as f32,
+            self.resolution[2] as f32,
+        ))
+    }
+
+    /// Returns maximum of voxels that influence interpolation inside each majorant cell.
+    fn build_majorants(&self) -> Vec<f32> {
+        let [mx, my, mz] = self.majorant_resolution;
+        // Interpolation near the cell border reads one voxel from the neighbouring cell
+        let range = |cell: usize, axis: usize| {
+            let start = (cell * MAJORANT_CELL).saturating_sub(1);
+            let end = ((cell + 1) * MAJORANT_CELL + 1).min(self.resolution[axis]);
+            start..end
+        };
+
+        let mut majorants = Vec::with_capacity(mx * my * mz);
+        for cz in 0..mz {
+            for cy in 0..my {
+                for cx in 0..mx {
+                    let mut majorant = 0.0f32;
+                    for z in range(cz, 2) {
+                        for y in range(cy, 1) {
+                            for x in range(cx, 0) {
+                                majorant = majorant.max(self.voxel(x, y, z));
+                            }
+                        }
+                    }
+                    majorants.push(majorant);
+                }
+            }
+        }
+
+        majorants
+    }
+
+    /// Returns parameters at which the ray enters and leaves the box of the grid.
+    fn box_interval(&self, ray: &Ray) -> Option<(f32, f32)> {
+        let mut enter = f32::NEG_INFINITY;
+        let mut exit = f32::INFINITY;
+
+        for axis in 0..3 {
+            let inverse = 1.0 / ray.end[axis as u32];
+            let t0 = (self.min[axis as u32] - ray.origin[axis as u32]) * inverse;
+            let t1 = (self.max[axis as u32] - ray.origin[axis as u32]) * inverse;
+            enter = enter.max(t0.min(t1));
+            exit = exit.min(t0.max(t1));
+        }
+
+        if enter <= exit {
+            Some((enter, exit))
+        } else {
+            None
+        }
+    }
+}
+
+impl DensityField for VoxelGrid {
+    fn density(&self, point: V3<f32>) -> f32 {
+        let inside = (0..3).all(|axis| (self.min[axis]..=self.max[axis]).contains(&point[axis]));
+        if !inside {
+            return 0.0;
+        }
+
+        // Position in voxel units, relative to the center of the first voxel
+        let position = (point - self.min).div(self.voxel_size()) - V3::new(0.5, 0.5, 0.5);
+
+        let mut indices = [(0, 0); 3];
+        let mut weights = [0.0; 3];
+        for axis in 0..3 {
+            let last = self.resolution[axis] - 1;
+            let coordinate = position[axis as u32].clamp(0.0, last as f32);
+            let index = (coordinate.floor() as usize).min(last);
+            indices[axis] = (index, (index + 1).min(last));
+            weights[axis] = coordinate - index as f32;
+        }
+
+        let mut density = 0.0;
+        for corner in 0..8 {
+            let pick = |axis: usize| {
+                if corner >> axis & 1 == 1 {
+                    (indices[axis].1, weights[axis])
+                } else {
+                    (indices[axis].0, 1.0 - weights[axis])
+                }
+            };
+            let ((x, wx), (y, wy), (z, wz)) = (pick(0), pick(1), pick(2));
+            density += wx * wy * wz * self.voxel(x, y, z);
+        }
+
+        density * self.scale
+    }
+
+    fn majorant(&self) -> f32 {
+        self.majorants.iter().cloned().fold(0.0, f32::max) * self.scale
+    }
+
+    /// Walks the majorant grid along the ray with 3D DDA, parts of the ray outside of the grid have zero majorant.
+    fn majorant_segments(&self, ray: &Ray, enter: f32, exit: f32) -> Vec<(f32, f32)> {
+        let (start, end) = match self.box_interval(ray) {
+            Some((box_enter, box_exit)) if box_enter.max(enter) < box_exit.min(exit) => {
+                (box_enter.max(enter), box_exit.min(exit))
+            }
+            _ => return vec![(exit, 0.0)],
+        };
+
+        let mut segments = vec![(start, 0.0)];
+
+        let cell_size = self.voxel_size() * MAJORANT_CELL as f32;
+        let origin = ray.point_at_param(start);
+
+        let mut cell = [0; 3];
+        let mut step = [0; 3];
+        let mut next_crossing = [f32::INFINITY; 3];
+        let mut crossing_delta = [f32::INFINITY; 3];
+
+        for axis in 0..3 {
+            let a = axis as u32;
+            let position = (origin[a] - self.min[a]) / cell_size[a];
+            let last = self.majorant_resolution[axis] as i64 - 1;
+            cell[axis] = (position.floor() as i64).clamp(0, last);
+
+            let direction = ray.end[a];
+            if direction > 0.0 {
+                step[axis] = 1;
+                next_crossing[axis] = start
+                    + ((cell[axis] + 1) as f32 * cell_size[a] + self.min[a] - origin[a])
+                        / direction;
+                crossing_delta[axis] = cell_size[a] / direction;
+            } else if direction < 0.0 {
+                step[axis] = -1;
+                next_crossing[axis] = start
+                    + (cell[axis] as f32 * cell_size[a] + self.min[a] - origin[a]) / direction;
+                crossing_delta[axis] = -cell_size[a] / direction;
+            }
+        }
+
+        loop {
+            let index = (cell[2] as usize * self.majorant_resolution[1] + cell[1] as usize)
+                * self.majorant_resolution[0]
+                + cell[0] as usize;
+            let majorant = self.majorants[index] * self.scale;
+
+            let axis = (0..3)
+                .min_by(|&a, &b| next_crossing[a].total_cmp(&next_crossing[b]))
+                .unwrap_or(0);
+            let crossing = next_crossing[axis].min(end);
+            segments.push((crossing, majorant));
+
+            if crossing >= end {
+                break;
+            }
+
+            cell[axis] += step[axis];
+            if cell[axis] < 0 || cell[axis] >= self.majorant_resolution[axis] as i64 {
+                // Ray left the grid earlier than expected because of rounding, the rest is bounded by the global majorant
+                segments.push((end, self.majorant()));
+                break;
+            }
+            next_crossing[axis] += crossing_delta[axis];
+        }
+
+        segments.push((exit, 0.0));
+        segments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::misc::Interval;
+    use crate::RaySetting;
+
+    fn grid() -> VoxelGrid {
+        let resolution = [20, 4, 4];
+        let data = (0..320)
+            .map(|i| if i % 20 < 10 { 0.0 } else { (i % 20) as f32 })
+            .collect();
+        VoxelGrid::new(
+            resolution,
+            Aabb::new(V3::new(0.0, 0.0, 0.0), V3::new(20.0, 4.0, 4.0)),
+            data,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn interpolation_test() {
+        let grid = grid();
+
+        assert_eq!(grid.density(V3::new(12.5, 2.0, 2.0)), 12.0);
+        assert_eq!(grid.density(V3::new(13.0, 2.0, 2.0)), 12.5);
+        assert_eq!(grid.density(V3::new(30.0, 2.0, 2.0)), 0.0);
+    }
+
+    #[test]
+    fn majorant_segments_test() {
+        let grid = grid();
+        let setting = RaySetting {
+            background_color: V3::default(),
+            depth: 1,
+            ray_time: Interval {
+                min: 0.0,
+                max: 100.0,
+            },
+        };
+        let ray = Ray::for_test(V3::new(-5.0, 2.0, 2.0), V3::new(1.0, 0.0, 0.0), &setting);
+
+        let segments = grid.majorant_segments(&ray, 0.0, 40.0);
+
+        assert_eq!(segments.last(), Some(&(40.0, 0.0)));
+        for window in segments.windows(2) {
+            assert!(window[0].0 <= window[1].0);
+        }
+        // Every tentative collision point is covered by a majorant not smaller than the density
+        let mut start = 0.0;
+        for &(end, majorant) in &segments {
+            let mut t = start + 0.05;
+            while t < end {
+                assert!(grid.density(ray.point_at_param(t)) <= majorant + 1e-4);
+                t += 0.1;
+            }
+            start = end;
+        }
+    }
+}
@@ -201,6 +201,11 @@ impl<
     pub fn max_component(self) -> T {
         self.x.max(self.y.max(self.z))
     }
+
+    /// Returns vector with absolute values of components.
+    pub fn abs(self) -> V3<T> {
+        V3::new(self.x.abs(), self.y.abs(), self.z.abs())
+    }
 }
 
 impl V3<f32> {
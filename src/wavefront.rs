@@ -0,0 +1,454 @@
+use crate::hit::*;
+use crate::material::ScatterRecord;
+use crate::misc::Ppm;
+use crate::ray::{channel_mask, ShadowRay};
+use crate::render::pixel_color;
+use crate::{ImageConfig, Ray, Render, Scene, V3};
+
+use rand::Rng;
+use rayon::prelude::*;
+
+/// Path traced by the wavefront renderer, waiting in a queue for the next stage.
+#[derive(Clone, Copy)]
+struct Path<'setting> {
+    /// Ray of the current segment of the path.
+    ray: Ray<'setting>,
+    /// Product of attenuations of all previous bounces divided by their pdfs, light found by the path is multiplied by it.
+    throughput: V3<f32>,
+    /// Index of the pixel in the image, row by row.
+    pixel: usize,
+    /// Number of bounces of the path.
+    depth: u16,
+    /// Pdf with which the material at the previous hit sampled direction of the Ray, or None if light couldn't be sampled there.
+    scattering_pdf: Option<f32>,
+}
+
+/// Work produced by shading one hit.
+struct Shaded<'setting> {
+    /// Light emitted by the hit object, multiplied by the throughput.
+    emitted: V3<f32>,
+    /// Shadow ray toward a light, with the light multiplied by the throughput.
+    shadow_ray: Option<ShadowRay<'setting>>,
+    /// Path continuing from the hit.
+    path: Option<Path<'setting>>,
+}
+
+/// Queues of paths between the stages, kept for all batches, so their memory is reused.
+#[derive(Default)]
+struct Queues<'setting> {
+    /// Paths waiting for intersection.
+    paths: Vec<Path<'setting>>,
+    /// Hits of the paths waiting for intersection, in the same order.
+    traced: Vec<Option<Hit>>,
+    /// Paths, which have to be intersected again.
+    retraced: Vec<Path<'setting>>,
+    /// Paths which hit an object with their hits, waiting for shading.
+    hits: Vec<(Path<'setting>, Hit)>,
+    /// Materials and indices of the hits, sorted by the materials.
+    order: Vec<(u32, u32)>,
+    /// Work produced by shading the hits, in the sorted order.
+    shaded: Vec<Shaded<'setting>>,
+    /// Paths continuing from the hits, by octants of their directions.
+    octants: [Vec<Path<'setting>>; 8],
+    /// Shadow rays waiting for tracing, with their pixels.
+    shadow_rays: Vec<(usize, ShadowRay<'setting>)>,
+    /// Light found by the paths, with their pixels.
+    lights: Vec<(usize, V3<f32>)>,
+}
+
+/// Renderer tracing large batches of paths stage by stage, instead of one path after another.
+///
+/// Each stage, generating rays from the camera, intersecting them with the scene, shading hits grouped by material and tracing shadow rays, is done for the whole batch at once, which keeps the same code and data in the caches.
+/// Gives the same image as rendering the [`Scene`] directly, up to the noise.
+pub struct Wavefront<'scene, W> {
+    scene: &'scene Scene<W>,
+    /// Number of paths traced at once.
+    batch_size: usize,
+}
+
+impl<'scene, W: Hittable> Wavefront<'scene, W> {
+    /// Returns wavefront renderer of the scene.
+    ///
+    /// `scene` - Scene to render.
+    pub fn new(scene: &'scene Scene<W>) -> Self {
+        Wavefront {
+            scene,
+            batch_size: 1 << 16,
+        }
+    }
+
+    /// Returns renderer tracing given number of paths at once, bigger batches are more coherent, but take more memory.
+    ///
+    /// `batch_size` - Number of paths traced at once.
+    pub fn with_batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// Returns sums of the colors of all samples of each pixel, row by row.
+    ///
+    /// `image_config` - Configuration of rendered image.
+    fn trace(&self, image_config: &ImageConfig) -> Vec<V3<f32>> {
+        let (nx, ny) = (image_config.nx as usize, image_config.ny as usize);
+        let samples = nx * ny * image_config.samples_per_pixel as usize;
+        let pixel_cone = self.scene.camera.pixel_cone(image_config.ny);
+        let mut colors = vec![V3::default(); nx * ny];
+        let mut queues = Queues::default();
+
+        // Samples of neighbouring pixels are in the same batch, so their rays are coherent
+        for start in (0..samples).step_by(self.batch_size) {
+            let batch = start..samples.min(start + self.batch_size);
+            queues.paths.par_extend(batch.into_par_iter().map(|sample| {
+                let pixel = sample % (nx * ny);
+                let mut rng = rand::thread_rng();
+                let u = ((pixel % nx) as f32 + rng.gen::<f32>()) / nx as f32;
+                let v = ((pixel / nx) as f32 + rng.gen::<f32>()) / ny as f32;
+
+                Path {
+                    ray: self
+                        .scene
+                        .camera
+                        .get_ray(u, v, &image_config.ray_setting)
+                        .with_cone(pixel_cone)
+                        .with_materials(&self.scene.materials),
+                    throughput: V3::new(1.0, 1.0, 1.0),
+                    pixel,
+                    depth: 0,
+                    scattering_pdf: None,
+                }
+            }));
+
+            while !queues.paths.is_empty() {
+                self.intersect(&mut queues);
+                self.shade(&mut queues);
+                self.trace_shadow_rays(&mut queues);
+
+                for (pixel, light) in queues.lights.drain(..) {
+                    colors[pixel] = colors[pixel] + light;
+                }
+            }
+        }
+
+        colors
+    }
+
+    /// Moves paths which hit an object to the queue of hits, and adds light of the background reached by the other paths.
+    ///
+    /// `queues` - Queues of paths between the stages.
+    fn intersect(&self, queues: &mut Queues) {
+        // Paths are traced again after they picked a color channel or went through a surface, which doesn't exist for them
+        while !queues.paths.is_empty() {
+            queues
+                .traced
+                .par_extend(queues.paths.par_iter_mut().map(|path| {
+                    // Free paths in a medium with colored extinction differ between channels, so the Ray has to pick one of them.
+                    let medium = path.ray.media.current();
+                    if path.ray.channel.is_none() && medium.is_scattering() && medium.is_chromatic()
+                    {
+                        path.pick_channel(rand::thread_rng().gen_range(0..3));
+                    }
+                    path.ray.trace(self.scene)
+                }));
+
+            for (mut path, hit) in queues.paths.drain(..).zip(queues.traced.drain(..)) {
+                match hit {
+                    Some(Hit {
+                        channel: Some(channel),
+                        ..
+                    }) if path.ray.channel.is_none() => {
+                        path.pick_channel(channel);
+                        queues.retraced.push(path);
+                    }
+                    Some(
+                        hit @ Hit {
+                            interface: Some(interface),
+                            front_face,
+                            ..
+                        },
+                    ) if path.ray.media.is_false_intersection(interface, front_face) => {
+                        path.ray = path.ray.crossed(&hit, interface, front_face);
+                        path.scattering_pdf = None;
+                        queues.retraced.push(path);
+                    }
+                    Some(hit) => queues.hits.push((path, hit)),
+                    None => queues.lights.push((
+                        path.pixel,
+                        path.throughput.hadamard(path.ray.setting.background_color),
+                    )),
+                }
+            }
+
+            std::mem::swap(&mut queues.paths, &mut queues.retraced);
+        }
+    }
+
+    /// Shades the queued hits, queues shadow rays and paths continuing from the hits, ordered by their directions.
+    ///
+    /// `queues` - Queues of paths between the stages.
+    fn shade(&self, queues: &mut Queues) {
+        // Hits of the same material are shaded together, only their indices are sorted, not the hits themselves
+        queues.order.clear();
+        queues.order.extend(
+            queues
+                .hits
+                .iter()
+                .enumerate()
+                .map(|(index, (_, hit))| (hit.material as u32, index as u32)),
+        );
+        queues.order.par_sort_unstable();
+
+        let hits = &queues.hits;
+        queues
+            .shaded
+            .par_extend(queues.order.par_iter().map(|&(_, index)| {
+                let (path, hit) = &hits[index as usize];
+                self.shade_hit(*path, hit)
+            }));
+
+        // Rays going into the same octant are traced together
+        for (&(_, index), shaded) in queues.order.iter().zip(queues.shaded.drain(..)) {
+            let pixel = queues.hits[index as usize].0.pixel;
+            queues.lights.push((pixel, shaded.emitted));
+            if let Some(shadow_ray) = shaded.shadow_ray {
+                queues.shadow_rays.push((pixel, shadow_ray));
+            }
+            if let Some(path) = shaded.path {
+                let end = path.ray.end;
+                let octant = (end.x < 0.0) as usize
+                    | ((end.y < 0.0) as usize) << 1
+                    | ((end.z < 0.0) as usize) << 2;
+                queues.octants[octant].push(path);
+            }
+        }
+        queues.hits.clear();
+        for octant in &mut queues.octants {
+            queues.paths.append(octant);
+        }
+    }
+
+    /// Adds light brought by the queued shadow rays through the objects in their way.
+    ///
+    /// `queues` - Queues of paths between the stages.
+    fn trace_shadow_rays(&self, queues: &mut Queues) {
+        queues.lights.par_extend(
+            queues
+                .shadow_rays
+                .par_drain(..)
+                .map(|(pixel, shadow_ray)| (pixel, shadow_ray.traced_light(self.scene)))
+                .filter(|(_, light)| *light != V3::default()),
+        );
+    }
+
+    /// Returns work produced by shading one hit, the same as a bounce of [`Ray::get_color`].
+    ///
+    /// `path` - Path which hit the object.
+    ///
+    /// `hit` - Hit of the path.
+    fn shade_hit<'setting>(&self, path: Path<'setting>, hit: &Hit) -> Shaded<'setting> {
+        let ray = &path.ray;
+        let material = self.scene.materials.get(hit.material);
+        let mut shaded = Shaded {
+            emitted: V3::default(),
+            shadow_ray: None,
+            path: None,
+        };
+
+        match material.scatter(ray, hit) {
+            // Invisible surface only changes the media the Ray is in, it isn't a bounce.
+            Some(ScatterRecord {
+                specular_ray: Some(specular_ray),
+                invisible: true,
+                ..
+            }) => {
+                shaded.path = Some(Path {
+                    ray: Ray {
+                        setting: ray.setting,
+                        materials: ray.materials,
+                        ..specular_ray
+                    },
+                    scattering_pdf: None,
+                    ..path
+                });
+            }
+            // If the ray bounce limit is exceeded, no more light is gathered.
+            Some(_) if path.depth >= ray.setting.depth => {}
+            Some(ScatterRecord {
+                specular_ray: Some(specular_ray),
+                attenuation,
+                ..
+            }) => {
+                shaded.path = Some(Path {
+                    ray: Ray {
+                        setting: ray.setting,
+                        materials: ray.materials,
+                        ..specular_ray
+                    },
+                    throughput: path.throughput.hadamard(attenuation),
+                    depth: path.depth + 1,
+                    scattering_pdf: None,
+                    ..path
+                });
+            }
+            // Light is sampled directly and by the material, both samples are weighted by multiple importance sampling.
+            Some(scatter_record) => {
+                shaded.emitted = path.throughput.hadamard(ray.emitted(
+                    self.scene,
+                    material,
+                    hit,
+                    path.scattering_pdf,
+                ));
+                shaded.shadow_ray = ray
+                    .sample_light(self.scene, hit, material, &scatter_record)
+                    .map(|shadow_ray| ShadowRay {
+                        light: path.throughput.hadamard(shadow_ray.light),
+                        ..shadow_ray
+                    });
+
+                let direction = scatter_record.pdf.generate(hit.point);
+                let pdf = scatter_record.pdf.value(hit.point, direction);
+                if pdf > 0.0 {
+                    let scattered_ray = ray.scattered(hit, direction);
+                    let weight = scatter_record.attenuation
+                        * material.scattering_pdf(ray, hit, &scattered_ray)
+                        / pdf;
+
+                    shaded.path = Some(Path {
+                        ray: scattered_ray,
+                        throughput: path.throughput.hadamard(weight),
+                        depth: path.depth + 1,
+                        scattering_pdf: Some(pdf),
+                        ..path
+                    });
+                }
+            }
+            // If scatter hasn't produced Ray, at example in case of absorbing the Ray, then the resulting color of the Ray is emitted light by the object.
+            None => {
+                shaded.emitted = path.throughput.hadamard(ray.emitted(
+                    self.scene,
+                    material,
+                    hit,
+                    path.scattering_pdf,
+                ))
+            }
+        }
+
+        shaded
+    }
+}
+
+impl Path<'_> {
+    /// Makes the Ray carry only one color channel, weighted by the probability of picking that channel.
+    ///
+    /// `channel` - Picked color channel.
+    fn pick_channel(&mut self, channel: u32) {
+        self.ray.channel = Some(channel);
+        self.throughput = self.throughput.hadamard(channel_mask(channel));
+    }
+}
+
+impl<W: Hittable> Render for Wavefront<'_, W> {
+    fn render(&self, image_config: ImageConfig) -> Ppm {
+        let mut output_file = Ppm::new(image_config.ny, image_config.nx);
+
+        // Average color of subpixels
+        let scale = 1.0 / (image_config.samples_per_pixel as f32);
+
+        for (pixel, color) in self.trace(&image_config).into_iter().enumerate() {
+            let (x, y) = (
+                pixel as u32 % image_config.nx,
+                pixel as u32 / image_config.nx,
+            );
+            output_file.set_pixel(x, y, pixel_color(color, scale));
+        }
+
+        output_file
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hittables::{FlatBvh, HittableList};
+    use crate::material::{Lambertian, LightSource, MaterialContainer};
+    use crate::misc::Interval;
+    use crate::primitive::{Sphere, XZrect};
+    use crate::texture::ConstantTexture;
+    use crate::transform::IntoFlipFace;
+    use crate::{Camera, RaySetting};
+
+    #[test]
+    fn same_image_as_recursive_test() {
+        let mut hittable = HittableList::new();
+        let mut lights = HittableList::new();
+        let mut materials = MaterialContainer::default();
+        let white = materials.add(Lambertian::new(Box::new(ConstantTexture {
+            color: V3::new(0.7, 0.7, 0.7),
+        })));
+        let light = materials.add(LightSource::new(Box::new(ConstantTexture {
+            color: V3::new(4.0, 4.0, 4.0),
+        })));
+        hittable.add(XZrect::new(-10.0, 10.0, -10.0, 10.0, 0.0, white));
+        hittable.add(Sphere::new(V3::new(0.0, 1.0, 0.0), 1.0, white));
+        lights.add_shared(hittable.add(XZrect::new(-1.0, 1.0, -1.0, 1.0, 3.0, light).flip_face()));
+
+        let scene = Scene {
+            camera: Camera::new(
+                V3::new(0.0, 2.0, -6.0),
+                V3::new(0.0, 1.0, 0.0),
+                V3::new(0.0, 1.0, 0.0),
+                40.0,
+                1.0,
+                0.0,
+                10.0,
+                0.0,
+                1.0,
+            ),
+            world: FlatBvh::new(&hittable),
+            lights: Some(lights),
+            materials,
+        };
+        let image_config = ImageConfig {
+            nx: 8,
+            ny: 8,
+            samples_per_pixel: 1024,
+            ray_setting: RaySetting {
+                background_color: V3::new(0.1, 0.1, 0.1),
+                depth: 8,
+                ray_time: Interval::new(0.0, 100.0),
+            },
+            name: "wavefront",
+        };
+
+        let mean = |colors: Vec<V3<f32>>| {
+            colors
+                .iter()
+                .map(|color| color.x + color.y + color.z)
+                .sum::<f32>()
+                / colors.len() as f32
+        };
+        let wavefront = mean(
+            Wavefront::new(&scene)
+                .with_batch_size(1000)
+                .trace(&image_config),
+        ) / 1024.0;
+
+        let pixel_cone = scene.camera.pixel_cone(image_config.ny);
+        let recursive = mean(
+            (0..64 * 1024)
+                .map(|sample| {
+                    let pixel = sample % 64;
+                    let u = ((pixel % 8) as f32 + rand::random::<f32>()) / 8.0;
+                    let v = ((pixel / 8) as f32 + rand::random::<f32>()) / 8.0;
+                    scene
+                        .camera
+                        .get_ray(u, v, &image_config.ray_setting)
+                        .with_cone(pixel_cone)
+                        .with_materials(&scene.materials)
+                        .get_color(&scene)
+                })
+                .collect(),
+        );
+
+        assert!((wavefront - recursive).abs() < 0.05 * recursive);
+    }
+}